@@ -0,0 +1,142 @@
+//! Error kinds surfaced by the CLI and the process exit code for each. The
+//! table is documented in `docs/spec.md`; codes are stable once released.
+
+use belljar_core::CoreError;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Code {
+    /// Anything not covered below (I/O, unexpected failures).
+    Failure = 1,
+    /// Invalid arguments; clap exits with this code on its own.
+    Usage = 2,
+    NotFound = 3,
+    Conflict = 4,
+    /// Refused to remove a session with unsaved work.
+    Unsafe = 5,
+    Git = 6,
+    Compose = 7,
+    Tmux = 8,
+    /// Registry locked, unreadable or written by a newer belljar.
+    Registry = 9,
+    Config = 10,
+}
+
+impl Code {
+    /// Stable name used in `--json` error documents.
+    pub fn name(self) -> &'static str {
+        match self {
+            Code::Failure => "failure",
+            Code::Usage => "usage",
+            Code::NotFound => "not_found",
+            Code::Conflict => "conflict",
+            Code::Unsafe => "unsafe",
+            Code::Git => "git",
+            Code::Compose => "compose",
+            Code::Tmux => "tmux",
+            Code::Registry => "registry",
+            Code::Config => "config",
+        }
+    }
+}
+
+pub fn code_of(e: &CoreError) -> Code {
+    match e {
+        CoreError::Io(_) => Code::Failure,
+        CoreError::Serde(_)
+        | CoreError::NoRegistryPath
+        | CoreError::RegistryLocked(_)
+        | CoreError::RegistryTooNew { .. } => Code::Registry,
+        CoreError::Compose(_) | CoreError::NoComposeFiles | CoreError::ServiceNotReady { .. } => {
+            Code::Compose
+        }
+        CoreError::Git(_) => Code::Git,
+        CoreError::NotFound { .. } => Code::NotFound,
+        CoreError::TmuxNotFound | CoreError::Tmux(_) => Code::Tmux,
+        CoreError::LabelConflict { .. } => Code::Conflict,
+        CoreError::UnsafeRemoval { .. } => Code::Unsafe,
+        CoreError::Config(_) => Code::Config,
+        CoreError::UnknownServices { .. }
+        | CoreError::UnknownStack { .. }
+        | CoreError::InvalidLimit(_)
+        | CoreError::InvalidName { .. } => Code::Usage,
+        CoreError::StepFailed { source, .. } => code_of(source),
+    }
+}
+
+/// A CLI failure with its exit code. `reported` is set when the details were
+/// already printed (e.g. as a `--json` result) and only the exit code is left.
+#[derive(Debug)]
+pub struct CliError {
+    pub code: Code,
+    pub message: String,
+    pub reported: bool,
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CliError {}
+
+/// Wrap a core error with context, keeping the exit code of its kind.
+pub fn core(context: &str, e: CoreError) -> anyhow::Error {
+    CliError {
+        code: code_of(&e),
+        message: format!("{context}: {e}"),
+        reported: false,
+    }
+    .into()
+}
+
+pub fn fail(code: Code, message: impl Into<String>) -> anyhow::Error {
+    CliError {
+        code,
+        message: message.into(),
+        reported: false,
+    }
+    .into()
+}
+
+pub fn not_found(kind: &'static str, label: &str) -> anyhow::Error {
+    core_bare(CoreError::NotFound {
+        kind,
+        label: label.to_string(),
+    })
+}
+
+/// A core error without extra context.
+pub fn core_bare(e: CoreError) -> anyhow::Error {
+    CliError {
+        code: code_of(&e),
+        message: e.to_string(),
+        reported: false,
+    }
+    .into()
+}
+
+/// Exit with `code` without printing anything further.
+pub fn reported(code: Code) -> anyhow::Error {
+    CliError {
+        code,
+        message: String::new(),
+        reported: true,
+    }
+    .into()
+}
+
+pub fn code(e: &anyhow::Error) -> Code {
+    if let Some(c) = e.downcast_ref::<CliError>() {
+        c.code
+    } else if let Some(c) = e.downcast_ref::<CoreError>() {
+        code_of(c)
+    } else {
+        Code::Failure
+    }
+}
+
+pub fn is_reported(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<CliError>().is_some_and(|c| c.reported)
+}
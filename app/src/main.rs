@@ -1,8 +1,15 @@
+use belljar_core::lifecycle::{Plan, Provisioned, StopTmux, Teardown, Worktree};
+use belljar_core::runtime::Target;
 use clap::{Args, Parser, Subcommand};
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
 use std::path::Path;
 use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+mod error;
+mod output;
 
 #[derive(Parser, Debug)]
 #[command(
@@ -11,6 +18,12 @@ use std::path::PathBuf;
     about = "belljar: session/worktree manager with per-session Docker Compose isolation"
 )]
 struct Cli {
+    /// Print machine-readable JSON (see docs/spec.md for the format)
+    #[arg(long, global = true)]
+    json: bool,
+    /// Override a config setting for this invocation (KEY=VALUE, repeatable)
+    #[arg(long = "set", global = true, value_name = "KEY=VALUE")]
+    overrides: Vec<String>,
     #[command(subcommand)]
     command: Commands,
 }
@@ -19,15 +32,48 @@ struct Cli {
 enum Commands {
     /// Create a new session with a worktree and optional services
     Start(StartArgs),
-    /// Create a new branch from a base (default: main) and open session
+    /// Create a new branch from a base (default: config base_branch) and open session
     New {
         label: String,
-        /// Base branch to create from (default: main)
+        /// Base branch to create from (default: config base_branch)
         #[arg(short = 'f', long = "from")]
         from: Option<String>,
         /// Path to git repository
         #[arg(short, long)]
         path: Option<PathBuf>,
+        /// Only start these compose services (comma-separated; default: all)
+        #[arg(long = "with", value_delimiter = ',', value_name = "SVC")]
+        with: Vec<String>,
+        /// Take compose files from .belljar/compose/<STACK>/
+        #[arg(long)]
+        stack: Option<String>,
+        /// Enable these compose profiles (comma-separated)
+        #[arg(long = "profile", value_delimiter = ',', value_name = "PROFILE")]
+        profiles: Vec<String>,
+        /// Cap CPUs for every service, or one with SVC=N (comma-separated, repeatable)
+        #[arg(long, value_delimiter = ',', value_name = "[SVC=]N")]
+        cpus: Vec<String>,
+        /// Cap memory (e.g. 512m) for every service, or one with SVC=SIZE
+        #[arg(long, value_delimiter = ',', value_name = "[SVC=]SIZE")]
+        memory: Vec<String>,
+        /// Seed the session's compose volumes from a template or LABEL@SNAPSHOT
+        #[arg(long, value_name = "TEMPLATE")]
+        from_volumes: Option<String>,
+        /// Run the containers on this Docker context (default: config docker_context)
+        #[arg(long, value_name = "NAME", conflicts_with = "docker_host")]
+        docker_context: Option<String>,
+        /// Run the containers on the engine at this address, e.g. ssh://host
+        #[arg(long, value_name = "URL")]
+        docker_host: Option<String>,
+        /// Tear down an existing session with this label and recreate it
+        #[arg(long)]
+        replace: bool,
+        /// Leave partially created state in place if a step fails (for debugging)
+        #[arg(long)]
+        keep_on_failure: bool,
+        /// Wait until services are healthy or running (timeout in seconds; default 120)
+        #[arg(long, value_name = "SECS", num_args = 0..=1, require_equals = true, default_missing_value = "120")]
+        wait: Option<u64>,
     },
     /// Checkout an existing branch/PR into a session
     Checkout {
@@ -36,13 +82,128 @@ enum Commands {
         path: Option<PathBuf>,
         #[arg(long)]
         label: Option<String>,
+        /// Only start these compose services (comma-separated; default: all)
+        #[arg(long = "with", value_delimiter = ',', value_name = "SVC")]
+        with: Vec<String>,
+        /// Take compose files from .belljar/compose/<STACK>/
+        #[arg(long)]
+        stack: Option<String>,
+        /// Enable these compose profiles (comma-separated)
+        #[arg(long = "profile", value_delimiter = ',', value_name = "PROFILE")]
+        profiles: Vec<String>,
+        /// Cap CPUs for every service, or one with SVC=N (comma-separated, repeatable)
+        #[arg(long, value_delimiter = ',', value_name = "[SVC=]N")]
+        cpus: Vec<String>,
+        /// Cap memory (e.g. 512m) for every service, or one with SVC=SIZE
+        #[arg(long, value_delimiter = ',', value_name = "[SVC=]SIZE")]
+        memory: Vec<String>,
+        /// Seed the session's compose volumes from a template or LABEL@SNAPSHOT
+        #[arg(long, value_name = "TEMPLATE")]
+        from_volumes: Option<String>,
+        /// Run the containers on this Docker context (default: config docker_context)
+        #[arg(long, value_name = "NAME", conflicts_with = "docker_host")]
+        docker_context: Option<String>,
+        /// Run the containers on the engine at this address, e.g. ssh://host
+        #[arg(long, value_name = "URL")]
+        docker_host: Option<String>,
+        /// Tear down an existing session with this label and recreate it
+        #[arg(long)]
+        replace: bool,
+        /// Leave partially created state in place if a step fails (for debugging)
+        #[arg(long)]
+        keep_on_failure: bool,
+        /// Wait until services are healthy or running (timeout in seconds; default 120)
+        #[arg(long, value_name = "SECS", num_args = 0..=1, require_equals = true, default_missing_value = "120")]
+        wait: Option<u64>,
     },
     /// List all sessions/workspaces
     Ls,
+    /// Show a session's worktree, tmux and per-service compose state
+    Status { label: String },
     /// Open a session in tmux
     Open { label: String },
     /// Remove a session or all sessions
-    Rm { target: String },
+    Rm {
+        target: String,
+        /// Remove even if the worktree has uncommitted or unpushed work
+        #[arg(long)]
+        force: bool,
+        /// Also delete the session's branch
+        #[arg(long)]
+        delete_branch: bool,
+    },
+    /// Stop a session's containers, keeping them and their volumes
+    Stop {
+        label: String,
+        /// Detach clients from the session's tmux session
+        #[arg(long, conflicts_with = "kill_tmux")]
+        detach: bool,
+        /// Kill the session's tmux session (`open` recreates it)
+        #[arg(long)]
+        kill_tmux: bool,
+    },
+    /// Mark a session as a template whose volumes `--from-volumes` copies
+    Template {
+        label: String,
+        /// Remove the mark instead
+        #[arg(long)]
+        unset: bool,
+    },
+    /// Save a session's compose volumes as a named snapshot (default: UTC time)
+    #[command(args_conflicts_with_subcommands = true)]
+    Snapshot {
+        #[command(subcommand)]
+        command: Option<SnapshotCmd>,
+        label: Option<String>,
+        name: Option<String>,
+    },
+    /// Replace a session's volume data with one of its snapshots
+    Restore { label: String, name: String },
+    /// Bring a stopped session's containers back up with their volumes
+    Resume {
+        label: String,
+        /// Wait until services are healthy or running (timeout in seconds; default 120)
+        #[arg(long, value_name = "SECS", num_args = 0..=1, require_equals = true, default_missing_value = "120")]
+        wait: Option<u64>,
+    },
+    /// Run a command in a session's service container
+    Exec {
+        label: String,
+        service: String,
+        /// Command and arguments (after `--`)
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+        /// Never allocate a TTY (default: only when stdin and stdout are terminals)
+        #[arg(short = 'T', long)]
+        no_tty: bool,
+    },
+    /// Show service logs for a session
+    Logs {
+        label: String,
+        /// Only these services (default: all)
+        services: Vec<String>,
+        /// Keep streaming new output
+        #[arg(short, long)]
+        follow: bool,
+        /// Only logs since a timestamp or relative time (e.g. 10m)
+        #[arg(long)]
+        since: Option<String>,
+        /// Number of lines to show from the end of each service's logs
+        #[arg(long)]
+        tail: Option<String>,
+        /// Show the logs saved when the session was last torn down
+        #[arg(long, conflicts_with_all = ["follow", "since", "tail"])]
+        saved: bool,
+    },
+    /// Show the host ports allocated to a session's compose services
+    Ports {
+        label: String,
+        /// Print `export BELLJAR_PORT_...=...` lines for eval
+        #[arg(long)]
+        env: bool,
+    },
+    /// Print a session's environment as shell exports
+    Env { label: String },
     /// Send a command to a session or all
     Send {
         target: String,
@@ -50,6 +211,38 @@ enum Commands {
     },
     /// Show control center (placeholder)
     ControlCenter,
+    /// Import existing git worktrees (and tmux sessions/compose projects) as sessions
+    Adopt {
+        /// Path to git repository
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+        /// Match running tmux sessions named after the worktree or branch
+        #[arg(long)]
+        tmux: bool,
+        /// Match compose projects whose files live in the worktree
+        #[arg(long)]
+        compose: bool,
+        /// Only list what would be adopted
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Reconcile the registry with git worktrees, tmux and docker compose
+    Gc {
+        /// Prune or repair drifted entries instead of only reporting them
+        #[arg(long)]
+        fix: bool,
+    },
+    /// Report the compose runtime in use and check the tools belljar needs
+    Doctor {
+        /// Repository whose config selects the runtime (default: cwd)
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+    },
+    /// Inspect the layered configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCmd,
+    },
     /// Workspace subcommands
     Workspace {
         #[command(subcommand)]
@@ -70,6 +263,57 @@ struct StartArgs {
     /// Branch name to create/checkout
     #[arg(long)]
     branch: Option<String>,
+    /// Only start these compose services (comma-separated; default: all)
+    #[arg(long = "with", value_delimiter = ',', value_name = "SVC")]
+    with: Vec<String>,
+    /// Take compose files from .belljar/compose/<STACK>/
+    #[arg(long)]
+    stack: Option<String>,
+    /// Enable these compose profiles (comma-separated)
+    #[arg(long = "profile", value_delimiter = ',', value_name = "PROFILE")]
+    profiles: Vec<String>,
+    /// Cap CPUs for every service, or one with SVC=N (comma-separated, repeatable)
+    #[arg(long, value_delimiter = ',', value_name = "[SVC=]N")]
+    cpus: Vec<String>,
+    /// Cap memory (e.g. 512m) for every service, or one with SVC=SIZE
+    #[arg(long, value_delimiter = ',', value_name = "[SVC=]SIZE")]
+    memory: Vec<String>,
+    /// Seed the session's compose volumes from a template or LABEL@SNAPSHOT
+    #[arg(long, value_name = "TEMPLATE")]
+    from_volumes: Option<String>,
+    /// Run the containers on this Docker context (default: config docker_context)
+    #[arg(long, value_name = "NAME", conflicts_with = "docker_host")]
+    docker_context: Option<String>,
+    /// Run the containers on the engine at this address, e.g. ssh://host
+    #[arg(long, value_name = "URL")]
+    docker_host: Option<String>,
+    /// Tear down an existing session with this label and recreate it
+    #[arg(long)]
+    replace: bool,
+    /// Leave partially created state in place if a step fails (for debugging)
+    #[arg(long)]
+    keep_on_failure: bool,
+    /// Wait until services are healthy or running (timeout in seconds; default 120)
+    #[arg(long, value_name = "SECS", num_args = 0..=1, require_equals = true, default_missing_value = "120")]
+    wait: Option<u64>,
+}
+
+#[derive(Subcommand, Debug)]
+enum SnapshotCmd {
+    /// List snapshots, of one session label or all
+    Ls { label: Option<String> },
+    /// Delete a snapshot
+    Rm { label: String, name: String },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCmd {
+    /// Print effective settings and where each one came from
+    Show {
+        /// Repository whose .belljar/config.toml to include (default: cwd)
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -94,307 +338,871 @@ enum WorkspaceCmd {
     Rm { target: String },
 }
 
-fn main() -> anyhow::Result<()> {
+fn main() -> ExitCode {
     let cli = Cli::parse();
+    let json = cli.json;
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            let code = error::code(&e);
+            if error::is_reported(&e) {
+                // Details were already printed
+            } else if json {
+                output::error(&e, code);
+            } else {
+                eprintln!("Error: {e}");
+            }
+            ExitCode::from(code as u8)
+        }
+    }
+}
+
+fn run(cli: Cli) -> anyhow::Result<()> {
+    let json = cli.json;
+    belljar_core::config::set_cli_overrides(&cli.overrides).map_err(error::core_bare)?;
+    if json {
+        let interactive = match &cli.command {
+            Commands::Open { .. } => Some("open"),
+            Commands::Exec { .. } => Some("exec"),
+            Commands::Logs { .. } => Some("logs"),
+            Commands::ControlCenter => Some("control-center"),
+            Commands::Wizard => Some("wizard"),
+            Commands::Workspace {
+                command: WorkspaceCmd::Open { .. },
+            } => Some("workspace open"),
+            Commands::Workspace {
+                command: WorkspaceCmd::Start { open: true, .. },
+            } => Some("workspace start --open"),
+            _ => None,
+        };
+        if let Some(name) = interactive {
+            return Err(error::fail(
+                error::Code::Usage,
+                format!("--json is not supported for interactive command: {name}"),
+            ));
+        }
+        // stdout carries only the JSON document
+        belljar_core::set_child_stdout_to_stderr(true);
+    }
     match cli.command {
         Commands::Start(args) => {
             let repo = resolve_repo_path(args.path.as_deref())?;
-            let mut session =
-                belljar_core::create_session(&args.label, &repo, args.branch.clone(), vec![])
-                    .map_err(|e| anyhow::anyhow!("create session failed: {e}"))?;
-
             // Ensure worktree if repo is git
-            if belljar_core::git::is_git_repo(&repo) {
-                match belljar_core::git::ensure_worktree(&repo, &args.label, &args.branch) {
-                    Ok(wt) => {
-                        belljar_core::git::set_session_worktree(&mut session, wt).ok();
-                    }
-                    Err(e) => eprintln!("warning: worktree setup failed: {e}"),
-                }
-            }
-            match belljar_core::compose::up(&session) {
-                Ok(()) => {
-                    println!(
-                        "created session: {} (project: {}) [compose up]",
-                        session.label, session.compose_project
-                    );
-                }
-                Err(belljar_core::CoreError::NoComposeFiles) => {
-                    println!(
-                        "created session: {} (project: {}); no compose files found, skipping",
-                        session.label, session.compose_project
-                    );
-                }
-                Err(e) => {
-                    eprintln!("warning: compose up failed: {e}");
-                    println!(
-                        "created session: {} (project: {})",
-                        session.label, session.compose_project
-                    );
-                }
+            let worktree = if belljar_core::git::is_git_repo(&repo) {
+                Worktree::Checkout
+            } else {
+                Worktree::Skip
+            };
+            let (done, replaced) = provision(
+                Plan {
+                    label: args.label.clone(),
+                    repo_path: repo,
+                    branch: args.branch.clone(),
+                    services: name_list(args.with),
+                    stack: args.stack,
+                    profiles: name_list(args.profiles),
+                    limits: limit_list(&args.cpus, &args.memory)?,
+                    volumes_from: args.from_volumes,
+                    target: engine_target(args.docker_context, args.docker_host),
+                    worktree,
+                    tmux: false,
+                    wait: args.wait.map(Duration::from_secs),
+                    keep_on_failure: args.keep_on_failure,
+                },
+                args.replace,
+                json,
+            )?;
+            let session = &done.session;
+            if json {
+                output::print(&created_doc(&done, replaced, None));
+            } else if done.compose_up {
+                println!(
+                    "created session: {} (project: {}) [{}]",
+                    session.label,
+                    session.compose_project,
+                    up_tag(&done)
+                );
+            } else {
+                println!(
+                    "created session: {} (project: {}); no compose files found, skipping",
+                    session.label, session.compose_project
+                );
             }
         }
-        Commands::New { label, from, path } => {
+        Commands::New {
+            label,
+            from,
+            path,
+            with,
+            stack,
+            profiles,
+            cpus,
+            memory,
+            from_volumes,
+            docker_context,
+            docker_host,
+            replace,
+            keep_on_failure,
+            wait,
+        } => {
             let repo = resolve_repo_path(path.as_deref())?;
-            // If session already exists, just focus it
-            if let Ok(Some(s)) = belljar_core::find_session(&label) {
-                // Ensure tmux session and focus appropriately
-                match belljar_core::tmux::ensure_session(&s) {
-                    Ok(()) => {
-                        if std::env::var("TMUX").is_ok() {
-                            if let Err(e) = belljar_core::tmux::switch_client(&s.tmux_session) {
-                                eprintln!("failed to focus session: {e}");
-                            }
-                        } else if let Err(e) = belljar_core::tmux::attach(&s.tmux_session) {
-                            match e {
-                                belljar_core::CoreError::TmuxNotFound => println!(
-                                    "tmux not found; cd {} to work in this session",
-                                    s.repo_path.display()
-                                ),
-                                other => eprintln!("failed to attach: {other}"),
-                            }
-                        }
-                    }
-                    Err(belljar_core::CoreError::TmuxNotFound) => println!(
-                        "tmux not found; cd {} to work in this session",
-                        s.repo_path.display()
-                    ),
-                    Err(e) => eprintln!("failed to open session: {e}"),
+            // If session already exists, just focus it (unless replacing it)
+            let existing = if replace {
+                None
+            } else {
+                belljar_core::find_session(&label).ok().flatten()
+            };
+            if let Some(s) = existing {
+                if json {
+                    output::print(&output::Created {
+                        session: output::SessionEntry::probe(vec![s]).remove(0),
+                        created: false,
+                        replaced: false,
+                        compose_up: false,
+                        base: None,
+                        copied_volumes: Vec::new(),
+                    });
+                    return Ok(());
                 }
-                return Ok(());
+                // Ensure tmux session and focus appropriately
+                return focus_session(&s);
             }
 
             if !belljar_core::git::is_git_repo(&repo) {
-                anyhow::bail!("not a git repository: {}", repo.display());
+                return Err(error::fail(
+                    error::Code::Git,
+                    format!("not a git repository: {}", repo.display()),
+                ));
             }
-            let base = from.unwrap_or_else(|| "main".to_string());
-            let mut session =
-                belljar_core::create_session(&label, &repo, Some(label.clone()), vec![])
-                    .map_err(|e| anyhow::anyhow!("create session failed: {e}"))?;
-
-            match belljar_core::git::ensure_worktree_from(&repo, &label, &label, &base) {
-                Ok(wt) => {
-                    belljar_core::git::set_session_worktree(&mut session, wt).ok();
-                }
-                Err(e) => eprintln!("warning: worktree setup failed: {e}"),
+            let base = match from {
+                Some(b) => b,
+                None => load_config(Some(&repo))?.base_branch.value,
+            };
+            let (done, replaced) = provision(
+                Plan {
+                    label: label.clone(),
+                    repo_path: repo,
+                    branch: Some(label.clone()),
+                    services: name_list(with),
+                    stack,
+                    profiles: name_list(profiles),
+                    limits: limit_list(&cpus, &memory)?,
+                    volumes_from: from_volumes,
+                    target: engine_target(docker_context, docker_host),
+                    worktree: Worktree::FromBase(base.clone()),
+                    wait: wait.map(Duration::from_secs),
+                    tmux: true,
+                    keep_on_failure,
+                },
+                replace,
+                json,
+            )?;
+            let session = &done.session;
+            if json {
+                // Scripts get the result; focusing tmux is left to them
+                output::print(&created_doc(&done, replaced, Some(base)));
+                return Ok(());
             }
-
-            match belljar_core::compose::up(&session) {
-                Ok(()) => println!(
-                    "created session: {} from {} (project: {}) [compose up]",
-                    session.label, base, session.compose_project
-                ),
-                Err(belljar_core::CoreError::NoComposeFiles) => println!(
+            if done.compose_up {
+                println!(
+                    "created session: {} from {} (project: {}) [{}]",
+                    session.label,
+                    base,
+                    session.compose_project,
+                    up_tag(&done)
+                );
+            } else {
+                println!(
                     "created session: {} from {} (project: {}); no compose files found, skipping",
                     session.label, base, session.compose_project
-                ),
-                Err(e) => eprintln!("warning: compose up failed: {e}"),
+                );
             }
 
             // Focus in tmux
-            match belljar_core::tmux::ensure_session(&session) {
-                Ok(()) => {
-                    if std::env::var("TMUX").is_ok() {
-                        if let Err(e) = belljar_core::tmux::switch_client(&session.tmux_session) {
-                            eprintln!("failed to focus session: {e}");
-                        }
-                    } else if let Err(e) = belljar_core::tmux::attach(&session.tmux_session) {
-                        match e {
-                            belljar_core::CoreError::TmuxNotFound => println!(
-                                "tmux not found; cd {} to work in this session",
-                                session.repo_path.display()
-                            ),
-                            other => eprintln!("failed to attach: {other}"),
-                        }
-                    }
-                }
-                Err(belljar_core::CoreError::TmuxNotFound) => println!(
+            if !done.tmux {
+                println!(
                     "tmux not found; cd {} to work in this session",
                     session.repo_path.display()
-                ),
-                Err(e) => eprintln!("failed to open session: {e}"),
+                );
+            } else {
+                focus_session(session)?;
             }
         }
         Commands::Checkout {
             target,
             path,
             label,
+            with,
+            stack,
+            profiles,
+            cpus,
+            memory,
+            from_volumes,
+            docker_context,
+            docker_host,
+            replace,
+            keep_on_failure,
+            wait,
         } => {
             let repo = resolve_repo_path(path.as_deref())?;
             let label = label.unwrap_or_else(|| target.clone());
-            let mut session =
-                belljar_core::create_session(&label, &repo, Some(target.clone()), vec![])
-                    .map_err(|e| anyhow::anyhow!("create session failed: {e}"))?;
-            if belljar_core::git::is_git_repo(&repo) {
-                match belljar_core::git::ensure_worktree(&repo, &label, &Some(target.clone())) {
-                    Ok(wt) => {
-                        belljar_core::git::set_session_worktree(&mut session, wt).ok();
-                    }
-                    Err(e) => eprintln!("warning: worktree setup failed: {e}"),
-                }
-            }
-            match belljar_core::compose::up(&session) {
-                Ok(()) => println!(
-                    "checked out: {} -> {} (project: {}) [compose up]",
-                    label, target, session.compose_project
-                ),
-                Err(belljar_core::CoreError::NoComposeFiles) => println!(
+            let worktree = if belljar_core::git::is_git_repo(&repo) {
+                Worktree::Checkout
+            } else {
+                Worktree::Skip
+            };
+            let (done, replaced) = provision(
+                Plan {
+                    label: label.clone(),
+                    repo_path: repo,
+                    branch: Some(target.clone()),
+                    services: name_list(with),
+                    stack,
+                    profiles: name_list(profiles),
+                    limits: limit_list(&cpus, &memory)?,
+                    volumes_from: from_volumes,
+                    target: engine_target(docker_context, docker_host),
+                    worktree,
+                    wait: wait.map(Duration::from_secs),
+                    tmux: false,
+                    keep_on_failure,
+                },
+                replace,
+                json,
+            )?;
+            if json {
+                output::print(&created_doc(&done, replaced, None));
+            } else if done.compose_up {
+                println!(
+                    "checked out: {} -> {} (project: {}) [{}]",
+                    label,
+                    target,
+                    done.session.compose_project,
+                    up_tag(&done)
+                );
+            } else {
+                println!(
                     "checked out: {} -> {} (project: {}); no compose files found, skipping",
-                    label, target, session.compose_project
-                ),
-                Err(e) => eprintln!("warning: compose up failed: {e}"),
+                    label, target, done.session.compose_project
+                );
             }
         }
         Commands::Ls => {
             let reg = belljar_core::load_registry()
-                .map_err(|e| anyhow::anyhow!("load registry failed: {e}"))?;
-            if reg.sessions.is_empty() {
+                .map_err(|e| error::core("load registry failed", e))?;
+            if json {
+                output::print(&output::SessionList {
+                    sessions: output::SessionEntry::probe(reg.sessions),
+                });
+            } else if reg.sessions.is_empty() {
                 println!("no sessions");
             } else {
-                for s in reg.sessions {
+                let live = belljar_core::status::probe(&reg.sessions);
+                for (s, live) in reg.sessions.iter().zip(live) {
                     println!(
-                        "{}\t{}\t{}",
+                        "{}\t{}\t{}\t{}{}{}",
                         s.label,
                         s.repo_path.display(),
-                        s.compose_project
+                        s.compose_project,
+                        if s.template { "template, " } else { "" },
+                        if s.stopped { "stopped, " } else { "" },
+                        live
                     );
                 }
             }
         }
+        Commands::Status { label } => {
+            let s = require_session(&label)?;
+            let detail = belljar_core::status::detail(&s);
+            if json {
+                output::print(&output::StatusDoc {
+                    session: output::SessionEntry {
+                        session: s,
+                        status: detail.live,
+                    },
+                    branch: detail.branch,
+                    dirty: detail.dirty,
+                    services: detail.services,
+                });
+            } else {
+                print_status(&s, &detail);
+            }
+        }
+        Commands::Stop {
+            label,
+            detach,
+            kill_tmux,
+        } => {
+            let s = require_session(&label)?;
+            let tmux_mode = if kill_tmux {
+                StopTmux::Kill
+            } else if detach {
+                StopTmux::Detach
+            } else {
+                StopTmux::Keep
+            };
+            let s = belljar_core::lifecycle::stop(&s, tmux_mode)
+                .map_err(|e| error::core("stop failed", e))?;
+            if json {
+                output::print(&output::SessionDoc {
+                    session: output::SessionEntry::probe(vec![s]).remove(0),
+                });
+            } else {
+                println!(
+                    "stopped session: {} (project: {}; containers and volumes kept)",
+                    s.label, s.compose_project
+                );
+            }
+        }
+        Commands::Template { label, unset } => {
+            let s = belljar_core::set_template(&label, !unset).map_err(error::core_bare)?;
+            if json {
+                output::print(&output::SessionDoc {
+                    session: output::SessionEntry::probe(vec![s]).remove(0),
+                });
+            } else if unset {
+                println!("{} is no longer a template", s.label);
+            } else {
+                println!(
+                    "marked {} as a template; seed new sessions with --from-volumes {}",
+                    s.label, s.label
+                );
+            }
+        }
+        Commands::Snapshot {
+            command: Some(SnapshotCmd::Ls { label }),
+            ..
+        } => {
+            let snapshots = belljar_core::volumes::snapshots(label.as_deref())
+                .map_err(|e| error::core("failed to list snapshots", e))?;
+            if json {
+                output::print(&output::SnapshotList { snapshots });
+            } else if snapshots.is_empty() {
+                println!("no snapshots");
+            } else {
+                for s in snapshots {
+                    println!(
+                        "{}\t{}\t{}\t{}",
+                        s.label,
+                        s.name,
+                        s.created_at,
+                        s.volumes.join(",")
+                    );
+                }
+            }
+        }
+        Commands::Snapshot {
+            command: Some(SnapshotCmd::Rm { label, name }),
+            ..
+        } => {
+            let snapshot =
+                belljar_core::volumes::remove_snapshot(&label, &name).map_err(error::core_bare)?;
+            if json {
+                output::print(&output::SnapshotDoc { snapshot });
+            } else {
+                println!("removed snapshot {label}@{name}");
+            }
+        }
+        Commands::Snapshot {
+            command: None,
+            label,
+            name,
+        } => {
+            let Some(label) = label else {
+                return Err(error::fail(
+                    error::Code::Usage,
+                    "usage: belljar snapshot <label> [name] | snapshot ls | snapshot rm",
+                ));
+            };
+            let s = require_session(&label)?;
+            let snapshot = belljar_core::volumes::snapshot(&s, name.as_deref())
+                .map_err(|e| error::core("snapshot failed", e))?;
+            if json {
+                output::print(&output::SnapshotDoc { snapshot });
+            } else {
+                println!(
+                    "saved snapshot {}@{} ({})",
+                    snapshot.label,
+                    snapshot.name,
+                    snapshot.volumes.join(", ")
+                );
+            }
+        }
+        Commands::Restore { label, name } => {
+            let s = require_session(&label)?;
+            let snapshot = belljar_core::lifecycle::restore(&s, &name)
+                .map_err(|e| error::core("restore failed", e))?;
+            if json {
+                output::print(&output::SnapshotDoc { snapshot });
+            } else {
+                println!(
+                    "restored {} from snapshot {} ({})",
+                    s.label,
+                    snapshot.name,
+                    snapshot.volumes.join(", ")
+                );
+            }
+        }
+        Commands::Resume { label, wait } => {
+            let s = require_session(&label)?;
+            let mut progress = wait_progress(s.label.clone(), json);
+            let s =
+                belljar_core::lifecycle::resume(&s, wait.map(Duration::from_secs), &mut progress)
+                    .map_err(|e| error::core("resume failed", e))?;
+            if json {
+                output::print(&output::SessionDoc {
+                    session: output::SessionEntry::probe(vec![s]).remove(0),
+                });
+            } else {
+                println!(
+                    "resumed session: {} (project: {})",
+                    s.label, s.compose_project
+                );
+            }
+        }
         Commands::Open { label } => {
-            match belljar_core::find_session(&label) {
-                Ok(Some(s)) => {
-                    match belljar_core::tmux::ensure_session(&s) {
-                        Ok(()) => {
-                            // Try to attach. If tmux not found, provide guidance.
-                            match belljar_core::tmux::attach(&s.tmux_session) {
-                                Ok(()) => {}
-                                Err(belljar_core::CoreError::TmuxNotFound) => {
-                                    println!(
-                                        "tmux not found; cd {} to work in this session",
-                                        s.repo_path.display()
-                                    );
-                                }
-                                Err(e) => eprintln!("failed to attach: {e}"),
-                            }
-                        }
+            let s = require_session(&label)?;
+            match belljar_core::tmux::ensure_session(&s) {
+                Ok(()) => {
+                    // Try to attach. If tmux not found, provide guidance.
+                    match belljar_core::tmux::attach(&s.tmux_session) {
+                        Ok(()) => {}
                         Err(belljar_core::CoreError::TmuxNotFound) => {
                             println!(
                                 "tmux not found; cd {} to work in this session",
                                 s.repo_path.display()
                             );
                         }
-                        Err(e) => eprintln!("failed to open session: {e}"),
+                        Err(e) => return Err(error::core("failed to attach", e)),
                     }
                 }
-                Ok(None) => println!("no such session: {label}"),
-                Err(e) => eprintln!("failed to load registry: {e}"),
-            }
-        }
-        Commands::Rm { target } => {
-            if target == "all" {
-                let reg = belljar_core::load_registry()
-                    .map_err(|e| anyhow::anyhow!("load registry failed: {e}"))?;
-                for s in reg.sessions {
-                    match belljar_core::compose::down(&s) {
-                        Ok(()) | Err(belljar_core::CoreError::NoComposeFiles) => {}
-                        Err(e) => eprintln!("warning: compose down failed for {}: {e}", s.label),
+                Err(belljar_core::CoreError::TmuxNotFound) => {
+                    println!(
+                        "tmux not found; cd {} to work in this session",
+                        s.repo_path.display()
+                    );
+                }
+                Err(e) => return Err(error::core("failed to open session", e)),
+            }
+        }
+        Commands::Rm {
+            target,
+            force,
+            delete_branch,
+        } => {
+            let opts = Teardown {
+                force,
+                remove_worktree: true,
+                delete_branch,
+            };
+            let sessions = if target == "all" {
+                belljar_core::load_registry()
+                    .map_err(|e| error::core("load registry failed", e))?
+                    .sessions
+            } else {
+                vec![require_session(&target)?]
+            };
+            let mut first_code = None;
+            let mut result = output::RemoveResult {
+                removed: Vec::new(),
+                refused: Vec::new(),
+            };
+            for s in &sessions {
+                match remove_session(s, opts, json) {
+                    Ok(warnings) => result.removed.push(output::RemovedEntry {
+                        label: s.label.clone(),
+                        warnings,
+                    }),
+                    Err(e) => {
+                        first_code.get_or_insert(error::code_of(&e));
+                        result.refused.push(output::Refused {
+                            label: s.label.clone(),
+                            error: e.to_string(),
+                        })
                     }
-                    let _ = belljar_core::remove_session(&s.label);
-                    println!("removed {}", s.label);
                 }
-            } else if let Some(s) = belljar_core::find_session(&target)
-                .map_err(|e| anyhow::anyhow!("find session failed: {e}"))?
-            {
-                match belljar_core::compose::down(&s) {
-                    Ok(()) | Err(belljar_core::CoreError::NoComposeFiles) => {}
-                    Err(e) => eprintln!("warning: compose down failed for {}: {e}", s.label),
+            }
+            if json {
+                output::print(&result);
+                if let Some(code) = first_code {
+                    return Err(error::reported(code));
                 }
-                belljar_core::remove_session(&target)
-                    .map_err(|e| anyhow::anyhow!("remove failed: {e}"))?;
-                println!("removed {}", s.label);
+            } else if let Some(code) = first_code {
+                let message = if target == "all" {
+                    format!("{} session(s) not removed", result.refused.len())
+                } else {
+                    format!("session {} not removed", result.refused[0].label)
+                };
+                return Err(error::fail(code, message));
+            }
+        }
+        Commands::Exec {
+            label,
+            service,
+            command,
+            no_tty,
+        } => {
+            let s = require_session(&label)?;
+            let tty = !no_tty && io::stdin().is_terminal() && io::stdout().is_terminal();
+            let code = belljar_core::compose::exec(&s, &service, &command, tty)
+                .map_err(|e| error::core("exec failed", e))?;
+            // Pass the command's exit status straight through
+            if code != 0 {
+                std::process::exit(code);
+            }
+        }
+        Commands::Logs {
+            label,
+            services,
+            follow,
+            since,
+            tail,
+            saved,
+        } => {
+            if saved {
+                return print_saved_logs(&label, &services);
+            }
+            let s = require_session(&label)?;
+            let opts = belljar_core::compose::LogsOptions {
+                services,
+                follow,
+                since,
+                tail,
+            };
+            let code = belljar_core::compose::logs(&s, &opts)
+                .map_err(|e| error::core("logs failed", e))?;
+            if code != 0 {
+                return Err(error::fail(
+                    error::Code::Compose,
+                    format!("compose logs exited with status {code}"),
+                ));
+            }
+        }
+        Commands::Ports { label, env } => {
+            let s = require_session(&label)?;
+            let vars = belljar_core::ports::env(&s);
+            if json {
+                output::print(&output::PortsDoc {
+                    label: s.label.clone(),
+                    ports: s.ports.clone(),
+                    env: vars.into_iter().collect(),
+                });
+            } else if env {
+                for (k, v) in vars {
+                    println!("export {k}={v}");
+                }
+            } else if s.ports.is_empty() {
+                println!("no published ports");
+            } else {
+                for p in &s.ports {
+                    println!("{}\t{}/{}\t{}", p.service, p.target, p.protocol, p.host);
+                }
+            }
+        }
+        Commands::Env { label } => {
+            let s = require_session(&label)?;
+            let vars = belljar_core::env::build(&s)
+                .map_err(|e| error::core("failed to build session environment", e))?;
+            if json {
+                output::print(&output::EnvDoc {
+                    label: s.label.clone(),
+                    env: vars.into_iter().collect(),
+                });
             } else {
-                println!("no such session: {target}");
+                for (k, v) in vars {
+                    println!("export {k}={}", shell_quote(&v));
+                }
             }
         }
         Commands::Send { target, command } => {
             let cmd = command.join(" ");
-            if target == "all" {
-                match belljar_core::load_registry() {
-                    Ok(reg) => {
-                        for s in reg.sessions {
-                            match belljar_core::tmux::ensure_session(&s) {
-                                Ok(()) => {
-                                    if let Err(e) =
-                                        belljar_core::tmux::send_keys(&s.tmux_session, &cmd)
-                                    {
-                                        eprintln!("send to {} failed: {e}", s.label);
-                                    }
-                                }
-                                Err(e) => eprintln!("ensure session {} failed: {e}", s.label),
-                            }
-                        }
+            let sessions = if target == "all" {
+                belljar_core::load_registry()
+                    .map_err(|e| error::core("failed to load registry", e))?
+                    .sessions
+            } else {
+                vec![require_session(&target)?]
+            };
+            let mut result = output::SendResult {
+                sent: Vec::new(),
+                failed: Vec::new(),
+            };
+            let mut first_code = None;
+            for s in sessions {
+                let sent = belljar_core::tmux::ensure_session(&s)
+                    .map_err(|e| (format!("ensure session {} failed", s.label), e))
+                    .and_then(|()| {
+                        belljar_core::tmux::send_keys(&s.tmux_session, &cmd)
+                            .map_err(|e| (format!("send to {} failed", s.label), e))
+                    });
+                match sent {
+                    Ok(()) => result.sent.push(s.label),
+                    Err((context, e)) => {
+                        eprintln!("{context}: {e}");
+                        first_code.get_or_insert(error::code_of(&e));
+                        result.failed.push(output::Refused {
+                            label: s.label,
+                            error: e.to_string(),
+                        });
                     }
-                    Err(e) => eprintln!("failed to load registry: {e}"),
                 }
-            } else {
-                match belljar_core::find_session(&target) {
-                    Ok(Some(s)) => match belljar_core::tmux::ensure_session(&s) {
-                        Ok(()) => {
-                            if let Err(e) = belljar_core::tmux::send_keys(&s.tmux_session, &cmd) {
-                                eprintln!("send failed: {e}");
-                            }
-                        }
-                        Err(e) => eprintln!("ensure session failed: {e}"),
-                    },
-                    Ok(None) => println!("no such session: {target}"),
-                    Err(e) => eprintln!("failed to load registry: {e}"),
+            }
+            if json {
+                output::print(&result);
+            }
+            if let Some(code) = first_code {
+                if json {
+                    return Err(error::reported(code));
                 }
+                return Err(error::fail(
+                    code,
+                    format!("send failed for {} session(s)", result.failed.len()),
+                ));
             }
         }
         Commands::ControlCenter => {
             // Create a tmux session named "belljar-cc" with one window per session
             let cc_name = "belljar-cc";
-            match belljar_core::load_registry() {
-                Ok(reg) => {
-                    if reg.sessions.is_empty() {
-                        println!("no sessions to show");
-                    } else {
-                        // Use the first session's repo as the base cwd
-                        let base = &reg.sessions[0].repo_path;
-                        match belljar_core::tmux::ensure_named_session(cc_name, base) {
-                            Ok(()) => {
-                                for s in reg.sessions {
-                                    // Try to create a window per session label
-                                    if let Err(e) = belljar_core::tmux::new_window(
-                                        cc_name,
-                                        &s.label,
-                                        &s.repo_path,
-                                    ) {
-                                        eprintln!("failed to create window for {}: {e}", s.label);
-                                    }
-                                }
-                                // Optional: choose a tiled layout
-                                let _ = belljar_core::tmux::select_layout(cc_name, "tiled");
-                                // Attach to control center
-                                if let Err(e) = belljar_core::tmux::attach(cc_name) {
-                                    eprintln!("failed to attach control center: {e}");
-                                }
-                            }
-                            Err(belljar_core::CoreError::TmuxNotFound) => {
-                                println!("tmux not found; control-center requires tmux");
+            let reg = belljar_core::load_registry()
+                .map_err(|e| error::core("failed to load registry", e))?;
+            if reg.sessions.is_empty() {
+                println!("no sessions to show");
+                return Ok(());
+            }
+            // Use the first session's repo as the base cwd
+            let base = &reg.sessions[0].repo_path;
+            let layout = load_config(Some(base))?.layout.value;
+            match belljar_core::tmux::ensure_named_session(cc_name, base) {
+                Ok(()) => {}
+                Err(belljar_core::CoreError::TmuxNotFound) => {
+                    return Err(error::fail(
+                        error::Code::Tmux,
+                        "tmux not found; control-center requires tmux",
+                    ));
+                }
+                Err(e) => return Err(error::core("failed to init control center", e)),
+            }
+            for s in &reg.sessions {
+                // Try to create a window per session label
+                if let Err(e) = belljar_core::tmux::new_window(cc_name, &s.label, &s.repo_path) {
+                    eprintln!("failed to create window for {}: {e}", s.label);
+                }
+            }
+            let _ = belljar_core::tmux::select_layout(cc_name, &layout);
+            // Attach to control center
+            belljar_core::tmux::attach(cc_name)
+                .map_err(|e| error::core("failed to attach control center", e))?;
+        }
+        Commands::Adopt {
+            path,
+            tmux,
+            compose,
+            dry_run,
+        } => {
+            let repo = resolve_repo_path(path.as_deref())?;
+            if !belljar_core::git::is_git_repo(&repo) {
+                return Err(error::fail(
+                    error::Code::Git,
+                    format!("not a git repository: {}", repo.display()),
+                ));
+            }
+            let candidates = belljar_core::adopt::scan(&repo, tmux, compose)
+                .map_err(|e| error::core("adopt scan failed", e))?;
+            if candidates.is_empty() && !json {
+                println!("nothing to adopt");
+            }
+            let mut result = output::AdoptResult {
+                dry_run,
+                candidates: Vec::new(),
+            };
+            let mut first_code = None;
+            for c in candidates {
+                let detail = format!(
+                    "{} -> {} (tmux: {}, project: {})",
+                    c.label,
+                    c.worktree_path.display(),
+                    c.tmux_session.as_deref().unwrap_or("-"),
+                    c.compose_project.as_deref().unwrap_or("-")
+                );
+                let outcome = if dry_run {
+                    if !json {
+                        println!("would adopt {detail}");
+                    }
+                    None
+                } else {
+                    match belljar_core::adopt::adopt(&repo, &c) {
+                        Ok(_) => {
+                            if !json {
+                                println!("adopted {detail}");
                             }
-                            Err(e) => eprintln!("failed to init control center: {e}"),
+                            Some(Ok(()))
+                        }
+                        Err(e) => {
+                            eprintln!("warning: cannot adopt {}: {e}", c.label);
+                            first_code.get_or_insert(error::code_of(&e));
+                            Some(Err(e.to_string()))
                         }
                     }
+                };
+                result.candidates.push(output::Adopted {
+                    candidate: c,
+                    adopted: matches!(outcome, Some(Ok(()))),
+                    error: outcome.and_then(Result::err),
+                });
+            }
+            if json {
+                output::print(&result);
+            }
+            if let Some(code) = first_code {
+                return Err(error::reported(code));
+            }
+        }
+        Commands::Gc { fix } => {
+            let report =
+                belljar_core::reconcile::scan().map_err(|e| error::core("reconcile failed", e))?;
+            let mut first_code = None;
+            if !report.compose_checked {
+                eprintln!("warning: compose runtime unavailable; compose checks skipped");
+            }
+            if !report.tmux_checked {
+                eprintln!("warning: tmux not found; tmux checks skipped");
+            }
+            if !report.worktrees_checked {
+                eprintln!("warning: git could not list worktrees; worktree checks skipped for some sessions");
+            }
+            let mut doc = output::GcReport {
+                findings: report
+                    .findings
+                    .iter()
+                    .map(|f| output::GcFinding {
+                        kind: f.subject.kind(),
+                        label: f.subject.label().to_string(),
+                        issue: f.issue.to_string(),
+                        action: f.action.to_string(),
+                        fix_error: None,
+                    })
+                    .collect(),
+                orphan_projects: report.orphan_projects.clone(),
+                compose_checked: report.compose_checked,
+                tmux_checked: report.tmux_checked,
+                worktrees_checked: report.worktrees_checked,
+                clean: report.is_clean(),
+            };
+            if !json {
+                for f in &report.findings {
+                    println!(
+                        "{} {}: {} [{}]",
+                        f.subject.kind(),
+                        f.subject.label(),
+                        f.issue,
+                        f.action
+                    );
+                }
+                let runtime = belljar_core::runtime::resolve(None).map_err(error::core_bare)?;
+                for p in &report.orphan_projects {
+                    println!("orphan compose project: {p} ({runtime} -p {p} down -v)");
                 }
-                Err(e) => eprintln!("failed to load registry: {e}"),
+            }
+            if report.is_clean() {
+                if !json {
+                    println!("registry is in sync");
+                }
+            } else if fix {
+                let attempted = belljar_core::reconcile::fix(&report);
+                for (f, res) in attempted {
+                    if let Some(entry) = doc
+                        .findings
+                        .iter_mut()
+                        .find(|d| d.kind == f.subject.kind() && d.label == f.subject.label())
+                    {
+                        entry.fix_error = Some(res.as_ref().err().map(|e| e.to_string()));
+                    }
+                    match res {
+                        Ok(()) if json => {}
+                        Ok(()) => println!(
+                            "{} {} {}",
+                            match f.action {
+                                belljar_core::reconcile::Action::Repair => "repaired",
+                                _ => "pruned",
+                            },
+                            f.subject.kind(),
+                            f.subject.label()
+                        ),
+                        Err(e) => {
+                            eprintln!(
+                                "warning: {} {} for {} failed: {e}",
+                                f.subject.kind(),
+                                f.action,
+                                f.subject.label()
+                            );
+                            first_code.get_or_insert(error::code_of(&e));
+                        }
+                    }
+                }
+            } else if !json {
+                println!("run `belljar gc --fix` to prune or repair these entries");
+            }
+            if json {
+                output::print(&doc);
+            }
+            if let Some(code) = first_code {
+                return Err(error::reported(code));
+            }
+        }
+        Commands::Config { command } => match command {
+            ConfigCmd::Show { path } => {
+                let repo = resolve_repo_path(path.as_deref())?;
+                let cfg = load_config(Some(&repo))?;
+                if json {
+                    output::print(&output::ConfigDoc {
+                        settings: cfg
+                            .entries()
+                            .into_iter()
+                            .map(|(key, value, source)| output::Setting {
+                                key,
+                                value,
+                                source: source.to_string(),
+                            })
+                            .collect(),
+                    });
+                } else {
+                    for (key, value, source) in cfg.entries() {
+                        println!("{key}\t{value}\t{source}");
+                    }
+                }
+            }
+        },
+        Commands::Doctor { path } => {
+            let repo = resolve_repo_path(path.as_deref())?;
+            let report = belljar_core::doctor::run(Some(&repo)).map_err(error::core_bare)?;
+            if json {
+                output::print(&output::DoctorDoc::new(&report));
+            } else {
+                let rt = &report.runtime;
+                let how = if rt.detected {
+                    "auto-detected"
+                } else {
+                    "configured"
+                };
+                println!("runtime\t{} ({rt}; {how})", rt.kind.name());
+                for c in &report.checks {
+                    match &c.result {
+                        Ok(version) => println!("ok\t{}\t{version}", c.name),
+                        Err(e) if c.required => println!("error\t{}\t{e}", c.name),
+                        Err(e) => println!("warn\t{}\t{e}", c.name),
+                    }
+                }
+            }
+            if let Some(e) = report.failure() {
+                return Err(error::reported(error::code_of(e)));
             }
         }
         Commands::Workspace { command: ws } => match ws {
             WorkspaceCmd::Ls => match belljar_core::list_workspaces() {
                 Ok(list) => {
-                    if list.is_empty() {
+                    if json {
+                        output::print(&output::WorkspaceList { workspaces: list });
+                    } else if list.is_empty() {
                         println!("no workspaces");
                     } else {
                         for w in list {
@@ -402,7 +1210,7 @@ fn main() -> anyhow::Result<()> {
                         }
                     }
                 }
-                Err(e) => eprintln!("failed to list workspaces: {e}"),
+                Err(e) => return Err(error::core("failed to list workspaces", e)),
             },
             WorkspaceCmd::Start {
                 label,
@@ -413,57 +1221,62 @@ fn main() -> anyhow::Result<()> {
                 let root = resolve_repo_path(path.as_deref())?;
                 let repo_paths: Vec<PathBuf> = repos.into_iter().map(|r| root.join(r)).collect();
                 match belljar_core::create_workspace(&label, &root, repo_paths) {
+                    Ok(ws) if json => output::print(&output::WorkspaceDoc { workspace: ws }),
                     Ok(ws) => {
                         println!("created workspace: {}", ws.label);
                         if open {
-                            let _ = belljar_core::tmux::ensure_named_session(
+                            belljar_core::tmux::ensure_named_session(
                                 &ws.tmux_session,
                                 &ws.root_path,
-                            );
-                            let _ = belljar_core::tmux::attach(&ws.tmux_session);
+                            )
+                            .map_err(|e| error::core("failed to ensure workspace session", e))?;
+                            belljar_core::tmux::attach(&ws.tmux_session)
+                                .map_err(|e| error::core("failed to attach workspace", e))?;
                         }
                     }
-                    Err(e) => eprintln!("failed to create workspace: {e}"),
+                    Err(e) => return Err(error::core("failed to create workspace", e)),
                 }
             }
-            WorkspaceCmd::Open { label } => match belljar_core::find_workspace(&label) {
-                Ok(Some(ws)) => {
-                    if let Err(e) =
-                        belljar_core::tmux::ensure_named_session(&ws.tmux_session, &ws.root_path)
-                    {
-                        eprintln!("failed to ensure workspace session: {e}");
-                    }
-                    for repo in &ws.repos {
-                        let name = repo
-                            .file_name()
-                            .map(|s| s.to_string_lossy().to_string())
-                            .unwrap_or_else(|| repo.display().to_string());
-                        if let Err(e) =
-                            belljar_core::tmux::new_window(&ws.tmux_session, &name, repo)
-                        {
-                            eprintln!("failed to create window for {name}: {e}");
-                        }
-                    }
-                    let _ = belljar_core::tmux::select_layout(&ws.tmux_session, "tiled");
-                    if let Err(e) = belljar_core::tmux::attach(&ws.tmux_session) {
-                        eprintln!("failed to attach workspace: {e}");
+            WorkspaceCmd::Open { label } => {
+                let ws = belljar_core::find_workspace(&label)
+                    .map_err(|e| error::core("failed to load registry", e))?
+                    .ok_or_else(|| error::not_found("workspace", &label))?;
+                belljar_core::tmux::ensure_named_session(&ws.tmux_session, &ws.root_path)
+                    .map_err(|e| error::core("failed to ensure workspace session", e))?;
+                for repo in &ws.repos {
+                    let name = repo
+                        .file_name()
+                        .map(|s| s.to_string_lossy().to_string())
+                        .unwrap_or_else(|| repo.display().to_string());
+                    if let Err(e) = belljar_core::tmux::new_window(&ws.tmux_session, &name, repo) {
+                        eprintln!("failed to create window for {name}: {e}");
                     }
                 }
-                Ok(None) => println!("no such workspace: {label}"),
-                Err(e) => eprintln!("failed to load registry: {e}"),
-            },
+                let layout = load_config(Some(&ws.root_path))?.layout.value;
+                let _ = belljar_core::tmux::select_layout(&ws.tmux_session, &layout);
+                belljar_core::tmux::attach(&ws.tmux_session)
+                    .map_err(|e| error::core("failed to attach workspace", e))?;
+            }
             WorkspaceCmd::Rm { target } => match belljar_core::remove_workspace(&target) {
+                Ok(Some(ws)) if json => output::print(&output::WorkspaceDoc { workspace: ws }),
                 Ok(Some(ws)) => println!("removed workspace {}", ws.label),
-                Ok(None) => println!("no such workspace: {target}"),
-                Err(e) => eprintln!("failed to remove workspace: {e}"),
+                Ok(None) => return Err(error::not_found("workspace", &target)),
+                Err(e) => return Err(error::core("failed to remove workspace", e)),
             },
         },
         Commands::Version => {
-            println!(
-                "belljar {} (core {})",
-                env!("CARGO_PKG_VERSION"),
-                belljar_core::version()
-            );
+            if json {
+                output::print(&output::VersionDoc {
+                    version: env!("CARGO_PKG_VERSION"),
+                    core: belljar_core::version(),
+                });
+            } else {
+                println!(
+                    "belljar {} (core {})",
+                    env!("CARGO_PKG_VERSION"),
+                    belljar_core::version()
+                );
+            }
         }
         Commands::Wizard => {
             run_wizard()?;
@@ -472,13 +1285,325 @@ fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// `--with`/`--profile` values without blanks or duplicates, in the order given.
+fn name_list(names: Vec<String>) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for name in names {
+        let name = name.trim();
+        if !name.is_empty() && !out.iter().any(|n| n == name) {
+            out.push(name.to_string());
+        }
+    }
+    out
+}
+
+/// Single-quote `value` for POSIX shells unless it is plainly safe.
+fn shell_quote(value: &str) -> String {
+    let plain = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-./:@,+=".contains(c));
+    if plain {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+/// The engine picked with `--docker-context`/`--docker-host`; an empty value
+/// picks the default engine over the configured one.
+fn engine_target(context: Option<String>, host: Option<String>) -> Option<Target> {
+    match (context, host) {
+        (Some(name), _) if !name.is_empty() => Some(Target::Context(name)),
+        (_, Some(host)) if !host.is_empty() => Some(Target::Host(host)),
+        (None, None) => None,
+        _ => Some(Target::Local),
+    }
+}
+
+/// Caps from `--cpus`/`--memory` entries, later entries winning.
+fn limit_list(
+    cpus: &[String],
+    memory: &[String],
+) -> anyhow::Result<Vec<belljar_core::limits::Limit>> {
+    let mut out = Vec::new();
+    let entries = cpus
+        .iter()
+        .map(|e| ("cpus", e))
+        .chain(memory.iter().map(|e| ("memory", e)));
+    for (resource, entry) in entries {
+        let limit = belljar_core::limits::parse(resource, entry).map_err(error::core_bare)?;
+        belljar_core::limits::merge(&mut out, limit);
+    }
+    Ok(out)
+}
+
+fn up_tag(done: &Provisioned) -> String {
+    let session = &done.session;
+    let mut tag = if session.services.is_empty() {
+        "compose up".to_string()
+    } else {
+        format!("compose up: {}", session.services.join(","))
+    };
+    if let Some(stack) = &session.stack {
+        tag.push_str(&format!("; stack: {stack}"));
+    }
+    if !session.profiles.is_empty() {
+        tag.push_str(&format!("; profiles: {}", session.profiles.join(",")));
+    }
+    if !done.copied_volumes.is_empty() {
+        tag.push_str(&format!(
+            "; volumes copied: {}",
+            done.copied_volumes.join(",")
+        ));
+    }
+    tag
+}
+
+fn created_doc(done: &Provisioned, replaced: bool, base: Option<String>) -> output::Created {
+    output::Created {
+        session: output::SessionEntry::probe(vec![done.session.clone()]).remove(0),
+        created: true,
+        replaced,
+        compose_up: done.compose_up,
+        base,
+        copied_volumes: done.copied_volumes.clone(),
+    }
+}
+
+/// Provision a new session, first tearing down any existing session with the
+/// same label when `replace` is set. Returns whether one was replaced.
+fn provision(plan: Plan, replace: bool, json: bool) -> anyhow::Result<(Provisioned, bool)> {
+    let mut replaced = false;
+    if replace {
+        if let Some(old) = belljar_core::find_session(&plan.label)
+            .map_err(|e| error::core("find session failed", e))?
+        {
+            // Keep the worktree so the new session picks it back up
+            let removed = belljar_core::lifecycle::teardown(&old, Teardown::default())
+                .map_err(|e| error::core("remove failed", e))?;
+            for (step, e) in removed.failures {
+                eprintln!("warning: {step} failed for {}: {e}", old.label);
+            }
+            if !json {
+                println!("replaced {}", old.label);
+            }
+            replaced = true;
+        }
+    }
+    let mut progress = wait_progress(plan.label.clone(), json);
+    let done =
+        belljar_core::lifecycle::provision_with_progress(&plan, &mut progress).map_err(|e| {
+            let code = error::code_of(&e);
+            let message = match e {
+                belljar_core::CoreError::LabelConflict { .. } => {
+                    format!("create session failed: {e} (use --replace to recreate it)")
+                }
+                belljar_core::CoreError::StepFailed { .. } if plan.keep_on_failure => {
+                    format!("{e} (partial session {} kept)", plan.label)
+                }
+                belljar_core::CoreError::StepFailed { .. } => {
+                    format!("{e} (session {} rolled back)", plan.label)
+                }
+                e => format!("create session failed: {e}"),
+            };
+            error::fail(code, message)
+        })?;
+    Ok((done, replaced))
+}
+
+/// Prints each service state change while `--wait` blocks (nothing with
+/// `--json`).
+fn wait_progress(label: String, json: bool) -> impl FnMut(&belljar_core::compose::ServiceState) {
+    let mut waiting = false;
+    move |s| {
+        if json {
+            return;
+        }
+        if !waiting {
+            println!("waiting for services of {label}...");
+            waiting = true;
+        }
+        match s.health.as_str() {
+            "" => println!("  {}: {}", s.service, s.state),
+            health => println!("  {}: {} ({health})", s.service, s.state),
+        }
+    }
+}
+
+/// Tear down one session, reporting best-effort failures as warnings.
+/// Returns the warnings, or why removal was refused or failed.
+fn remove_session(
+    s: &belljar_core::Session,
+    opts: Teardown,
+    json: bool,
+) -> Result<Vec<String>, belljar_core::CoreError> {
+    match belljar_core::lifecycle::teardown(s, opts) {
+        Ok(removed) => {
+            let mut warnings = Vec::new();
+            for (step, e) in removed.failures {
+                eprintln!("warning: {step} failed for {}: {e}", s.label);
+                warnings.push(format!("{step} failed: {e}"));
+            }
+            if !json {
+                println!("removed {}", s.label);
+            }
+            Ok(warnings)
+        }
+        Err(e @ belljar_core::CoreError::UnsafeRemoval { .. }) => {
+            eprintln!("error: {e} (use --force to remove anyway)");
+            Err(e)
+        }
+        Err(e) => {
+            eprintln!("error: remove {} failed: {e}", s.label);
+            Err(e)
+        }
+    }
+}
+
+fn load_config(repo: Option<&Path>) -> anyhow::Result<belljar_core::config::Config> {
+    belljar_core::config::load(repo).map_err(error::core_bare)
+}
+
+fn print_status(s: &belljar_core::Session, d: &belljar_core::status::Detail) {
+    println!(
+        "session:   {} ({}){}",
+        s.label,
+        s.id,
+        if s.template { " [template]" } else { "" }
+    );
+    println!("repo:      {}", s.repo_path.display());
+    if let Some(wt) = &s.worktree_path {
+        let state = if !d.live.worktree {
+            "missing".to_string()
+        } else {
+            let branch = d.branch.as_deref().unwrap_or("detached");
+            match d.dirty {
+                Some(true) => format!("{branch}, dirty"),
+                Some(false) => format!("{branch}, clean"),
+                None => branch.to_string(),
+            }
+        };
+        println!("worktree:  {} ({state})", wt.display());
+    }
+    let tmux = match d.live.tmux {
+        Some(true) => "running",
+        Some(false) => "not running",
+        None => "tmux not found",
+    };
+    println!("tmux:      {} ({tmux})", s.tmux_session);
+    println!(
+        "project:   {} ({}{})",
+        s.compose_project,
+        d.live
+            .compose
+            .as_deref()
+            .unwrap_or("compose runtime unavailable"),
+        if s.stopped {
+            "; stopped by belljar"
+        } else {
+            ""
+        }
+    );
+    println!("engine:    {}", Target::of(s));
+    if !s.limits.is_empty() {
+        let limits: Vec<String> = s
+            .limits
+            .iter()
+            .map(|l| {
+                format!(
+                    "{} {}",
+                    l.service.as_deref().unwrap_or("each service"),
+                    l.summary()
+                )
+            })
+            .collect();
+        println!("limits:    {}", limits.join("; "));
+    }
+    match &d.services {
+        Some(services) if !services.is_empty() => {
+            println!("services:");
+            for svc in services {
+                let health = if svc.health.is_empty() {
+                    "-"
+                } else {
+                    &svc.health
+                };
+                let ports = if svc.ports.is_empty() {
+                    "-".to_string()
+                } else {
+                    svc.ports.join(", ")
+                };
+                println!("  {}\t{}\t{}\t{}", svc.service, svc.state, health, ports);
+            }
+        }
+        Some(_) => println!("services:  none running"),
+        None => {}
+    }
+}
+
+/// Print the logs saved at the last teardown of `label`, one section per
+/// service.
+fn print_saved_logs(label: &str, services: &[String]) -> anyhow::Result<()> {
+    let dir = belljar_core::compose::latest_saved_logs(label)
+        .map_err(|e| error::core("failed to read saved logs", e))?
+        .ok_or_else(|| error::fail(error::Code::NotFound, format!("no saved logs for {label}")))?;
+    let mut files: Vec<PathBuf> = fs::read_dir(&dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            let svc = p.file_stem().map(|s| s.to_string_lossy().to_string());
+            services.is_empty() || svc.is_some_and(|s| services.contains(&s))
+        })
+        .collect();
+    files.sort();
+    println!("# {}", dir.display());
+    for f in files {
+        let svc = f.file_stem().unwrap_or_default().to_string_lossy();
+        println!("==> {svc} <==");
+        print!("{}", String::from_utf8_lossy(&fs::read(&f)?));
+    }
+    Ok(())
+}
+
+fn require_session(label: &str) -> anyhow::Result<belljar_core::Session> {
+    belljar_core::find_session(label)
+        .map_err(|e| error::core("failed to load registry", e))?
+        .ok_or_else(|| error::not_found("session", label))
+}
+
+/// Ensure the session's tmux session exists and switch to or attach it,
+/// printing a `cd` hint when tmux is not installed.
+fn focus_session(s: &belljar_core::Session) -> anyhow::Result<()> {
+    let focused = belljar_core::tmux::ensure_session(s).and_then(|()| {
+        if std::env::var("TMUX").is_ok() {
+            belljar_core::tmux::switch_client(&s.tmux_session)
+        } else {
+            belljar_core::tmux::attach(&s.tmux_session)
+        }
+    });
+    match focused {
+        Ok(()) => Ok(()),
+        Err(belljar_core::CoreError::TmuxNotFound) => {
+            println!(
+                "tmux not found; cd {} to work in this session",
+                s.repo_path.display()
+            );
+            Ok(())
+        }
+        Err(e) => Err(error::core("failed to focus session", e)),
+    }
+}
+
 fn resolve_repo_path(path: Option<&Path>) -> anyhow::Result<PathBuf> {
     let p = match path {
         Some(p) => p.to_path_buf(),
         None => std::env::current_dir()?,
     };
     if !p.exists() {
-        anyhow::bail!("path does not exist: {}", p.display());
+        return Err(error::fail(
+            error::Code::Usage,
+            format!("path does not exist: {}", p.display()),
+        ));
     }
     Ok(p)
 }
@@ -0,0 +1,275 @@
+//! JSON output for `--json`. Every document is a single object carrying
+//! `format_version`; bump `FORMAT_VERSION` whenever a field is renamed,
+//! removed or changes type (adding fields does not bump it). The shapes are
+//! documented in `docs/spec.md`.
+
+use crate::error::Code;
+use belljar_core::adopt::Candidate;
+use belljar_core::compose::ServiceState;
+use belljar_core::doctor::Report;
+use belljar_core::ports::PortMapping;
+use belljar_core::runtime::Kind;
+use belljar_core::status::Live;
+use belljar_core::volumes::Snapshot;
+use belljar_core::{Session, Workspace};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct Envelope<'a, T: Serialize> {
+    format_version: u32,
+    #[serde(flatten)]
+    body: &'a T,
+}
+
+/// Print `body` as one line of JSON on stdout.
+pub fn print<T: Serialize>(body: &T) {
+    let doc = Envelope {
+        format_version: FORMAT_VERSION,
+        body,
+    };
+    println!(
+        "{}",
+        serde_json::to_string(&doc).expect("output types always serialize")
+    );
+}
+
+#[derive(Serialize)]
+pub struct ErrorBody {
+    /// Error kind, see `error::Code::name`.
+    pub kind: &'static str,
+    /// Process exit code.
+    pub code: u8,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+pub struct ErrorDoc {
+    pub error: ErrorBody,
+}
+
+pub fn error(e: &anyhow::Error, code: Code) {
+    print(&ErrorDoc {
+        error: ErrorBody {
+            kind: code.name(),
+            code: code as u8,
+            message: e.to_string(),
+        },
+    });
+}
+
+/// A registry session plus what is actually running for it.
+#[derive(Serialize)]
+pub struct SessionEntry {
+    #[serde(flatten)]
+    pub session: Session,
+    pub status: Live,
+}
+
+impl SessionEntry {
+    pub fn probe(sessions: Vec<Session>) -> Vec<SessionEntry> {
+        let live = belljar_core::status::probe(&sessions);
+        sessions
+            .into_iter()
+            .zip(live)
+            .map(|(session, status)| SessionEntry { session, status })
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+pub struct SessionList {
+    pub sessions: Vec<SessionEntry>,
+}
+
+/// `status`: a session plus git and per-service detail.
+#[derive(Serialize)]
+pub struct StatusDoc {
+    pub session: SessionEntry,
+    pub branch: Option<String>,
+    pub dirty: Option<bool>,
+    pub services: Option<Vec<ServiceState>>,
+}
+
+#[derive(Serialize)]
+pub struct WorkspaceList {
+    pub workspaces: Vec<Workspace>,
+}
+
+/// Result of `stop` and `resume`.
+#[derive(Serialize)]
+pub struct SessionDoc {
+    pub session: SessionEntry,
+}
+
+/// Result of `start`, `new` and `checkout`.
+#[derive(Serialize)]
+pub struct Created {
+    pub session: SessionEntry,
+    /// False when `new` found an existing session with the label.
+    pub created: bool,
+    /// An existing session was torn down first (`--replace`).
+    pub replaced: bool,
+    pub compose_up: bool,
+    /// Base branch for `new`.
+    pub base: Option<String>,
+    /// Compose volume keys copied from `--from-volumes`.
+    pub copied_volumes: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct RemovedEntry {
+    pub label: String,
+    /// Best-effort cleanup steps that failed.
+    pub warnings: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct Refused {
+    pub label: String,
+    pub error: String,
+}
+
+#[derive(Serialize)]
+pub struct RemoveResult {
+    pub removed: Vec<RemovedEntry>,
+    pub refused: Vec<Refused>,
+}
+
+#[derive(Serialize)]
+pub struct PortsDoc {
+    pub label: String,
+    pub ports: Vec<PortMapping>,
+    pub env: BTreeMap<String, String>,
+}
+
+#[derive(Serialize)]
+pub struct SnapshotDoc {
+    pub snapshot: Snapshot,
+}
+
+#[derive(Serialize)]
+pub struct SnapshotList {
+    pub snapshots: Vec<Snapshot>,
+}
+
+#[derive(Serialize)]
+pub struct EnvDoc {
+    pub label: String,
+    pub env: BTreeMap<String, String>,
+}
+
+#[derive(Serialize)]
+pub struct SendResult {
+    pub sent: Vec<String>,
+    pub failed: Vec<Refused>,
+}
+
+#[derive(Serialize)]
+pub struct Adopted {
+    #[serde(flatten)]
+    pub candidate: Candidate,
+    pub adopted: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct AdoptResult {
+    pub dry_run: bool,
+    pub candidates: Vec<Adopted>,
+}
+
+#[derive(Serialize)]
+pub struct GcFinding {
+    pub kind: &'static str,
+    pub label: String,
+    pub issue: String,
+    pub action: String,
+    /// Outcome of `--fix`: absent when not attempted, null on success.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix_error: Option<Option<String>>,
+}
+
+#[derive(Serialize)]
+pub struct GcReport {
+    pub findings: Vec<GcFinding>,
+    pub orphan_projects: Vec<String>,
+    pub compose_checked: bool,
+    pub tmux_checked: bool,
+    pub worktrees_checked: bool,
+    pub clean: bool,
+}
+
+#[derive(Serialize)]
+pub struct Setting {
+    pub key: String,
+    pub value: String,
+    pub source: String,
+}
+
+#[derive(Serialize)]
+pub struct ConfigDoc {
+    pub settings: Vec<Setting>,
+}
+
+#[derive(Serialize)]
+pub struct RuntimeDoc {
+    pub kind: Kind,
+    /// The compose invocation, e.g. `podman compose`.
+    pub command: String,
+    pub detected: bool,
+}
+
+#[derive(Serialize)]
+pub struct CheckDoc {
+    pub name: &'static str,
+    pub required: bool,
+    pub ok: bool,
+    pub version: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Result of `doctor`.
+#[derive(Serialize)]
+pub struct DoctorDoc {
+    pub runtime: RuntimeDoc,
+    pub checks: Vec<CheckDoc>,
+    pub ok: bool,
+}
+
+impl DoctorDoc {
+    pub fn new(report: &Report) -> Self {
+        Self {
+            runtime: RuntimeDoc {
+                kind: report.runtime.kind,
+                command: report.runtime.to_string(),
+                detected: report.runtime.detected,
+            },
+            checks: report
+                .checks
+                .iter()
+                .map(|c| CheckDoc {
+                    name: c.name,
+                    required: c.required,
+                    ok: c.result.is_ok(),
+                    version: c.result.as_ref().ok().cloned(),
+                    error: c.result.as_ref().err().map(|e| e.to_string()),
+                })
+                .collect(),
+            ok: report.failure().is_none(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct WorkspaceDoc {
+    pub workspace: Workspace,
+}
+
+#[derive(Serialize)]
+pub struct VersionDoc {
+    pub version: &'static str,
+    pub core: &'static str,
+}
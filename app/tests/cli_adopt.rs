@@ -0,0 +1,159 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn init_git_repo() -> tempfile::TempDir {
+    let td = TempDir::new().unwrap();
+    let repo = td.path();
+    assert!(Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .arg("init")
+        .status()
+        .unwrap()
+        .success());
+    assert!(Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["config", "user.email", "ci@example.com"])
+        .status()
+        .unwrap()
+        .success());
+    assert!(Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["config", "user.name", "CI"])
+        .status()
+        .unwrap()
+        .success());
+    fs::write(repo.join("README.md"), "init\n").unwrap();
+    assert!(Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["add", "."])
+        .status()
+        .unwrap()
+        .success());
+    assert!(Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["commit", "-m", "init"])
+        .status()
+        .unwrap()
+        .success());
+    td
+}
+
+fn prepend_path(dir: &Path) -> String {
+    let old = std::env::var("PATH").unwrap_or_default();
+    format!("{}:{}", dir.display(), old)
+}
+
+/// tmux and docker shims reporting a running session and a project in `wt`
+fn make_shims(wt: &Path) -> (TempDir, std::path::PathBuf) {
+    let dir = TempDir::new().unwrap();
+    let log = dir.path().join("docker.log");
+    let tmux = "#!/usr/bin/env bash\nif [ \"$1\" = list-sessions ]; then echo handmade; echo other; fi\nexit 0\n".to_string();
+    let docker = format!(
+        "#!/usr/bin/env bash\necho \"$@\" >> {}\nif [ \"$2\" = ls ]; then echo '[{{\"Name\":\"legacy_stack\",\"Status\":\"running(2)\",\"ConfigFiles\":\"{}/docker-compose.yml\"}}]'; fi\nexit 0\n",
+        log.display(),
+        wt.display()
+    );
+    for (name, script) in [("tmux", tmux), ("docker", docker)] {
+        let shim = dir.path().join(name);
+        fs::write(&shim, script).unwrap();
+        let mut perm = fs::metadata(&shim).unwrap().permissions();
+        perm.set_mode(0o755);
+        fs::set_permissions(&shim, perm).unwrap();
+    }
+    (dir, log)
+}
+
+#[test]
+fn adopt_imports_handmade_worktree_once() {
+    let data = TempDir::new().unwrap();
+    let repo = init_git_repo();
+    let outside = TempDir::new().unwrap();
+    let wt = outside.path().join("handmade");
+    assert!(Command::new("git")
+        .arg("-C")
+        .arg(repo.path())
+        .args(["worktree", "add", "-b", "feature-x"])
+        .arg(&wt)
+        .status()
+        .unwrap()
+        .success());
+    let (shim_dir, log) = make_shims(&wt);
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["adopt", "--tmux", "--compose", "--path"])
+        .arg(repo.path())
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim_dir.path()))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("adopted handmade -> "))
+        .stdout(predicate::str::contains(
+            "tmux: handmade, project: legacy_stack",
+        ));
+
+    // Nothing new was started
+    let logged = fs::read_to_string(&log).unwrap();
+    assert!(!logged.contains("up -d"));
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .arg("ls")
+        .env("BELLJAR_DATA_DIR", data.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("handmade\t"))
+        .stdout(predicate::str::contains("legacy_stack"));
+
+    // Already-registered worktrees are skipped
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["adopt", "--path"])
+        .arg(repo.path())
+        .env("BELLJAR_DATA_DIR", data.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("nothing to adopt"));
+}
+
+#[test]
+fn adopt_dry_run_records_nothing() {
+    let data = TempDir::new().unwrap();
+    let repo = init_git_repo();
+    let outside = TempDir::new().unwrap();
+    assert!(Command::new("git")
+        .arg("-C")
+        .arg(repo.path())
+        .args(["worktree", "add", "-b", "y"])
+        .arg(outside.path().join("wt-y"))
+        .status()
+        .unwrap()
+        .success());
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["adopt", "--dry-run", "--path"])
+        .arg(repo.path())
+        .env("BELLJAR_DATA_DIR", data.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("would adopt wt-y"));
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .arg("ls")
+        .env("BELLJAR_DATA_DIR", data.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("no sessions"));
+}
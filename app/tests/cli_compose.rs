@@ -32,6 +32,11 @@ fn init_git_repo_with_compose() -> (tempfile::TempDir, PathBuf) {
         .unwrap()
         .success());
     fs::write(repo.join("README.md"), "init\n").unwrap();
+    // compose file is committed so session worktrees have it too
+    let bj = repo.join(".belljar/compose");
+    fs::create_dir_all(&bj).unwrap();
+    let cf = bj.join("svc.yml");
+    fs::write(&cf, "services: {}\n").unwrap();
     assert!(Command::new("git")
         .arg("-C")
         .arg(repo)
@@ -46,11 +51,6 @@ fn init_git_repo_with_compose() -> (tempfile::TempDir, PathBuf) {
         .status()
         .unwrap()
         .success());
-    // add compose file
-    let bj = repo.join(".belljar/compose");
-    fs::create_dir_all(&bj).unwrap();
-    let cf = bj.join("svc.yml");
-    fs::write(&cf, "services: {}\n").unwrap();
     (td, cf)
 }
 
@@ -105,7 +105,7 @@ fn checkout_with_compose_runs_up() {
     assert!(Command::new("git")
         .arg("-C")
         .arg(repo_td.path())
-        .args(["checkout", "-b", "fx"])
+        .args(["branch", "fx"])
         .status()
         .unwrap()
         .success());
@@ -186,3 +186,132 @@ fn rm_all_triggers_down_for_all() {
         "expected at least two down invocations, got {count} in: {logged}"
     );
 }
+
+/// Commit `extra.yml` on a new branch `fx` only
+fn branch_with_extra_compose_file(repo: &Path) {
+    assert!(Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["checkout", "-q", "-b", "fx"])
+        .status()
+        .unwrap()
+        .success());
+    fs::write(repo.join(".belljar/compose/extra.yml"), "services: {}\n").unwrap();
+    for args in [
+        vec!["add", "."],
+        vec!["commit", "-q", "-m", "extra"],
+        vec!["checkout", "-q", "-"],
+    ] {
+        assert!(Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(&args)
+            .status()
+            .unwrap()
+            .success());
+    }
+}
+
+fn down_line(log: &Path) -> String {
+    fs::read_to_string(log)
+        .unwrap()
+        .lines()
+        .find(|l| l.contains(" down "))
+        .expect("down was run")
+        .to_string()
+}
+
+#[test]
+fn compose_files_come_from_the_worktree() {
+    let data = TempDir::new().unwrap();
+    let (repo_td, _cf) = init_git_repo_with_compose();
+    branch_with_extra_compose_file(repo_td.path());
+    let (shim_dir, log) = make_docker_shim_logging();
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["checkout", "fx", "--path"])
+        .arg(repo_td.path())
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim_dir.path()))
+        .assert()
+        .success();
+
+    let wt_files = repo_td
+        .path()
+        .join(".belljar/worktrees/fx/.belljar/compose");
+    let up = fs::read_to_string(&log)
+        .unwrap()
+        .lines()
+        .find(|l| l.contains(" up -d"))
+        .unwrap()
+        .to_string();
+    assert!(
+        up.contains(&format!("-f {}", wt_files.join("svc.yml").display())),
+        "{up}"
+    );
+    assert!(
+        up.contains(&format!("-f {}", wt_files.join("extra.yml").display())),
+        "{up}"
+    );
+
+    // Files added after `up` are not picked up by `down`
+    fs::write(wt_files.join("later.yml"), "services: {}\n").unwrap();
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["rm", "fx", "--force"])
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim_dir.path()))
+        .assert()
+        .success();
+    let down = down_line(&log);
+    assert!(down.contains("extra.yml"), "{down}");
+    assert!(!down.contains("later.yml"), "{down}");
+    assert!(down.ends_with("down -v --remove-orphans"), "{down}");
+}
+
+#[test]
+fn down_by_project_name_when_recorded_files_are_gone() {
+    let data = TempDir::new().unwrap();
+    let (repo_td, _cf) = init_git_repo_with_compose();
+    branch_with_extra_compose_file(repo_td.path());
+    let (shim_dir, log) = make_docker_shim_logging();
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["checkout", "fx", "--path"])
+        .arg(repo_td.path())
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim_dir.path()))
+        .assert()
+        .success();
+    let out = Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["--json", "ls"])
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim_dir.path()))
+        .assert()
+        .success();
+    let doc: serde_json::Value = serde_json::from_slice(&out.get_output().stdout).unwrap();
+    let session = &doc["sessions"][0];
+    assert_eq!(session["compose_files"].as_array().unwrap().len(), 2);
+    let project = session["compose_project"].as_str().unwrap().to_string();
+
+    fs::remove_file(
+        repo_td
+            .path()
+            .join(".belljar/worktrees/fx/.belljar/compose/extra.yml"),
+    )
+    .unwrap();
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["rm", "fx", "--force"])
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim_dir.path()))
+        .assert()
+        .success();
+    assert_eq!(
+        down_line(&log),
+        format!("compose -p {project} down -v --remove-orphans")
+    );
+}
@@ -0,0 +1,33 @@
+use assert_cmd::cargo::CommandCargoExt;
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+use tempfile::TempDir;
+
+#[test]
+fn parallel_starts_all_land_in_registry() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+
+    let children: Vec<_> = (0..6)
+        .map(|i| {
+            Command::cargo_bin("belljar")
+                .unwrap()
+                .args(["start", &format!("p{i}"), "--path"])
+                .arg(repo.path())
+                .env("BELLJAR_DATA_DIR", data.path())
+                .spawn()
+                .unwrap()
+        })
+        .collect();
+    for mut c in children {
+        assert!(c.wait().unwrap().success());
+    }
+
+    let mut ls = Command::cargo_bin("belljar").unwrap();
+    ls.arg("ls").env("BELLJAR_DATA_DIR", data.path());
+    let mut assert = ls.assert().success();
+    for i in 0..6 {
+        assert = assert.stdout(predicate::str::contains(format!("p{i}\t")));
+    }
+}
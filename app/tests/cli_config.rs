@@ -0,0 +1,172 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::process::Command;
+use tempfile::TempDir;
+
+#[test]
+fn config_show_reports_values_and_sources() {
+    let data = TempDir::new().unwrap();
+    let user = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    fs::write(
+        user.path().join("config.toml"),
+        "layout = \"even-vertical\"\n",
+    )
+    .unwrap();
+    fs::create_dir_all(repo.path().join(".belljar")).unwrap();
+    fs::write(
+        repo.path().join(".belljar/config.toml"),
+        "base_branch = \"trunk\"\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("belljar").unwrap();
+    cmd.env("BELLJAR_DATA_DIR", data.path())
+        .env("BELLJAR_CONFIG_DIR", user.path())
+        .env("BELLJAR_DOCKER", "podman")
+        .args(["--set", "project_prefix=bj_", "config", "show", "-p"])
+        .arg(repo.path());
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("base_branch\ttrunk\trepo ("))
+        .stdout(predicate::str::contains("layout\teven-vertical\tuser ("))
+        .stdout(predicate::str::contains(
+            "docker\tpodman\tenv (BELLJAR_DOCKER)",
+        ))
+        .stdout(predicate::str::contains("project_prefix\tbj_\tcli"))
+        .stdout(predicate::str::contains(
+            "worktree_dir\t.belljar/worktrees\tdefault",
+        ));
+}
+
+#[test]
+fn invalid_config_is_reported() {
+    let data = TempDir::new().unwrap();
+    let user = TempDir::new().unwrap();
+    fs::write(user.path().join("config.toml"), "bogus = true\n").unwrap();
+    let mut cmd = Command::cargo_bin("belljar").unwrap();
+    cmd.env("BELLJAR_DATA_DIR", data.path())
+        .env("BELLJAR_CONFIG_DIR", user.path())
+        .args(["config", "show"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("config error"));
+
+    let mut cmd = Command::cargo_bin("belljar").unwrap();
+    cmd.env("BELLJAR_DATA_DIR", data.path())
+        .args(["--set", "colour=blue", "ls"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown config key: colour"));
+}
+
+#[test]
+fn config_file_values_are_validated_like_overrides() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    fs::create_dir_all(repo.path().join(".belljar")).unwrap();
+    for (text, needle) in [
+        ("runtime = \"podmn\"\n", "runtime must be auto or one of"),
+        ("cpus = \"db=lots\"\n", "cpus: "),
+        ("memory = \"1q\"\n", "memory: "),
+        ("port_base = 70000\n", "port_base"),
+    ] {
+        fs::write(repo.path().join(".belljar/config.toml"), text).unwrap();
+        let mut cmd = Command::cargo_bin("belljar").unwrap();
+        cmd.env("BELLJAR_DATA_DIR", data.path())
+            .args(["config", "show", "-p"])
+            .arg(repo.path());
+        cmd.assert()
+            .code(10)
+            .stderr(predicate::str::contains("config.toml"))
+            .stderr(predicate::str::contains(needle));
+    }
+}
+
+#[test]
+fn env_table_entries_are_shown_and_validated() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    fs::create_dir_all(repo.path().join(".belljar")).unwrap();
+    fs::write(
+        repo.path().join(".belljar/config.toml"),
+        "[env]\nDB_NAME = \"app_{slug}\"\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("belljar").unwrap();
+    cmd.env("BELLJAR_DATA_DIR", data.path())
+        .args(["--set", "env.MODE=dev", "config", "show", "-p"])
+        .arg(repo.path());
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("env.DB_NAME\tapp_{slug}\trepo ("))
+        .stdout(predicate::str::contains("env.MODE\tdev\tcli"));
+
+    let mut cmd = Command::cargo_bin("belljar").unwrap();
+    cmd.env("BELLJAR_DATA_DIR", data.path())
+        .args(["--set", "env.1BAD=x", "ls"]);
+    cmd.assert()
+        .code(10)
+        .stderr(predicate::str::contains("1BAD"));
+}
+
+#[test]
+fn project_prefix_and_docker_binary_come_from_config() {
+    let data = TempDir::new().unwrap();
+    let user = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    fs::write(repo.path().join("docker-compose.yml"), "services: {}\n").unwrap();
+    fs::create_dir_all(repo.path().join(".belljar")).unwrap();
+    fs::write(
+        repo.path().join(".belljar/config.toml"),
+        "project_prefix = \"team_\"\ndocker = \"definitely-not-docker\"\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("belljar").unwrap();
+    cmd.env("BELLJAR_DATA_DIR", data.path())
+        .env("BELLJAR_CONFIG_DIR", user.path())
+        .args(["start", "cfg", "-p"])
+        .arg(repo.path());
+    // The configured docker binary does not exist, so compose up fails
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("compose up failed"));
+
+    let mut cmd = Command::cargo_bin("belljar").unwrap();
+    cmd.env("BELLJAR_DATA_DIR", data.path())
+        .env("BELLJAR_CONFIG_DIR", user.path())
+        .args(["--set", "docker=true", "start", "cfg", "-p"])
+        .arg(repo.path());
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("(project: team_"));
+}
+
+#[test]
+fn invalid_repo_config_stops_commands_instead_of_falling_back() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    fs::create_dir_all(repo.path().join(".belljar")).unwrap();
+    fs::write(
+        repo.path().join(".belljar/config.toml"),
+        "worktree_dir = \"wt\"\nbogus = 1\n",
+    )
+    .unwrap();
+    for args in [vec!["start", "a", "-p"], vec!["doctor", "-p"]] {
+        let mut cmd = Command::cargo_bin("belljar").unwrap();
+        cmd.env("BELLJAR_DATA_DIR", data.path())
+            .args(args)
+            .arg(repo.path());
+        cmd.assert()
+            .code(10)
+            .stderr(predicate::str::contains("bogus"));
+    }
+    assert!(!repo.path().join(".belljar/worktrees").exists());
+    let mut cmd = Command::cargo_bin("belljar").unwrap();
+    cmd.env("BELLJAR_DATA_DIR", data.path())
+        .args(["status", "a"]);
+    cmd.assert().code(3);
+}
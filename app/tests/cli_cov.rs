@@ -99,7 +99,7 @@ fn checkout_flow() {
     assert!(Command::new("git")
         .arg("-C")
         .arg(repo.path())
-        .args(["checkout", "-b", "fx"])
+        .args(["branch", "fx"])
         .status()
         .unwrap()
         .success());
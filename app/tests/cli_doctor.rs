@@ -0,0 +1,148 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+fn prepend_path(dir: &Path) -> String {
+    let old = std::env::var("PATH").unwrap_or_default();
+    format!("{}:{}", dir.display(), old)
+}
+
+fn write_exe(path: &Path, script: &str) {
+    fs::write(path, script).unwrap();
+    let mut perm = fs::metadata(path).unwrap().permissions();
+    perm.set_mode(0o755);
+    fs::set_permissions(path, perm).unwrap();
+}
+
+/// A directory holding only `git` and a logging `name` shim, to be used as
+/// the whole PATH so no other runtime can be detected
+fn isolated_path_with(name: &str) -> (TempDir, PathBuf) {
+    let dir = TempDir::new().unwrap();
+    let log = dir.path().join("calls.log");
+    let git = std::env::split_paths(&std::env::var_os("PATH").unwrap())
+        .map(|d| d.join("git"))
+        .find(|p| p.exists())
+        .unwrap();
+    std::os::unix::fs::symlink(git, dir.path().join("git")).unwrap();
+    write_exe(
+        &dir.path().join(name),
+        &format!(
+            "#!/bin/bash\necho \"$@\" >> {}\n[ \"$*\" = \"compose version\" ] && echo 'podman-compose version 1.0.6'\nexit 0\n",
+            log.display()
+        ),
+    );
+    (dir, log)
+}
+
+#[test]
+fn doctor_reports_detected_runtime() {
+    let (dir, _log) = isolated_path_with("podman");
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .arg("doctor")
+        .env("PATH", dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "runtime\tpodman (podman compose; auto-detected)",
+        ))
+        .stdout(predicate::str::contains(
+            "ok\tcompose\tpodman-compose version 1.0.6",
+        ))
+        .stdout(predicate::str::contains("ok\tgit\tgit version"))
+        .stdout(predicate::str::contains("warn\ttmux\ttmux not found"));
+}
+
+#[test]
+fn doctor_fails_when_configured_runtime_is_missing() {
+    let (dir, _log) = isolated_path_with("podman");
+    let out = Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["--json", "--set", "runtime=nerdctl", "doctor"])
+        .env("PATH", dir.path())
+        .assert()
+        .code(7);
+    let doc: serde_json::Value = serde_json::from_slice(&out.get_output().stdout).unwrap();
+    assert_eq!(doc["runtime"]["kind"], "nerdctl");
+    assert_eq!(doc["runtime"]["command"], "nerdctl compose");
+    assert_eq!(doc["runtime"]["detected"], false);
+    assert_eq!(doc["checks"][0]["name"], "compose");
+    assert_eq!(doc["checks"][0]["ok"], false);
+    assert_eq!(doc["ok"], false);
+}
+
+#[test]
+fn configured_standalone_runtime_runs_sessions() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    fs::write(repo.path().join("docker-compose.yml"), "services: {}\n").unwrap();
+    let (dir, log) = isolated_path_with("docker-compose");
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["start", "v1", "-p"])
+        .arg(repo.path())
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("BELLJAR_RUNTIME", "docker-compose")
+        .env("PATH", prepend_path(dir.path()))
+        .assert()
+        .success();
+    let calls = fs::read_to_string(&log).unwrap();
+    let up = calls.lines().find(|l| l.contains("up -d")).unwrap();
+    assert!(up.starts_with("-p belljar_"), "{up}");
+}
+
+#[test]
+fn unknown_runtime_is_a_config_error() {
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["--set", "runtime=lxc", "doctor"])
+        .assert()
+        .code(10)
+        .stderr(predicate::str::contains("runtime must be auto or one of"));
+}
+
+#[test]
+fn doctor_warns_about_features_compose_v1_lacks() {
+    let (dir, _log) = isolated_path_with("podman");
+    write_exe(
+        &dir.path().join("docker-compose"),
+        "#!/bin/bash\n[ \"$*\" = version ] && echo 'docker-compose version 1.29.2, build 5becea4c'\nexit 0\n",
+    );
+    let out = Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["--json", "--set", "runtime=docker-compose", "doctor"])
+        .env("PATH", dir.path())
+        .assert()
+        .success();
+    let doc: serde_json::Value = serde_json::from_slice(&out.get_output().stdout).unwrap();
+    let features = &doc["checks"][1];
+    assert_eq!(features["name"], "features");
+    assert_eq!(features["required"], false);
+    assert_eq!(features["ok"], false);
+    let error = features["error"].as_str().unwrap();
+    for missing in [
+        "ls --format json",
+        "ps --format json",
+        "config --format json",
+        "!override",
+    ] {
+        assert!(error.contains(missing), "{error}");
+    }
+    assert_eq!(doc["ok"], true);
+
+    // The text form lists the same gaps as a warning
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .arg("doctor")
+        .env("PATH", dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "warn\tfeatures\tcompose error: docker-compose lacks ls",
+        ));
+}
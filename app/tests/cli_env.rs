@@ -0,0 +1,193 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+fn prepend_path(dir: &Path) -> String {
+    let old = std::env::var("PATH").unwrap_or_default();
+    format!("{}:{}", dir.display(), old)
+}
+
+const CONFIG: &str =
+    r#"{"services":{"db":{"ports":[{"target":5432,"published":"5432","protocol":"tcp"}]}}}"#;
+
+/// docker shim that renders a fixed config and logs calls with the session env
+fn make_docker_shim() -> (TempDir, PathBuf) {
+    let dir = TempDir::new().unwrap();
+    let log = dir.path().join("docker.log");
+    let shim = dir.path().join("docker");
+    let script = format!(
+        "#!/usr/bin/env bash\necho \"[$BELLJAR_SESSION $COMPOSE_PROJECT_NAME $DB_NAME] $@\" >> {}\nfor a in \"$@\"; do if [ \"$a\" = config ]; then echo '{}'; fi; done\nexit 0\n",
+        log.display(),
+        CONFIG
+    );
+    fs::write(&shim, script).unwrap();
+    let mut perm = fs::metadata(&shim).unwrap().permissions();
+    perm.set_mode(0o755);
+    fs::set_permissions(&shim, perm).unwrap();
+    (dir, log)
+}
+
+fn belljar(data: &Path, shim: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("belljar").unwrap();
+    cmd.env("BELLJAR_DATA_DIR", data)
+        .env("BELLJAR_PORTS_BASE", "43000")
+        .env("BELLJAR_PORTS_BLOCK", "10")
+        .env("PATH", prepend_path(shim));
+    cmd
+}
+
+fn make_repo(config: &str) -> TempDir {
+    let repo = TempDir::new().unwrap();
+    fs::write(repo.path().join("docker-compose.yml"), "services: {}\n").unwrap();
+    fs::create_dir_all(repo.path().join(".belljar")).unwrap();
+    fs::write(repo.path().join(".belljar/config.toml"), config).unwrap();
+    repo
+}
+
+#[test]
+fn session_env_reaches_compose_env_file_and_exports() {
+    let data = TempDir::new().unwrap();
+    let repo = make_repo(
+        "env_file = \".env.belljar\"\n[env]\nDB_NAME = \"app_{slug}\"\nDB_URL = \"postgres://localhost:{BELLJAR_PORT_DB}/app_{slug}\"\n",
+    );
+    let (shim, log) = make_docker_shim();
+
+    belljar(data.path(), shim.path())
+        .args(["start", "Feat-1", "-p"])
+        .arg(repo.path())
+        .assert()
+        .success();
+
+    // Compose interpolation sees the session env
+    let calls = fs::read_to_string(&log).unwrap();
+    let up = calls.lines().find(|l| l.contains(" up ")).unwrap();
+    assert!(up.starts_with("[Feat-1 "), "{up}");
+    assert!(up.contains(" app_feat_1]"), "{up}");
+
+    let text = fs::read_to_string(repo.path().join(".env.belljar")).unwrap();
+    assert!(text.starts_with("# Generated by belljar"), "{text}");
+    assert!(text.contains("\nBELLJAR_SESSION=Feat-1\n"), "{text}");
+    assert!(text.contains("\nDB_NAME=app_feat_1\n"), "{text}");
+    assert!(
+        text.contains("\nDB_URL=postgres://localhost:43000/app_feat_1\n"),
+        "{text}"
+    );
+
+    belljar(data.path(), shim.path())
+        .args(["env", "Feat-1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("export BELLJAR_SESSION=Feat-1\n"))
+        .stdout(predicate::str::contains("export BELLJAR_PORT_DB=43000\n"))
+        .stdout(predicate::str::contains("export COMPOSE_PROJECT_NAME="))
+        .stdout(predicate::str::contains("export DB_NAME=app_feat_1\n"));
+
+    let out = belljar(data.path(), shim.path())
+        .args(["--json", "env", "Feat-1"])
+        .assert()
+        .success();
+    let doc: serde_json::Value = serde_json::from_slice(&out.get_output().stdout).unwrap();
+    assert_eq!(doc["label"], "Feat-1");
+    assert_eq!(doc["env"]["DB_NAME"], "app_feat_1");
+    assert_eq!(
+        doc["env"]["BELLJAR_WORKTREE"],
+        repo.path().display().to_string()
+    );
+}
+
+#[test]
+fn env_file_not_generated_by_belljar_is_not_overwritten() {
+    let data = TempDir::new().unwrap();
+    let repo = make_repo("env_file = \".env\"\n");
+    fs::write(repo.path().join(".env"), "SECRET=1\n").unwrap();
+    let (shim, _log) = make_docker_shim();
+
+    belljar(data.path(), shim.path())
+        .args(["start", "a", "-p"])
+        .arg(repo.path())
+        .assert()
+        .code(10)
+        .stderr(predicate::str::contains("refusing to overwrite"));
+    assert_eq!(
+        fs::read_to_string(repo.path().join(".env")).unwrap(),
+        "SECRET=1\n"
+    );
+}
+
+#[test]
+fn unknown_placeholder_is_a_config_error() {
+    let data = TempDir::new().unwrap();
+    let repo = make_repo("[env]\nDB_NAME = \"app_{nope}\"\n");
+    let (shim, _log) = make_docker_shim();
+
+    belljar(data.path(), shim.path())
+        .args(["start", "a", "-p"])
+        .arg(repo.path())
+        .assert()
+        .code(10)
+        .stderr(predicate::str::contains(
+            "env.DB_NAME: unknown placeholder {nope}",
+        ));
+}
+
+#[test]
+fn env_values_with_spaces_are_quoted() {
+    let data = TempDir::new().unwrap();
+    let repo = make_repo("[env]\nGREETING = \"it's {label}\"\n");
+    let (shim, _log) = make_docker_shim();
+
+    belljar(data.path(), shim.path())
+        .args(["start", "a", "-p"])
+        .arg(repo.path())
+        .assert()
+        .success();
+    belljar(data.path(), shim.path())
+        .args(["env", "a"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("export GREETING='it'\\''s a'\n"));
+}
+
+fn git(repo: &Path, args: &[&str]) -> bool {
+    Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .status()
+        .unwrap()
+        .success()
+}
+
+#[test]
+fn generated_env_file_does_not_make_the_worktree_dirty() {
+    let data = TempDir::new().unwrap();
+    let repo = make_repo("env_file = \".env.session\"\n");
+    assert!(git(repo.path(), &["init"]));
+    assert!(git(
+        repo.path(),
+        &["config", "user.email", "ci@example.com"]
+    ));
+    assert!(git(repo.path(), &["config", "user.name", "CI"]));
+    fs::write(repo.path().join(".gitignore"), ".belljar/worktrees/\n").unwrap();
+    assert!(git(repo.path(), &["add", "."]));
+    assert!(git(repo.path(), &["commit", "-m", "init"]));
+    let (shim, _log) = make_docker_shim();
+
+    belljar(data.path(), shim.path())
+        .args(["start", "a", "-p"])
+        .arg(repo.path())
+        .assert()
+        .success();
+    let wt = repo.path().join(".belljar/worktrees/a");
+    assert!(wt.join(".env.session").exists());
+
+    belljar(data.path(), shim.path())
+        .args(["rm", "a"])
+        .assert()
+        .success();
+    assert!(!wt.exists());
+}
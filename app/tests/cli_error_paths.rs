@@ -38,7 +38,7 @@ fn open_without_tmux_prints_fallback() {
 }
 
 #[test]
-fn send_all_with_tmux_missing_reports_errors_but_succeeds() {
+fn send_all_with_tmux_missing_reports_errors_and_fails() {
     let data = TempDir::new().unwrap();
     let repo = TempDir::new().unwrap();
     // create two sessions
@@ -57,7 +57,9 @@ fn send_all_with_tmux_missing_reports_errors_but_succeeds() {
         .env("BELLJAR_DATA_DIR", data.path())
         .env("PATH", "/nonexistent")
         .assert()
-        .success();
+        .failure()
+        .code(8)
+        .stderr(predicate::str::contains("send failed for 2 session(s)"));
 }
 
 #[test]
@@ -0,0 +1,95 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+fn prepend_path(dir: &Path) -> String {
+    let old = std::env::var("PATH").unwrap_or_default();
+    format!("{}:{}", dir.display(), old)
+}
+
+/// docker shim that logs calls and exits 42 when the exec'd command is `fail`
+fn make_docker_shim() -> (TempDir, PathBuf) {
+    let dir = TempDir::new().unwrap();
+    let log = dir.path().join("docker.log");
+    let shim = dir.path().join("docker");
+    let script = format!(
+        "#!/usr/bin/env bash\necho \"$@\" >> {}\nif [ \"${{@: -1}}\" = fail ]; then echo boom; exit 42; fi\nexit 0\n",
+        log.display()
+    );
+    fs::write(&shim, script).unwrap();
+    let mut perm = fs::metadata(&shim).unwrap().permissions();
+    perm.set_mode(0o755);
+    fs::set_permissions(&shim, perm).unwrap();
+    (dir, log)
+}
+
+#[test]
+fn exec_runs_in_service_and_passes_exit_code() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    fs::write(repo.path().join("docker-compose.yml"), "services: {}\n").unwrap();
+    let (shim, log) = make_docker_shim();
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["start", "ex", "-p"])
+        .arg(repo.path())
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim.path()))
+        .assert()
+        .success();
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["exec", "ex", "db", "--", "psql", "-c", "select 1"])
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim.path()))
+        .assert()
+        .success();
+    let calls = fs::read_to_string(&log).unwrap();
+    let exec = calls.lines().last().unwrap();
+    assert!(exec.starts_with("compose -p belljar_"), "{exec}");
+    assert!(exec.contains("docker-compose.yml"), "{exec}");
+    // Not a terminal under test, so no TTY is requested
+    assert!(exec.ends_with("exec -T db psql -c select 1"), "{exec}");
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["exec", "ex", "db", "--", "fail"])
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim.path()))
+        .assert()
+        .code(42)
+        .stdout(predicate::str::contains("boom"));
+}
+
+#[test]
+fn exec_errors() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["exec", "ghost", "db", "--", "true"])
+        .env("BELLJAR_DATA_DIR", data.path())
+        .assert()
+        .code(3);
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["start", "bare", "-p"])
+        .arg(repo.path())
+        .env("BELLJAR_DATA_DIR", data.path())
+        .assert()
+        .success();
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["exec", "bare", "db", "--", "true"])
+        .env("BELLJAR_DATA_DIR", data.path())
+        .assert()
+        .code(7)
+        .stderr(predicate::str::contains("no compose files"));
+}
@@ -0,0 +1,114 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn init_git_repo() -> tempfile::TempDir {
+    let td = TempDir::new().unwrap();
+    let repo = td.path();
+    for args in [
+        vec!["init"],
+        vec!["config", "user.email", "ci@example.com"],
+        vec!["config", "user.name", "CI"],
+    ] {
+        assert!(Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(&args)
+            .status()
+            .unwrap()
+            .success());
+    }
+    fs::write(repo.join("README.md"), "init\n").unwrap();
+    for args in [vec!["add", "."], vec!["commit", "-m", "init"]] {
+        assert!(Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(&args)
+            .status()
+            .unwrap()
+            .success());
+    }
+    td
+}
+
+fn belljar(data: &TempDir) -> Command {
+    let mut cmd = Command::cargo_bin("belljar").unwrap();
+    cmd.env("BELLJAR_DATA_DIR", data.path());
+    cmd
+}
+
+#[test]
+fn label_conflict_exits_4() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    belljar(&data)
+        .args(["start", "dup", "-p"])
+        .arg(repo.path())
+        .assert()
+        .success();
+    belljar(&data)
+        .args(["start", "dup", "-p"])
+        .arg(repo.path())
+        .assert()
+        .failure()
+        .code(4)
+        .stderr(predicate::str::contains(
+            "session label already in use: dup",
+        ));
+}
+
+#[test]
+fn git_failure_exits_6() {
+    let data = TempDir::new().unwrap();
+    let repo = init_git_repo();
+    // The base does not exist, so git worktree add fails
+    belljar(&data)
+        .args(["new", "feat", "--from", "no-such-base", "-p"])
+        .arg(repo.path())
+        .assert()
+        .failure()
+        .code(6)
+        .stderr(predicate::str::contains("git error"));
+}
+
+#[test]
+fn unknown_workspace_and_bad_path() {
+    let data = TempDir::new().unwrap();
+    belljar(&data)
+        .args(["workspace", "rm", "ghost"])
+        .assert()
+        .failure()
+        .code(3)
+        .stderr(predicate::str::contains("no such workspace: ghost"));
+    belljar(&data)
+        .args(["start", "x", "-p", "/definitely/not/here"])
+        .assert()
+        .failure()
+        .code(2);
+}
+
+#[test]
+fn json_errors_carry_kind_and_code() {
+    let data = TempDir::new().unwrap();
+    let out = belljar(&data)
+        .args(["--json", "open", "ghost"])
+        .assert()
+        .failure()
+        .code(2);
+    let doc: serde_json::Value =
+        serde_json::from_slice(&out.get_output().stdout).expect("json error document");
+    assert_eq!(doc["error"]["kind"], "usage");
+
+    let out = belljar(&data)
+        .args(["--json", "rm", "ghost"])
+        .assert()
+        .failure()
+        .code(3);
+    let doc: serde_json::Value =
+        serde_json::from_slice(&out.get_output().stdout).expect("json error document");
+    assert_eq!(doc["error"]["kind"], "not_found");
+    assert_eq!(doc["error"]["code"], 3);
+    assert_eq!(doc["error"]["message"], "no such session: ghost");
+}
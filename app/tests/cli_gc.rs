@@ -0,0 +1,343 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn init_git_repo() -> tempfile::TempDir {
+    let td = TempDir::new().unwrap();
+    let repo = td.path();
+    assert!(Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .arg("init")
+        .status()
+        .unwrap()
+        .success());
+    assert!(Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["config", "user.email", "ci@example.com"])
+        .status()
+        .unwrap()
+        .success());
+    assert!(Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["config", "user.name", "CI"])
+        .status()
+        .unwrap()
+        .success());
+    fs::write(repo.join("README.md"), "init\n").unwrap();
+    assert!(Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["add", "."])
+        .status()
+        .unwrap()
+        .success());
+    assert!(Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["commit", "-m", "init"])
+        .status()
+        .unwrap()
+        .success());
+    td
+}
+
+fn prepend_path(dir: &Path) -> String {
+    let old = std::env::var("PATH").unwrap_or_default();
+    format!("{}:{}", dir.display(), old)
+}
+
+/// docker shim whose `compose ls` reports a single project nobody owns
+fn make_docker_shim() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    let shim = dir.path().join("docker");
+    let script = "#!/usr/bin/env bash\nif [ \"$2\" = ls ]; then echo '[{\"Name\":\"belljar_deadbeef\",\"Status\":\"running(1)\"},{\"Name\":\"other\",\"Status\":\"running(1)\"}]'; fi\nexit 0\n";
+    fs::write(&shim, script).unwrap();
+    let mut perm = fs::metadata(&shim).unwrap().permissions();
+    perm.set_mode(0o755);
+    fs::set_permissions(&shim, perm).unwrap();
+    dir
+}
+
+#[test]
+fn gc_reports_and_prunes_deleted_worktree() {
+    let data = TempDir::new().unwrap();
+    let repo = init_git_repo();
+    let shim_dir = make_docker_shim();
+
+    for s in ["keep", "gone"] {
+        Command::cargo_bin("belljar")
+            .unwrap()
+            .args(["start", s, "--path"])
+            .arg(repo.path())
+            .env("BELLJAR_DATA_DIR", data.path())
+            .assert()
+            .success();
+    }
+    fs::remove_dir_all(repo.path().join(".belljar/worktrees/gone")).unwrap();
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .arg("gc")
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim_dir.path()))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("session gone: worktree missing"))
+        .stdout(predicate::str::contains("session keep: worktree").not())
+        .stdout(predicate::str::contains(
+            "orphan compose project: belljar_deadbeef",
+        ))
+        .stdout(predicate::str::contains("orphan compose project: other").not());
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["gc", "--fix"])
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim_dir.path()))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("pruned session gone"));
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .arg("ls")
+        .env("BELLJAR_DATA_DIR", data.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("keep\t"))
+        .stdout(predicate::str::contains("gone\t").not());
+}
+
+#[test]
+fn gc_prunes_session_whose_repo_moved() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    let shim_dir = make_docker_shim();
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["start", "moved", "--path"])
+        .arg(repo.path())
+        .env("BELLJAR_DATA_DIR", data.path())
+        .assert()
+        .success();
+    drop(repo);
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["gc", "--fix"])
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim_dir.path()))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "session moved: repository missing",
+        ))
+        .stdout(predicate::str::contains("pruned session moved"));
+}
+
+#[test]
+fn gc_skips_worktrees_git_cannot_list() {
+    let data = TempDir::new().unwrap();
+    let repo = init_git_repo();
+    let shim_dir = make_docker_shim();
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["start", "kept", "--path"])
+        .arg(repo.path())
+        .env("BELLJAR_DATA_DIR", data.path())
+        .assert()
+        .success();
+    // git that fails to list worktrees but works otherwise
+    let real_git =
+        String::from_utf8(Command::new("which").arg("git").output().unwrap().stdout).unwrap();
+    let git_shim = shim_dir.path().join("git");
+    fs::write(
+        &git_shim,
+        format!(
+            "#!/usr/bin/env bash\ncase \" $* \" in *' worktree list '*) exit 1 ;; esac\nexec {} \"$@\"\n",
+            real_git.trim()
+        ),
+    )
+    .unwrap();
+    let mut perm = fs::metadata(&git_shim).unwrap().permissions();
+    perm.set_mode(0o755);
+    fs::set_permissions(&git_shim, perm).unwrap();
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["gc", "--fix"])
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim_dir.path()))
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("worktree checks skipped"))
+        .stdout(predicate::str::contains("pruned session kept").not());
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .arg("ls")
+        .env("BELLJAR_DATA_DIR", data.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("kept\t"));
+}
+
+#[test]
+fn gc_fix_does_not_restart_stopped_sessions() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    fs::write(repo.path().join("docker-compose.yml"), "services: {}\n").unwrap();
+    let shim_dir = TempDir::new().unwrap();
+    let log = shim_dir.path().join("docker.log");
+    let shim = shim_dir.path().join("docker");
+    fs::write(
+        &shim,
+        format!(
+            "#!/usr/bin/env bash\necho \"$@\" >> {}\ncase \" $* \" in\n  *' ls --all '*) echo '[]' ;;\n  *' config '*) echo '{{\"services\":{{}}}}' ;;\nesac\nexit 0\n",
+            log.display()
+        ),
+    )
+    .unwrap();
+    let mut perm = fs::metadata(&shim).unwrap().permissions();
+    perm.set_mode(0o755);
+    fs::set_permissions(&shim, perm).unwrap();
+    let belljar = || {
+        let mut cmd = Command::cargo_bin("belljar").unwrap();
+        cmd.env("BELLJAR_DATA_DIR", data.path())
+            .env("BELLJAR_RUNTIME", "docker")
+            .env("PATH", prepend_path(shim_dir.path()));
+        cmd
+    };
+    belljar()
+        .args(["start", "idle", "--path"])
+        .arg(repo.path())
+        .assert()
+        .success();
+    belljar().args(["stop", "idle"]).assert().success();
+    fs::write(&log, "").unwrap();
+
+    belljar()
+        .args(["gc", "--fix"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "session idle: compose project not found",
+        ));
+    let calls = fs::read_to_string(&log).unwrap();
+    assert!(!calls.contains(" up "), "{calls}");
+}
+
+#[test]
+fn projects_are_listed_with_the_runtime_of_the_session_repo() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    fs::write(repo.path().join("docker-compose.yml"), "services: {}\n").unwrap();
+    fs::create_dir_all(repo.path().join(".belljar")).unwrap();
+    fs::write(
+        repo.path().join(".belljar/config.toml"),
+        "runtime = \"podman\"\n",
+    )
+    .unwrap();
+    let shim_dir = TempDir::new().unwrap();
+    let log = shim_dir.path().join("podman.log");
+    let up = shim_dir.path().join("up");
+    // docker knows no projects; podman knows the one it brought up
+    let shims = [
+        ("docker", "[ \"$2\" = ls ] && echo '[]'\nexit 0\n".to_string()),
+        (
+            "podman",
+            format!(
+                "echo \"$@\" >> {log}\ncase \" $* \" in\n  *' ls --all '*) echo \"[{{\\\"Name\\\":\\\"$(cat {up})\\\",\\\"Status\\\":\\\"running(1)\\\"}}]\" ;;\n  *' up '*) echo \"$3\" > {up} ;;\n  *' config '*) echo '{{\"services\":{{}}}}' ;;\nesac\nexit 0\n",
+                log = log.display(),
+                up = up.display()
+            ),
+        ),
+    ];
+    for (name, body) in shims {
+        let shim = shim_dir.path().join(name);
+        fs::write(&shim, format!("#!/usr/bin/env bash\n{body}")).unwrap();
+        let mut perm = fs::metadata(&shim).unwrap().permissions();
+        perm.set_mode(0o755);
+        fs::set_permissions(&shim, perm).unwrap();
+    }
+    let belljar = || {
+        let mut cmd = Command::cargo_bin("belljar").unwrap();
+        cmd.env("BELLJAR_DATA_DIR", data.path())
+            .env_remove("BELLJAR_RUNTIME")
+            .env("PATH", prepend_path(shim_dir.path()));
+        cmd
+    };
+    belljar()
+        .args(["start", "pod", "--path"])
+        .arg(repo.path())
+        .assert()
+        .success();
+    fs::write(&log, "").unwrap();
+
+    let out = belljar().args(["--json", "ls"]).assert().success();
+    let doc: serde_json::Value = serde_json::from_slice(&out.get_output().stdout).unwrap();
+    assert_eq!(doc["sessions"][0]["status"]["compose"], "running(1)");
+    belljar()
+        .args(["gc", "--fix"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("compose project not found").not());
+    let calls = fs::read_to_string(&log).unwrap();
+    assert!(calls.contains("compose ls --all"), "{calls}");
+    assert!(!calls.contains(" up "), "{calls}");
+}
+
+#[test]
+fn gc_fix_takes_down_the_project_of_a_pruned_session() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    fs::write(repo.path().join("docker-compose.yml"), "services: {}\n").unwrap();
+    let shim_dir = TempDir::new().unwrap();
+    let log = shim_dir.path().join("docker.log");
+    let shim = shim_dir.path().join("docker");
+    fs::write(
+        &shim,
+        format!(
+            "#!/usr/bin/env bash\necho \"[$DOCKER_HOST] $@\" >> {}\ncase \" $* \" in\n  *' ls --all '*) echo '[]' ;;\n  *' config '*) echo '{{\"services\":{{}}}}' ;;\nesac\nexit 0\n",
+            log.display()
+        ),
+    )
+    .unwrap();
+    let mut perm = fs::metadata(&shim).unwrap().permissions();
+    perm.set_mode(0o755);
+    fs::set_permissions(&shim, perm).unwrap();
+    let belljar = || {
+        let mut cmd = Command::cargo_bin("belljar").unwrap();
+        cmd.env("BELLJAR_DATA_DIR", data.path())
+            .env("BELLJAR_RUNTIME", "docker")
+            .env_remove("DOCKER_HOST")
+            .env("PATH", prepend_path(shim_dir.path()));
+        cmd
+    };
+    belljar()
+        .args(["start", "far", "--docker-host", "ssh://box", "--path"])
+        .arg(repo.path())
+        .assert()
+        .success();
+    drop(repo);
+    fs::write(&log, "").unwrap();
+
+    belljar()
+        .args(["gc", "--fix"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("pruned session far"));
+    let calls = fs::read_to_string(&log).unwrap();
+    let down = calls.lines().find(|l| l.contains(" down ")).unwrap();
+    assert!(
+        down.starts_with("[ssh://box] compose -p belljar_"),
+        "{calls}"
+    );
+}
@@ -0,0 +1,182 @@
+use assert_cmd::prelude::*;
+use serde_json::Value;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn belljar(data: &TempDir) -> Command {
+    let mut cmd = Command::cargo_bin("belljar").unwrap();
+    cmd.env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", "/nonexistent")
+        .arg("--json");
+    cmd
+}
+
+fn parse(out: &[u8]) -> Value {
+    let text = String::from_utf8_lossy(out);
+    let doc: Value = serde_json::from_str(text.trim()).expect("stdout is one JSON document");
+    assert_eq!(doc["format_version"], 1);
+    doc
+}
+
+#[test]
+fn ls_and_start_emit_versioned_json() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+
+    let out = belljar(&data).arg("ls").assert().success();
+    let doc = parse(&out.get_output().stdout);
+    assert_eq!(doc["sessions"], Value::Array(vec![]));
+
+    let out = belljar(&data)
+        .args(["start", "js", "-p"])
+        .arg(repo.path())
+        .assert()
+        .success();
+    let doc = parse(&out.get_output().stdout);
+    assert_eq!(doc["created"], true);
+    assert_eq!(doc["replaced"], false);
+    assert_eq!(doc["compose_up"], false);
+    assert_eq!(doc["session"]["label"], "js");
+    assert!(doc["session"]["compose_project"]
+        .as_str()
+        .unwrap()
+        .starts_with("belljar_"));
+
+    let out = belljar(&data).arg("ls").assert().success();
+    let doc = parse(&out.get_output().stdout);
+    let s = &doc["sessions"][0];
+    for field in [
+        "id",
+        "label",
+        "repo_path",
+        "branch",
+        "worktree_path",
+        "compose_project",
+        "services",
+        "tmux_session",
+        "created_at",
+    ] {
+        assert!(s.get(field).is_some(), "missing {field}");
+    }
+    assert_eq!(s["status"]["worktree"], true);
+    // Neither tmux nor docker is on PATH, so live checks are unknown
+    assert_eq!(s["status"]["tmux"], Value::Null);
+    assert_eq!(s["status"]["compose"], Value::Null);
+
+    let out = belljar(&data)
+        .args(["rm", "js", "--force"])
+        .assert()
+        .success();
+    let doc = parse(&out.get_output().stdout);
+    assert_eq!(doc["removed"][0]["label"], "js");
+}
+
+#[test]
+fn errors_are_reported_as_json() {
+    let data = TempDir::new().unwrap();
+    let out = belljar(&data)
+        .args(["start", "x", "-p", "/definitely/not/here"])
+        .assert()
+        .failure();
+    let doc = parse(&out.get_output().stdout);
+    assert!(doc["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("path does not exist"));
+
+    let out = belljar(&data).args(["open", "x"]).assert().failure();
+    let doc = parse(&out.get_output().stdout);
+    assert!(doc["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("interactive command: open"));
+}
+
+#[test]
+fn workspace_ls_json() {
+    let data = TempDir::new().unwrap();
+    let root = TempDir::new().unwrap();
+    belljar(&data)
+        .args(["workspace", "start", "wj", "-p"])
+        .arg(root.path())
+        .assert()
+        .success();
+    let out = belljar(&data).args(["workspace", "ls"]).assert().success();
+    let doc = parse(&out.get_output().stdout);
+    assert_eq!(doc["workspaces"][0]["label"], "wj");
+    assert_eq!(doc["workspaces"][0]["tmux_session"], "ws-wj");
+}
+
+fn git(repo: &Path, args: &[&str]) -> bool {
+    Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .status()
+        .unwrap()
+        .success()
+}
+
+/// docker and tmux shims that chatter on stdout like the real tools
+fn make_noisy_shims() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    for tool in ["docker", "tmux"] {
+        let shim = dir.path().join(tool);
+        fs::write(
+            &shim,
+            "#!/usr/bin/env bash\ncase \" $* \" in\n  *' config '*) echo '{\"services\":{}}' ;;\n  *' ls --all '*) echo '[]' ;;\n  *) echo \"noise from $0 $1\" ;;\nesac\nexit 0\n",
+        )
+        .unwrap();
+        let mut perm = fs::metadata(&shim).unwrap().permissions();
+        perm.set_mode(0o755);
+        fs::set_permissions(&shim, perm).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn child_output_stays_off_stdout() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    assert!(git(repo.path(), &["init"]));
+    assert!(git(
+        repo.path(),
+        &["config", "user.email", "ci@example.com"]
+    ));
+    assert!(git(repo.path(), &["config", "user.name", "CI"]));
+    fs::write(repo.path().join("docker-compose.yml"), "services: {}\n").unwrap();
+    fs::write(repo.path().join(".gitignore"), ".belljar/\n").unwrap();
+    assert!(git(repo.path(), &["add", "."]));
+    assert!(git(repo.path(), &["commit", "-m", "init"]));
+    let shims = make_noisy_shims();
+    let path = format!(
+        "{}:{}",
+        shims.path().display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+
+    // git checks out the worktree and compose runs `up`, both talkative
+    let out = belljar(&data)
+        .env("PATH", &path)
+        .env("BELLJAR_RUNTIME", "docker")
+        .args(["start", "js", "-p"])
+        .arg(repo.path())
+        .assert()
+        .success();
+    let doc = parse(&out.get_output().stdout);
+    assert_eq!(doc["compose_up"], true);
+    let stderr = String::from_utf8_lossy(&out.get_output().stderr);
+    assert!(stderr.contains("noise from"), "{stderr}");
+
+    let out = belljar(&data)
+        .env("PATH", &path)
+        .env("BELLJAR_RUNTIME", "docker")
+        .args(["rm", "js", "--force"])
+        .assert()
+        .success();
+    let doc = parse(&out.get_output().stdout);
+    assert_eq!(doc["removed"][0]["label"], "js");
+}
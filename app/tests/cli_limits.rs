@@ -0,0 +1,134 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+fn prepend_path(dir: &Path) -> String {
+    let old = std::env::var("PATH").unwrap_or_default();
+    format!("{}:{}", dir.display(), old)
+}
+
+/// docker shim for a db+web stack that logs every call
+fn make_docker_shim() -> (TempDir, PathBuf) {
+    let dir = TempDir::new().unwrap();
+    let log = dir.path().join("docker.log");
+    let shim = dir.path().join("docker");
+    let script = format!(
+        "#!/usr/bin/env bash\necho \"$@\" >> {}\ncase \" $* \" in\n  *' --services '*) printf 'db\\nweb\\n' ;;\n  *' config '*) echo '{{\"services\":{{}}}}' ;;\nesac\nexit 0\n",
+        log.display()
+    );
+    fs::write(&shim, script).unwrap();
+    let mut perm = fs::metadata(&shim).unwrap().permissions();
+    perm.set_mode(0o755);
+    fs::set_permissions(&shim, perm).unwrap();
+    (dir, log)
+}
+
+fn belljar(data: &Path, shim: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("belljar").unwrap();
+    cmd.env("BELLJAR_DATA_DIR", data)
+        .env("PATH", prepend_path(shim));
+    cmd
+}
+
+fn make_repo() -> TempDir {
+    let repo = TempDir::new().unwrap();
+    fs::write(repo.path().join("docker-compose.yml"), "services: {}\n").unwrap();
+    repo
+}
+
+#[test]
+fn limits_are_layered_as_an_override_and_recorded() {
+    let data = TempDir::new().unwrap();
+    let repo = make_repo();
+    fs::create_dir_all(repo.path().join(".belljar")).unwrap();
+    fs::write(
+        repo.path().join(".belljar/config.toml"),
+        "cpus = \"2\"\nmemory = \"1g,db=4g\"\n",
+    )
+    .unwrap();
+    let (shim, log) = make_docker_shim();
+
+    belljar(data.path(), shim.path())
+        .args([
+            "start", "capped", "--cpus", "db=0.5", "--memory", "768m", "-p",
+        ])
+        .arg(repo.path())
+        .assert()
+        .success();
+
+    let out = belljar(data.path(), shim.path())
+        .args(["--json", "ls"])
+        .assert()
+        .success();
+    let doc: serde_json::Value = serde_json::from_slice(&out.get_output().stdout).unwrap();
+    let session = &doc["sessions"][0];
+    assert_eq!(
+        session["limits"],
+        serde_json::json!([
+            { "service": null, "cpus": "2", "memory": "768m" },
+            { "service": "db", "cpus": "0.5", "memory": "4g" }
+        ])
+    );
+
+    // The override follows the repo's compose file on every compose call
+    let id = session["id"].as_str().unwrap();
+    let file = data.path().join("limits").join(format!("{id}.yml"));
+    let yaml = fs::read_to_string(&file).unwrap();
+    assert!(
+        yaml.contains("  \"db\":\n    cpus: 0.5\n    mem_limit: \"4g\"\n"),
+        "{yaml}"
+    );
+    assert!(
+        yaml.contains("  \"web\":\n    cpus: 2\n    mem_limit: \"768m\"\n"),
+        "{yaml}"
+    );
+    let calls = fs::read_to_string(&log).unwrap();
+    let up = calls.lines().find(|l| l.contains(" up ")).unwrap();
+    let compose_file = repo.path().join("docker-compose.yml");
+    let order = format!("-f {} -f {}", compose_file.display(), file.display());
+    assert!(up.contains(&order), "{up}");
+
+    belljar(data.path(), shim.path())
+        .args(["status", "capped"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "limits:    each service cpus=2 memory=768m; db cpus=0.5 memory=4g",
+        ));
+
+    belljar(data.path(), shim.path())
+        .args(["rm", "capped"])
+        .assert()
+        .success();
+    assert!(!file.exists());
+}
+
+#[test]
+fn invalid_limits_are_usage_errors() {
+    let data = TempDir::new().unwrap();
+    let repo = make_repo();
+    let (shim, _log) = make_docker_shim();
+
+    belljar(data.path(), shim.path())
+        .args(["start", "a", "--cpus", "0", "-p"])
+        .arg(repo.path())
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("cpus must be a positive number"));
+    belljar(data.path(), shim.path())
+        .args(["start", "a", "--memory", "lots", "-p"])
+        .arg(repo.path())
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("memory must be a size"));
+    belljar(data.path(), shim.path())
+        .args(["start", "a", "--cpus", "cache=1", "-p"])
+        .arg(repo.path())
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("cache"));
+}
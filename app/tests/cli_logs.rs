@@ -0,0 +1,106 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+fn prepend_path(dir: &Path) -> String {
+    let old = std::env::var("PATH").unwrap_or_default();
+    format!("{}:{}", dir.display(), old)
+}
+
+/// docker shim defining db and web; `logs` prints a line per requested service
+fn make_docker_shim() -> (TempDir, PathBuf) {
+    let dir = TempDir::new().unwrap();
+    let log = dir.path().join("docker.log");
+    let shim = dir.path().join("docker");
+    let script = format!(
+        r#"#!/usr/bin/env bash
+echo "$@" >> {}
+for a in "$@"; do
+  if [ "$a" = config ]; then printf 'db\nweb\n'; exit 0; fi
+  if [ "$a" = logs ]; then echo "${{@: -1}} says hi"; exit 0; fi
+done
+exit 0
+"#,
+        log.display()
+    );
+    fs::write(&shim, script).unwrap();
+    let mut perm = fs::metadata(&shim).unwrap().permissions();
+    perm.set_mode(0o755);
+    fs::set_permissions(&shim, perm).unwrap();
+    (dir, log)
+}
+
+fn belljar(data: &Path, shim: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("belljar").unwrap();
+    cmd.env("BELLJAR_DATA_DIR", data)
+        .env("PATH", prepend_path(shim));
+    cmd
+}
+
+#[test]
+fn logs_pass_filters_and_are_saved_on_teardown() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    fs::write(repo.path().join("docker-compose.yml"), "services: {}\n").unwrap();
+    let (shim, log) = make_docker_shim();
+
+    belljar(data.path(), shim.path())
+        .args(["start", "lg", "-p"])
+        .arg(repo.path())
+        .assert()
+        .success();
+
+    belljar(data.path(), shim.path())
+        .args([
+            "logs", "lg", "db", "--follow", "--since", "10m", "--tail", "5",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("db says hi"));
+    let calls = fs::read_to_string(&log).unwrap();
+    assert!(
+        calls.contains("logs --follow --since 10m --tail 5 db"),
+        "{calls}"
+    );
+
+    belljar(data.path(), shim.path())
+        .args(["rm", "lg"])
+        .assert()
+        .success();
+    let calls = fs::read_to_string(&log).unwrap();
+    let saved = calls
+        .find("logs --no-color --timestamps web")
+        .expect(&calls);
+    assert!(saved < calls.find("down -v").unwrap(), "{calls}");
+
+    belljar(data.path(), shim.path())
+        .args(["logs", "lg", "--saved"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("==> db <==\ndb says hi"))
+        .stdout(predicate::str::contains("==> web <==\nweb says hi"));
+    belljar(data.path(), shim.path())
+        .args(["logs", "lg", "web", "--saved"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("db says hi").not());
+}
+
+#[test]
+fn logs_without_saved_logs_or_session() {
+    let data = TempDir::new().unwrap();
+    let (shim, _log) = make_docker_shim();
+    belljar(data.path(), shim.path())
+        .args(["logs", "nope"])
+        .assert()
+        .code(3);
+    belljar(data.path(), shim.path())
+        .args(["logs", "nope", "--saved"])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("no saved logs for nope"));
+}
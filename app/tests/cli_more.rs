@@ -79,7 +79,7 @@ fn init_git_repo() -> tempfile::TempDir {
 }
 
 #[test]
-fn send_error_path_reports_but_exits_success() {
+fn send_error_path_reports_and_exits_nonzero() {
     let data = TempDir::new().unwrap();
     let repo = init_git_repo();
     // create session
@@ -99,7 +99,8 @@ fn send_error_path_reports_but_exits_success() {
         .env("BELLJAR_DATA_DIR", data.path())
         .env("PATH", prepend_path(shim_dir.path()))
         .assert()
-        .success()
+        .failure()
+        .code(8)
         .stderr(predicate::str::contains("send failed"));
 }
 
@@ -123,7 +124,8 @@ fn open_attach_failure_is_reported() {
         .env("BELLJAR_DATA_DIR", data.path())
         .env("PATH", prepend_path(shim_dir.path()))
         .assert()
-        .success()
+        .failure()
+        .code(8)
         .stderr(predicate::str::contains("failed to attach"));
 }
 
@@ -148,6 +150,7 @@ fn control_center_attach_failure_reported() {
         .env("BELLJAR_DATA_DIR", data.path())
         .env("PATH", prepend_path(shim_dir.path()))
         .assert()
-        .success()
+        .failure()
+        .code(8)
         .stderr(predicate::str::contains("failed to attach control center"));
 }
@@ -0,0 +1,154 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+fn prepend_path(dir: &Path) -> String {
+    let old = std::env::var("PATH").unwrap_or_default();
+    format!("{}:{}", dir.display(), old)
+}
+
+const CONFIG: &str = r#"{"services":{"db":{"ports":[{"mode":"ingress","target":5432,"published":"5432","protocol":"tcp"}]},"web":{"ports":[{"target":80,"published":"8080","host_ip":"127.0.0.1","protocol":"tcp"},{"target":9000,"protocol":"tcp"}]}}}"#;
+
+/// docker shim that renders a fixed config and logs calls with BELLJAR_PORT_DB
+fn make_docker_shim() -> (TempDir, PathBuf) {
+    let dir = TempDir::new().unwrap();
+    let log = dir.path().join("docker.log");
+    let shim = dir.path().join("docker");
+    let script = format!(
+        "#!/usr/bin/env bash\necho \"[$BELLJAR_PORT_DB] $@\" >> {}\nfor a in \"$@\"; do if [ \"$a\" = config ]; then echo '{}'; fi; done\nexit 0\n",
+        log.display(),
+        CONFIG
+    );
+    fs::write(&shim, script).unwrap();
+    let mut perm = fs::metadata(&shim).unwrap().permissions();
+    perm.set_mode(0o755);
+    fs::set_permissions(&shim, perm).unwrap();
+    (dir, log)
+}
+
+fn belljar(data: &Path, shim: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("belljar").unwrap();
+    cmd.env("BELLJAR_DATA_DIR", data)
+        .env("BELLJAR_PORTS_BASE", "41000")
+        .env("BELLJAR_PORTS_BLOCK", "10")
+        .env("PATH", prepend_path(shim));
+    cmd
+}
+
+#[test]
+fn parallel_sessions_get_distinct_port_blocks() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    fs::write(repo.path().join("docker-compose.yml"), "services: {}\n").unwrap();
+    let (shim, log) = make_docker_shim();
+
+    for label in ["a", "b"] {
+        belljar(data.path(), shim.path())
+            .args(["start", label, "-p"])
+            .arg(repo.path())
+            .assert()
+            .success();
+    }
+
+    let out = belljar(data.path(), shim.path())
+        .args(["--json", "ls"])
+        .assert()
+        .success();
+    let doc: serde_json::Value = serde_json::from_slice(&out.get_output().stdout).unwrap();
+    let a = &doc["sessions"][0]["ports"];
+    let b = &doc["sessions"][1]["ports"];
+    assert_eq!(a.as_array().unwrap().len(), 2);
+    assert_eq!(a[0]["service"], "db");
+    assert_eq!(a[0]["target"], 5432);
+    let (a_db, b_db) = (
+        a[0]["host"].as_u64().unwrap(),
+        b[0]["host"].as_u64().unwrap(),
+    );
+    assert!((41000..41010).contains(&a_db), "{a_db}");
+    assert!((41010..41020).contains(&b_db), "{b_db}");
+
+    // The override remaps published ports and keeps unpublished ones
+    let id = doc["sessions"][0]["id"].as_str().unwrap();
+    let override_file = data.path().join("ports").join(format!("{id}.yml"));
+    let yaml = fs::read_to_string(&override_file).unwrap();
+    assert!(yaml.contains(&format!("\"{a_db}:5432/tcp\"")), "{yaml}");
+    assert!(yaml.contains(":80/tcp\""), "{yaml}");
+    assert!(yaml.contains("\"127.0.0.1:"), "{yaml}");
+    assert!(yaml.contains("\"9000/tcp\""), "{yaml}");
+    assert!(yaml.contains("ports: !override"), "{yaml}");
+
+    let calls = fs::read_to_string(&log).unwrap();
+    assert!(
+        calls.contains(&format!("[{a_db}] compose -p"))
+            && calls.contains(&override_file.display().to_string()),
+        "{calls}"
+    );
+
+    belljar(data.path(), shim.path())
+        .args(["ports", "a", "--env"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "export BELLJAR_PORT_DB={a_db}"
+        )))
+        .stdout(predicate::str::contains(format!(
+            "export BELLJAR_PORT_DB_5432={a_db}"
+        )))
+        .stdout(predicate::str::contains("export BELLJAR_PORT_WEB_80="));
+
+    belljar(data.path(), shim.path())
+        .args(["rm", "a"])
+        .assert()
+        .success();
+    assert!(!override_file.exists());
+}
+
+#[test]
+fn ports_for_session_without_compose() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    let (shim, _log) = make_docker_shim();
+    belljar(data.path(), shim.path())
+        .args(["start", "plain", "-p"])
+        .arg(repo.path())
+        .assert()
+        .success();
+    belljar(data.path(), shim.path())
+        .args(["ports", "plain"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("no published ports"));
+}
+
+#[test]
+fn local_port_checks_skip_sessions_on_a_remote_engine() {
+    let repo = TempDir::new().unwrap();
+    fs::write(repo.path().join("docker-compose.yml"), "services: {}\n").unwrap();
+    let (shim, _log) = make_docker_shim();
+    // Hold the first port of the first block on this host
+    let held = std::net::TcpListener::bind(("0.0.0.0", 0)).unwrap();
+    let base = held.local_addr().unwrap().port().to_string();
+
+    let db_port = |remote: bool| {
+        let data = TempDir::new().unwrap();
+        let mut start = belljar(data.path(), shim.path());
+        start.env("BELLJAR_PORTS_BASE", &base).args(["start", "s"]);
+        if remote {
+            start.args(["--docker-host", "ssh://box"]);
+        }
+        start.arg("-p").arg(repo.path()).assert().success();
+        let out = belljar(data.path(), shim.path())
+            .env("BELLJAR_PORTS_BASE", &base)
+            .args(["--json", "ports", "s"])
+            .assert()
+            .success();
+        let doc: serde_json::Value = serde_json::from_slice(&out.get_output().stdout).unwrap();
+        doc["ports"][0]["host"].as_u64().unwrap().to_string()
+    };
+    assert_ne!(db_port(false), base);
+    assert_eq!(db_port(true), base);
+}
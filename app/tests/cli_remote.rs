@@ -0,0 +1,185 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+fn prepend_path(dir: &Path) -> String {
+    let old = std::env::var("PATH").unwrap_or_default();
+    format!("{}:{}", dir.display(), old)
+}
+
+/// docker shim that logs every call with the engine it was pointed at.
+/// Only the engine at ssh://box has a (running) web container.
+fn make_docker_shim() -> (TempDir, PathBuf) {
+    let dir = TempDir::new().unwrap();
+    let log = dir.path().join("docker.log");
+    let shim = dir.path().join("docker");
+    let script = format!(
+        r#"#!/usr/bin/env bash
+echo "[$DOCKER_CONTEXT|$DOCKER_HOST] $@" >> {}
+case " $* " in
+  *' ls --all '*) echo '[]' ;;
+  *' ps --all '*)
+    if [ "$DOCKER_HOST" = ssh://box ]; then
+      echo '{{"Service":"web","Name":"p-web-1","State":"running","Health":"","ExitCode":0}}'
+    fi ;;
+  *' config '*) echo '{{"services":{{}}}}' ;;
+esac
+exit 0
+"#,
+        log.display()
+    );
+    fs::write(&shim, script).unwrap();
+    let mut perm = fs::metadata(&shim).unwrap().permissions();
+    perm.set_mode(0o755);
+    fs::set_permissions(&shim, perm).unwrap();
+    (dir, log)
+}
+
+fn belljar(data: &Path, shim: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("belljar").unwrap();
+    cmd.env("BELLJAR_DATA_DIR", data)
+        .env("BELLJAR_RUNTIME", "docker")
+        .env_remove("DOCKER_CONTEXT")
+        .env_remove("DOCKER_HOST")
+        .env("PATH", prepend_path(shim));
+    cmd
+}
+
+fn make_repo(config: &str) -> TempDir {
+    let repo = TempDir::new().unwrap();
+    fs::write(repo.path().join("docker-compose.yml"), "services: {}\n").unwrap();
+    fs::create_dir_all(repo.path().join(".belljar")).unwrap();
+    fs::write(repo.path().join(".belljar/config.toml"), config).unwrap();
+    repo
+}
+
+#[test]
+fn docker_host_is_recorded_and_used_by_every_compose_call() {
+    let data = TempDir::new().unwrap();
+    let repo = make_repo("");
+    let (shim, log) = make_docker_shim();
+
+    belljar(data.path(), shim.path())
+        .args([
+            "start",
+            "far",
+            "--docker-host",
+            "ssh://box",
+            "--wait=10",
+            "-p",
+        ])
+        .arg(repo.path())
+        .assert()
+        .success();
+    let out = belljar(data.path(), shim.path())
+        .args(["--json", "status", "far"])
+        .assert()
+        .success();
+    let doc: serde_json::Value = serde_json::from_slice(&out.get_output().stdout).unwrap();
+    assert_eq!(doc["session"]["docker_host"], "ssh://box");
+    assert_eq!(doc["session"]["docker_context"], serde_json::Value::Null);
+
+    belljar(data.path(), shim.path())
+        .args(["status", "far"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("engine:    host ssh://box\n"))
+        .stdout(predicate::str::contains("web\trunning"));
+    belljar(data.path(), shim.path())
+        .args(["exec", "far", "db", "--", "true"])
+        .assert()
+        .success();
+    belljar(data.path(), shim.path())
+        .args(["logs", "far"])
+        .assert()
+        .success();
+    belljar(data.path(), shim.path())
+        .args(["rm", "far"])
+        .assert()
+        .success();
+
+    let calls = fs::read_to_string(&log).unwrap();
+    for needle in [" up ", " ps --all ", " exec ", " logs", " down "] {
+        let lines: Vec<&str> = calls.lines().filter(|l| l.contains(needle)).collect();
+        assert!(!lines.is_empty(), "{needle} not in {calls}");
+        for line in lines {
+            assert!(line.starts_with("[|ssh://box] "), "{line}");
+        }
+    }
+    // Status probed the remote engine for the session's project
+    assert!(
+        calls.contains("[|ssh://box] compose ls --all --format json"),
+        "{calls}"
+    );
+}
+
+#[test]
+fn docker_context_comes_from_repo_config_unless_overridden() {
+    let data = TempDir::new().unwrap();
+    let repo = make_repo("docker_context = \"beefy\"\n");
+    let (shim, log) = make_docker_shim();
+
+    belljar(data.path(), shim.path())
+        .env("DOCKER_HOST", "tcp://stray:2375")
+        .args(["start", "a", "-p"])
+        .arg(repo.path())
+        .assert()
+        .success();
+    let calls = fs::read_to_string(&log).unwrap();
+    let up = calls.lines().find(|l| l.contains(" up ")).unwrap();
+    // A DOCKER_HOST in belljar's environment would win over the context
+    assert!(up.starts_with("[beefy|] "), "{up}");
+    belljar(data.path(), shim.path())
+        .args(["env", "a"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("export DOCKER_CONTEXT=beefy\n"));
+
+    fs::write(&log, "").unwrap();
+    belljar(data.path(), shim.path())
+        .args(["start", "b", "--docker-context", "", "-p"])
+        .arg(repo.path())
+        .assert()
+        .success();
+    let calls = fs::read_to_string(&log).unwrap();
+    let up = calls.lines().find(|l| l.contains(" up ")).unwrap();
+    assert!(up.starts_with("[|] "), "{up}");
+    belljar(data.path(), shim.path())
+        .args(["status", "b"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("engine:    default engine\n"));
+}
+
+#[test]
+fn conflicting_engine_choices_are_rejected() {
+    let data = TempDir::new().unwrap();
+    let (shim, _log) = make_docker_shim();
+
+    let repo = make_repo("");
+    belljar(data.path(), shim.path())
+        .args([
+            "start",
+            "a",
+            "--docker-context",
+            "beefy",
+            "--docker-host",
+            "ssh://box",
+            "-p",
+        ])
+        .arg(repo.path())
+        .assert()
+        .code(2);
+
+    let repo = make_repo("docker_context = \"beefy\"\ndocker_host = \"ssh://box\"\n");
+    belljar(data.path(), shim.path())
+        .args(["start", "a", "-p"])
+        .arg(repo.path())
+        .assert()
+        .code(10)
+        .stderr(predicate::str::contains("are both set"));
+}
@@ -0,0 +1,129 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+fn prepend_path(dir: &Path) -> String {
+    let old = std::env::var("PATH").unwrap_or_default();
+    format!("{}:{}", dir.display(), old)
+}
+
+fn make_shims() -> (TempDir, PathBuf) {
+    let dir = TempDir::new().unwrap();
+    let log = dir.path().join("calls.log");
+    for bin in ["docker", "tmux"] {
+        let shim = dir.path().join(bin);
+        let script = format!(
+            "#!/usr/bin/env bash\necho \"{bin} $@\" >> {}\nexit 0\n",
+            log.display()
+        );
+        fs::write(&shim, script).unwrap();
+        let mut perm = fs::metadata(&shim).unwrap().permissions();
+        perm.set_mode(0o755);
+        fs::set_permissions(&shim, perm).unwrap();
+    }
+    (dir, log)
+}
+
+#[test]
+fn start_duplicate_label_fails() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    for expect_ok in [true, false] {
+        let mut cmd = Command::cargo_bin("belljar").unwrap();
+        cmd.args(["start", "foo", "--path"])
+            .arg(repo.path())
+            .env("BELLJAR_DATA_DIR", data.path());
+        if expect_ok {
+            cmd.assert().success();
+        } else {
+            cmd.assert().failure().stderr(predicate::str::contains(
+                "session label already in use: foo",
+            ));
+        }
+    }
+}
+
+#[test]
+fn start_replace_tears_down_old_session() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    fs::create_dir_all(repo.path().join(".belljar/compose")).unwrap();
+    fs::write(
+        repo.path().join(".belljar/compose/svc.yml"),
+        "services: {}\n",
+    )
+    .unwrap();
+    let (shim_dir, log) = make_shims();
+
+    for extra in [None, Some("--replace")] {
+        Command::cargo_bin("belljar")
+            .unwrap()
+            .args(["start", "foo", "--path"])
+            .arg(repo.path())
+            .args(extra)
+            .env("BELLJAR_DATA_DIR", data.path())
+            .env("PATH", prepend_path(shim_dir.path()))
+            .assert()
+            .success();
+    }
+
+    let logged = fs::read_to_string(&log).unwrap();
+    assert!(logged.contains("down -v"));
+    assert!(logged.contains("tmux kill-session -t foo"));
+
+    let out = Command::cargo_bin("belljar")
+        .unwrap()
+        .arg("ls")
+        .env("BELLJAR_DATA_DIR", data.path())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert_eq!(stdout.matches("foo\t").count(), 1);
+}
+
+fn git(repo: &Path, args: &[&str]) -> bool {
+    Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .status()
+        .unwrap()
+        .success()
+}
+
+#[test]
+fn replace_onto_another_branch_does_not_reuse_the_old_worktree() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    assert!(git(repo.path(), &["init"]));
+    assert!(git(
+        repo.path(),
+        &["config", "user.email", "ci@example.com"]
+    ));
+    assert!(git(repo.path(), &["config", "user.name", "CI"]));
+    fs::write(repo.path().join(".gitignore"), ".belljar/\n").unwrap();
+    assert!(git(repo.path(), &["add", "."]));
+    assert!(git(repo.path(), &["commit", "-m", "init"]));
+    let (shim_dir, _log) = make_shims();
+
+    for (branch, extra) in [("b1", None), ("b2", Some("--replace"))] {
+        let mut cmd = Command::cargo_bin("belljar").unwrap();
+        cmd.args(["start", "foo", "--branch", branch, "--path"])
+            .arg(repo.path())
+            .args(extra)
+            .env("BELLJAR_DATA_DIR", data.path())
+            .env("PATH", prepend_path(shim_dir.path()));
+        if extra.is_none() {
+            cmd.assert().success();
+        } else {
+            // The old worktree is still checked out on b1
+            cmd.assert()
+                .code(6)
+                .stderr(predicate::str::contains("is on b1, not b2"));
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+fn git(repo: &Path, args: &[&str]) -> bool {
+    Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .status()
+        .unwrap()
+        .success()
+}
+
+fn init_git_repo() -> tempfile::TempDir {
+    let td = TempDir::new().unwrap();
+    let repo = td.path();
+    assert!(git(repo, &["init"]));
+    assert!(git(repo, &["config", "user.email", "ci@example.com"]));
+    assert!(git(repo, &["config", "user.name", "CI"]));
+    fs::write(repo.join("README.md"), "init\n").unwrap();
+    fs::write(repo.join(".gitignore"), ".belljar/\n").unwrap();
+    assert!(git(repo, &["add", "."]));
+    assert!(git(repo, &["commit", "-m", "init"]));
+    td
+}
+
+fn prepend_path(dir: &Path) -> String {
+    let old = std::env::var("PATH").unwrap_or_default();
+    format!("{}:{}", dir.display(), old)
+}
+
+/// tmux shim where every session exists; logs calls
+fn make_tmux_shim() -> (TempDir, PathBuf) {
+    let dir = TempDir::new().unwrap();
+    let log = dir.path().join("tmux.log");
+    let shim = dir.path().join("tmux");
+    let script = format!(
+        "#!/usr/bin/env bash\necho \"$@\" >> {}\nexit 0\n",
+        log.display()
+    );
+    fs::write(&shim, script).unwrap();
+    let mut perm = fs::metadata(&shim).unwrap().permissions();
+    perm.set_mode(0o755);
+    fs::set_permissions(&shim, perm).unwrap();
+    (dir, log)
+}
+
+fn start(data: &Path, repo: &Path, label: &str) {
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["start", label, "--path"])
+        .arg(repo)
+        .env("BELLJAR_DATA_DIR", data)
+        .assert()
+        .success();
+}
+
+fn rm(data: &Path, shim: &Path, args: &[&str]) -> assert_cmd::assert::Assert {
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .arg("rm")
+        .args(args)
+        .env("BELLJAR_DATA_DIR", data)
+        .env("PATH", prepend_path(shim))
+        .assert()
+}
+
+#[test]
+fn rm_removes_worktree_and_kills_tmux() {
+    let data = TempDir::new().unwrap();
+    let repo = init_git_repo();
+    let (shim_dir, log) = make_tmux_shim();
+    start(data.path(), repo.path(), "s1");
+    let wt = repo.path().join(".belljar/worktrees/s1");
+    assert!(wt.exists());
+
+    rm(data.path(), shim_dir.path(), &["s1", "--delete-branch"])
+        .success()
+        .stdout(predicate::str::contains("removed s1"));
+
+    assert!(!wt.exists());
+    assert!(fs::read_to_string(&log)
+        .unwrap()
+        .contains("kill-session -t s1"));
+    assert!(!git(
+        repo.path(),
+        &["rev-parse", "--verify", "--quiet", "refs/heads/s1"]
+    ));
+}
+
+#[test]
+fn rm_refuses_dirty_worktree_without_force() {
+    let data = TempDir::new().unwrap();
+    let repo = init_git_repo();
+    let (shim_dir, _log) = make_tmux_shim();
+    start(data.path(), repo.path(), "s1");
+    let wt = repo.path().join(".belljar/worktrees/s1");
+    fs::write(wt.join("wip.txt"), "unsaved\n").unwrap();
+
+    rm(data.path(), shim_dir.path(), &["s1"])
+        .failure()
+        .code(5)
+        .stderr(predicate::str::contains("uncommitted changes"));
+    assert!(wt.join("wip.txt").exists());
+
+    rm(data.path(), shim_dir.path(), &["s1", "--force"])
+        .success()
+        .stdout(predicate::str::contains("removed s1"));
+    assert!(!wt.exists());
+}
+
+#[test]
+fn rm_all_skips_unpushed_work() {
+    let data = TempDir::new().unwrap();
+    let repo = init_git_repo();
+    let (shim_dir, _log) = make_tmux_shim();
+    start(data.path(), repo.path(), "clean");
+    start(data.path(), repo.path(), "ahead");
+    let wt = repo.path().join(".belljar/worktrees/ahead");
+    fs::write(wt.join("feature.txt"), "work\n").unwrap();
+    assert!(git(&wt, &["add", "."]));
+    assert!(git(&wt, &["commit", "-m", "local only"]));
+
+    rm(data.path(), shim_dir.path(), &["all", "--delete-branch"])
+        .failure()
+        .stdout(predicate::str::contains("removed clean"))
+        .stderr(predicate::str::contains("1 unpushed commit"));
+
+    assert!(wt.exists());
+    assert!(git(
+        repo.path(),
+        &["rev-parse", "--verify", "--quiet", "refs/heads/ahead"]
+    ));
+}
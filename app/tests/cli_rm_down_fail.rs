@@ -22,10 +22,18 @@ fn prepend_path(dir: &Path) -> String {
 fn rm_single_down_failure_warns() {
     let data = TempDir::new().unwrap();
     let repo = init_repo_with_compose();
-    // docker shim: any call fails
+    // docker shim: succeeds until the marker file exists, then every call fails
     let shim_dir = TempDir::new().unwrap();
+    let marker = shim_dir.path().join("fail");
     let docker = shim_dir.path().join("docker");
-    fs::write(&docker, "#!/usr/bin/env bash\nexit 1\n").unwrap();
+    fs::write(
+        &docker,
+        format!(
+            "#!/usr/bin/env bash\nif [ -e {} ]; then exit 1; fi\nexit 0\n",
+            marker.display()
+        ),
+    )
+    .unwrap();
     let mut perm = fs::metadata(&docker).unwrap().permissions();
     perm.set_mode(0o755);
     fs::set_permissions(&docker, perm).unwrap();
@@ -39,6 +47,7 @@ fn rm_single_down_failure_warns() {
         .env("PATH", prepend_path(shim_dir.path()))
         .assert()
         .success();
+    fs::write(&marker, "").unwrap();
 
     // rm s1 with failing docker
     Command::cargo_bin("belljar")
@@ -80,6 +89,8 @@ fn send_all_ensure_session_failure_warns() {
         .env("BELLJAR_DATA_DIR", data.path())
         .env("PATH", prepend_path(shim_dir.path()))
         .assert()
-        .success()
-        .stderr(predicate::str::contains("ensure session a failed"));
+        .failure()
+        .code(8)
+        .stderr(predicate::str::contains("ensure session a failed"))
+        .stderr(predicate::str::contains("ensure session b failed"));
 }
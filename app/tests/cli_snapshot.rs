@@ -0,0 +1,271 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+fn prepend_path(dir: &Path) -> String {
+    let old = std::env::var("PATH").unwrap_or_default();
+    format!("{}:{}", dir.display(), old)
+}
+
+/// docker shim that logs calls, reports a `<project>_pgdata` volume for
+/// every project, "exports" a volume as its name and saves whatever a
+/// restore container reads from stdin to `restored-<volume>`. Restoring
+/// fails when RESTORE_FAILS is set, and `up` when UP_FAILS is.
+fn make_docker_shim() -> (TempDir, PathBuf) {
+    let dir = TempDir::new().unwrap();
+    let log = dir.path().join("docker.log");
+    let shim = dir.path().join("docker");
+    let script = format!(
+        r#"#!/usr/bin/env bash
+echo "$@" >> {log}
+case " $* " in
+  *' up '*) [ -z "$UP_FAILS" ] ;;
+  *' --services '*) echo db ;;
+  *' config '*) echo '{{"services":{{}}}}' ;;
+  *' volume ls '*)
+    for a in "$@"; do
+      case "$a" in label=com.docker.compose.project=*) p="${{a##*=}}" ;; esac
+    done
+    printf '%s_pgdata\tpgdata\n' "$p" ;;
+  ' run '*' tar -cf '*) v="${{4%%:*}}"; echo "data of $v" ;;
+  ' run --rm -i '*)
+    [ -n "$RESTORE_FAILS" ] && exit 1
+    v="${{5%%:*}}"; cat > {dir}/restored-"$v" ;;
+esac
+"#,
+        log = log.display(),
+        dir = dir.path().display()
+    );
+    fs::write(&shim, script).unwrap();
+    let mut perm = fs::metadata(&shim).unwrap().permissions();
+    perm.set_mode(0o755);
+    fs::set_permissions(&shim, perm).unwrap();
+    (dir, log)
+}
+
+fn belljar(data: &Path, shim: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("belljar").unwrap();
+    cmd.env("BELLJAR_DATA_DIR", data)
+        .env("BELLJAR_RUNTIME", "docker")
+        .env("PATH", prepend_path(shim));
+    cmd
+}
+
+fn start(data: &Path, shim: &Path, repo: &Path, label: &str) -> String {
+    belljar(data, shim)
+        .args(["start", label, "-p"])
+        .arg(repo)
+        .assert()
+        .success();
+    let out = belljar(data, shim)
+        .args(["--json", "status", label])
+        .assert()
+        .success();
+    let doc: serde_json::Value = serde_json::from_slice(&out.get_output().stdout).unwrap();
+    doc["session"]["compose_project"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+fn make_repo() -> TempDir {
+    let repo = TempDir::new().unwrap();
+    fs::write(repo.path().join("docker-compose.yml"), "services: {}\n").unwrap();
+    repo
+}
+
+#[test]
+fn snapshot_and_restore_round_trip() {
+    let data = TempDir::new().unwrap();
+    let repo = make_repo();
+    let (shim, log) = make_docker_shim();
+    let project = start(data.path(), shim.path(), repo.path(), "a");
+    fs::write(&log, "").unwrap();
+
+    belljar(data.path(), shim.path())
+        .args(["snapshot", "a", "seeded"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("saved snapshot a@seeded (pgdata)"));
+    let tar = data.path().join("snapshots/a/seeded/pgdata.tar");
+    assert_eq!(
+        fs::read_to_string(&tar).unwrap(),
+        format!("data of {project}_pgdata\n")
+    );
+    let calls = fs::read_to_string(&log).unwrap();
+    let lines: Vec<&str> = calls.lines().collect();
+    let pause = lines.iter().position(|l| l.ends_with(" pause")).unwrap();
+    let export = lines
+        .iter()
+        .position(|l| l.contains(" tar -cf - "))
+        .unwrap();
+    let unpause = lines.iter().position(|l| l.ends_with(" unpause")).unwrap();
+    assert!(pause < export && export < unpause, "{calls}");
+
+    belljar(data.path(), shim.path())
+        .args(["snapshot", "a", "seeded"])
+        .assert()
+        .code(4);
+
+    let out = belljar(data.path(), shim.path())
+        .args(["--json", "snapshot", "ls"])
+        .assert()
+        .success();
+    let doc: serde_json::Value = serde_json::from_slice(&out.get_output().stdout).unwrap();
+    assert_eq!(doc["snapshots"][0]["label"], "a");
+    assert_eq!(doc["snapshots"][0]["name"], "seeded");
+    assert_eq!(doc["snapshots"][0]["compose_project"], project);
+    assert_eq!(
+        doc["snapshots"][0]["volumes"],
+        serde_json::json!(["pgdata"])
+    );
+
+    fs::write(&log, "").unwrap();
+    belljar(data.path(), shim.path())
+        .args(["restore", "a", "seeded"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "restored a from snapshot seeded (pgdata)",
+        ));
+    let restored = shim.path().join(format!("restored-{project}_pgdata"));
+    assert_eq!(
+        fs::read_to_string(restored).unwrap(),
+        format!("data of {project}_pgdata\n")
+    );
+    let calls = fs::read_to_string(&log).unwrap();
+    let lines: Vec<&str> = calls.lines().collect();
+    let stop = lines.iter().position(|l| l.ends_with(" stop")).unwrap();
+    let unpack = lines
+        .iter()
+        .position(|l| l.contains("run --rm -i"))
+        .unwrap();
+    let up = lines.iter().position(|l| l.contains(" up ")).unwrap();
+    assert!(stop < unpack && unpack < up, "{calls}");
+    assert!(lines[unpack].contains("find /to -mindepth 1 -delete && tar -xf - -C /to"));
+
+    belljar(data.path(), shim.path())
+        .args(["snapshot", "rm", "a", "seeded"])
+        .assert()
+        .success();
+    assert!(!tar.exists());
+    belljar(data.path(), shim.path())
+        .args(["snapshot", "ls", "a"])
+        .assert()
+        .success()
+        .stdout("no snapshots\n");
+}
+
+#[test]
+fn new_session_can_be_seeded_from_a_snapshot() {
+    let data = TempDir::new().unwrap();
+    let repo = make_repo();
+    let (shim, _log) = make_docker_shim();
+    let a = start(data.path(), shim.path(), repo.path(), "a");
+    belljar(data.path(), shim.path())
+        .args(["snapshot", "a", "seeded"])
+        .assert()
+        .success();
+
+    belljar(data.path(), shim.path())
+        .args(["start", "b", "--from-volumes", "a@seeded", "-p"])
+        .arg(repo.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("volumes copied: pgdata"));
+    let out = belljar(data.path(), shim.path())
+        .args(["--json", "status", "b"])
+        .assert()
+        .success();
+    let doc: serde_json::Value = serde_json::from_slice(&out.get_output().stdout).unwrap();
+    let b = doc["session"]["compose_project"].as_str().unwrap();
+    assert_eq!(
+        fs::read_to_string(shim.path().join(format!("restored-{b}_pgdata"))).unwrap(),
+        format!("data of {a}_pgdata\n")
+    );
+}
+
+#[test]
+fn snapshot_errors() {
+    let data = TempDir::new().unwrap();
+    let repo = make_repo();
+    let (shim, _log) = make_docker_shim();
+    start(data.path(), shim.path(), repo.path(), "a");
+
+    belljar(data.path(), shim.path())
+        .args(["snapshot", "a", "../escape"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("invalid snapshot name: ../escape"));
+    belljar(data.path(), shim.path())
+        .args(["snapshot", "ls", ".."])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("invalid session name: .."));
+    belljar(data.path(), shim.path())
+        .args(["snapshot", "rm", "../a", "x"])
+        .assert()
+        .code(2);
+    belljar(data.path(), shim.path())
+        .args(["restore", "a", "nope"])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("no such snapshot: a@nope"));
+    belljar(data.path(), shim.path())
+        .args(["start", "b", "--from-volumes", "a@nope", "-p"])
+        .arg(repo.path())
+        .assert()
+        .code(3);
+}
+
+#[test]
+fn failed_restore_leaves_the_session_up_or_marked_stopped() {
+    let data = TempDir::new().unwrap();
+    let repo = make_repo();
+    let (shim, log) = make_docker_shim();
+    let project = start(data.path(), shim.path(), repo.path(), "a");
+    belljar(data.path(), shim.path())
+        .args(["snapshot", "a", "s"])
+        .assert()
+        .success();
+    let stopped = || {
+        let out = belljar(data.path(), shim.path())
+            .args(["--json", "status", "a"])
+            .assert()
+            .success();
+        let doc: serde_json::Value = serde_json::from_slice(&out.get_output().stdout).unwrap();
+        doc["session"]["stopped"].clone()
+    };
+
+    fs::write(&log, "").unwrap();
+    belljar(data.path(), shim.path())
+        .env("RESTORE_FAILS", "1")
+        .args(["restore", "a", "s"])
+        .assert()
+        .failure();
+    let calls = fs::read_to_string(&log).unwrap();
+    let lines: Vec<&str> = calls.lines().collect();
+    let unpack = lines
+        .iter()
+        .position(|l| l.contains("run --rm -i"))
+        .unwrap();
+    let up = lines.iter().position(|l| l.contains(" up ")).unwrap();
+    assert!(unpack < up, "{calls}");
+    assert_eq!(stopped(), false);
+
+    belljar(data.path(), shim.path())
+        .env("RESTORE_FAILS", "1")
+        .env("UP_FAILS", "1")
+        .args(["restore", "a", "s"])
+        .assert()
+        .failure()
+        // The unpack error is reported, not the failed `up`
+        .stderr(predicate::str::contains(format!(
+            "restore {project}_pgdata"
+        )));
+    assert_eq!(stopped(), true);
+}
@@ -0,0 +1,167 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+fn prepend_path(dir: &Path) -> String {
+    let old = std::env::var("PATH").unwrap_or_default();
+    format!("{}:{}", dir.display(), old)
+}
+
+/// docker shim that logs every invocation
+fn make_docker_shim() -> (TempDir, PathBuf) {
+    let dir = TempDir::new().unwrap();
+    let log = dir.path().join("docker.log");
+    let shim = dir.path().join("docker");
+    let script = format!(
+        "#!/usr/bin/env bash\necho \"$@\" >> {}\nexit 0\n",
+        log.display()
+    );
+    fs::write(&shim, script).unwrap();
+    let mut perm = fs::metadata(&shim).unwrap().permissions();
+    perm.set_mode(0o755);
+    fs::set_permissions(&shim, perm).unwrap();
+    (dir, log)
+}
+
+/// `.belljar/compose/base.yml` plus an `api` stack of two files
+fn repo_with_stacks() -> TempDir {
+    let repo = TempDir::new().unwrap();
+    let compose = repo.path().join(".belljar/compose");
+    fs::create_dir_all(compose.join("api")).unwrap();
+    fs::write(compose.join("base.yml"), "services: {}\n").unwrap();
+    fs::write(compose.join("api/app.yml"), "services: {}\n").unwrap();
+    fs::write(compose.join("api/db.yaml"), "services: {}\n").unwrap();
+    repo
+}
+
+fn line_with(log: &Path, needle: &str) -> String {
+    fs::read_to_string(log)
+        .unwrap()
+        .lines()
+        .find(|l| l.contains(needle))
+        .unwrap_or_else(|| panic!("no `{needle}` call"))
+        .to_string()
+}
+
+#[test]
+fn stack_and_profiles_are_remembered() {
+    let data = TempDir::new().unwrap();
+    let repo = repo_with_stacks();
+    let (shim, log) = make_docker_shim();
+    let api = repo.path().join(".belljar/compose/api");
+    let files = format!(
+        "-f {} -f {} --profile debug --profile seed ",
+        api.join("app.yml").display(),
+        api.join("db.yaml").display()
+    );
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args([
+            "start",
+            "st",
+            "--stack",
+            "api",
+            "--profile",
+            "debug,seed",
+            "-p",
+        ])
+        .arg(repo.path())
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim.path()))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "[compose up; stack: api; profiles: debug,seed]",
+        ));
+    let up = line_with(&log, " up -d");
+    assert!(up.contains(&files), "{up}");
+    assert!(!up.contains("base.yml"), "{up}");
+
+    let out = Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["--json", "ls"])
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim.path()))
+        .assert()
+        .success();
+    let doc: serde_json::Value = serde_json::from_slice(&out.get_output().stdout).unwrap();
+    assert_eq!(doc["sessions"][0]["stack"], "api");
+    assert_eq!(
+        doc["sessions"][0]["profiles"],
+        serde_json::json!(["debug", "seed"])
+    );
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["logs", "st"])
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim.path()))
+        .assert()
+        .success();
+    let logs = line_with(&log, " logs");
+    assert!(logs.contains(&files), "{logs}");
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["rm", "st"])
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim.path()))
+        .assert()
+        .success();
+    let down = line_with(&log, " down -v");
+    assert!(down.contains(&files), "{down}");
+}
+
+#[test]
+fn default_selection_ignores_stacks() {
+    let data = TempDir::new().unwrap();
+    let repo = repo_with_stacks();
+    let (shim, log) = make_docker_shim();
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["start", "plain", "-p"])
+        .arg(repo.path())
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim.path()))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[compose up]"));
+    let up = line_with(&log, " up -d");
+    assert!(up.contains("base.yml"), "{up}");
+    assert!(!up.contains("app.yml") && !up.contains("--profile"), "{up}");
+}
+
+#[test]
+fn unknown_stack_is_rejected() {
+    let data = TempDir::new().unwrap();
+    let repo = repo_with_stacks();
+    let (shim, log) = make_docker_shim();
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["start", "bad", "--stack", "web", "-p"])
+        .arg(repo.path())
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim.path()))
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "unknown stack: web (.belljar/compose/ defines: api)",
+        ));
+    assert!(!fs::read_to_string(&log)
+        .unwrap_or_default()
+        .contains(" up "));
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .arg("ls")
+        .env("BELLJAR_DATA_DIR", data.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("no sessions"));
+}
@@ -0,0 +1,157 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn init_git_repo() -> tempfile::TempDir {
+    let td = TempDir::new().unwrap();
+    let repo = td.path();
+    for args in [
+        vec!["init"],
+        vec!["config", "user.email", "ci@example.com"],
+        vec!["config", "user.name", "CI"],
+    ] {
+        assert!(Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(&args)
+            .status()
+            .unwrap()
+            .success());
+    }
+    fs::write(repo.join("README.md"), "init\n").unwrap();
+    for args in [vec!["add", "."], vec!["commit", "-m", "init"]] {
+        assert!(Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(&args)
+            .status()
+            .unwrap()
+            .success());
+    }
+    td
+}
+
+fn prepend_path(dir: &Path) -> String {
+    let old = std::env::var("PATH").unwrap_or_default();
+    format!("{}:{}", dir.display(), old)
+}
+
+fn write_exe(path: &Path, script: &str) {
+    fs::write(path, script).unwrap();
+    let mut perm = fs::metadata(path).unwrap().permissions();
+    perm.set_mode(0o755);
+    fs::set_permissions(path, perm).unwrap();
+}
+
+/// docker reports every project as running with two containers; tmux knows
+/// only the session `st`
+fn make_shims() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    let docker = r#"#!/usr/bin/env bash
+for a in "$@"; do
+  if [ "$a" = ls ]; then echo "[{\"Name\":\"$PROJECT\",\"Status\":\"running(2)\"}]"; exit 0; fi
+  if [ "$a" = ps ]; then
+    echo '{"Service":"web","Name":"p-web-1","State":"running","Health":"","Publishers":[{"URL":"0.0.0.0","TargetPort":80,"PublishedPort":20001,"Protocol":"tcp"}]}'
+    echo '{"Service":"db","Name":"p-db-1","State":"running","Health":"healthy","Publishers":[{"URL":"","TargetPort":5432,"PublishedPort":0,"Protocol":"tcp"}]}'
+    exit 0
+  fi
+done
+exit 0
+"#;
+    write_exe(&dir.path().join("docker"), docker);
+    let tmux = "#!/usr/bin/env bash\nif [ \"$1\" = list-sessions ]; then echo st; fi\nexit 0\n";
+    write_exe(&dir.path().join("tmux"), tmux);
+    dir
+}
+
+#[test]
+fn ls_summary_and_status_detail() {
+    let data = TempDir::new().unwrap();
+    let repo = init_git_repo();
+    let shims = make_shims();
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["start", "st", "-p"])
+        .arg(repo.path())
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shims.path()))
+        .assert()
+        .success();
+    let out = Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["--json", "ls"])
+        .env("BELLJAR_DATA_DIR", data.path())
+        .assert()
+        .success();
+    let doc: serde_json::Value = serde_json::from_slice(&out.get_output().stdout).unwrap();
+    let project = doc["sessions"][0]["compose_project"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    fs::write(
+        repo.path().join(".belljar/worktrees/st/wip.txt"),
+        "uncommitted\n",
+    )
+    .unwrap();
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .arg("ls")
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shims.path()))
+        .env("PROJECT", &project)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "{project}\trunning(2), tmux"
+        )));
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["status", "st"])
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shims.path()))
+        .env("PROJECT", &project)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("(st, dirty)"))
+        .stdout(predicate::str::contains("tmux:      st (running)"))
+        .stdout(predicate::str::contains(format!(
+            "project:   {project} (running(2))"
+        )))
+        .stdout(predicate::str::contains("  db\trunning\thealthy\t-"))
+        .stdout(predicate::str::contains(
+            "  web\trunning\t-\t0.0.0.0:20001->80/tcp",
+        ));
+
+    let out = Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["--json", "status", "st"])
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shims.path()))
+        .env("PROJECT", &project)
+        .assert()
+        .success();
+    let doc: serde_json::Value = serde_json::from_slice(&out.get_output().stdout).unwrap();
+    assert_eq!(doc["branch"], "st");
+    assert_eq!(doc["dirty"], true);
+    assert_eq!(doc["session"]["status"]["tmux"], true);
+    assert_eq!(doc["services"][0]["service"], "db");
+    assert_eq!(doc["services"][0]["health"], "healthy");
+}
+
+#[test]
+fn status_of_unknown_session() {
+    let data = TempDir::new().unwrap();
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["status", "nope"])
+        .env("BELLJAR_DATA_DIR", data.path())
+        .assert()
+        .code(3);
+}
@@ -0,0 +1,173 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+fn prepend_path(dir: &Path) -> String {
+    let old = std::env::var("PATH").unwrap_or_default();
+    format!("{}:{}", dir.display(), old)
+}
+
+fn write_exe(path: &Path, script: &str) {
+    fs::write(path, script).unwrap();
+    let mut perm = fs::metadata(path).unwrap().permissions();
+    perm.set_mode(0o755);
+    fs::set_permissions(path, perm).unwrap();
+}
+
+/// docker and tmux shims that log every invocation to `calls.log`; tmux
+/// reports every session as existing
+fn make_shims() -> (TempDir, PathBuf) {
+    let dir = TempDir::new().unwrap();
+    let log = dir.path().join("calls.log");
+    write_exe(
+        &dir.path().join("docker"),
+        &format!(
+            "#!/usr/bin/env bash\necho \"docker $@\" >> {}\nexit 0\n",
+            log.display()
+        ),
+    );
+    write_exe(
+        &dir.path().join("tmux"),
+        &format!(
+            "#!/usr/bin/env bash\necho \"tmux $@\" >> {}\nexit 0\n",
+            log.display()
+        ),
+    );
+    (dir, log)
+}
+
+fn repo_with_compose() -> TempDir {
+    let repo = TempDir::new().unwrap();
+    fs::write(repo.path().join("docker-compose.yml"), "services: {}\n").unwrap();
+    repo
+}
+
+fn ls_json(data: &Path, shims: &Path) -> serde_json::Value {
+    let out = Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["--json", "ls"])
+        .env("BELLJAR_DATA_DIR", data)
+        .env("PATH", prepend_path(shims))
+        .assert()
+        .success();
+    serde_json::from_slice(&out.get_output().stdout).unwrap()
+}
+
+#[test]
+fn stop_keeps_volumes_and_resume_brings_the_project_back() {
+    let data = TempDir::new().unwrap();
+    let repo = repo_with_compose();
+    let (shims, log) = make_shims();
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["start", "idle", "-p"])
+        .arg(repo.path())
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shims.path()))
+        .assert()
+        .success();
+    fs::write(&log, "").unwrap();
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["stop", "idle", "--kill-tmux"])
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shims.path()))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("stopped session: idle"));
+    let calls = fs::read_to_string(&log).unwrap();
+    assert!(calls.contains("tmux kill-session -t idle"), "{calls}");
+    assert!(calls.contains(" stop\n"), "{calls}");
+    assert!(!calls.contains("down"), "{calls}");
+
+    let doc = ls_json(data.path(), shims.path());
+    assert_eq!(doc["sessions"][0]["stopped"], true);
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .arg("ls")
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shims.path()))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\tstopped, "));
+
+    fs::write(&log, "").unwrap();
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["resume", "idle"])
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shims.path()))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("resumed session: idle"));
+    let calls = fs::read_to_string(&log).unwrap();
+    assert!(calls.contains(" up -d"), "{calls}");
+    assert!(!calls.contains("down"), "{calls}");
+    let doc = ls_json(data.path(), shims.path());
+    assert_eq!(doc["sessions"][0]["stopped"], false);
+}
+
+#[test]
+fn stop_detach_leaves_tmux_running() {
+    let data = TempDir::new().unwrap();
+    let repo = repo_with_compose();
+    let (shims, log) = make_shims();
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["start", "det", "-p"])
+        .arg(repo.path())
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shims.path()))
+        .assert()
+        .success();
+    let out = Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["--json", "stop", "det", "--detach"])
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shims.path()))
+        .assert()
+        .success();
+    let doc: serde_json::Value = serde_json::from_slice(&out.get_output().stdout).unwrap();
+    assert_eq!(doc["session"]["label"], "det");
+    assert_eq!(doc["session"]["stopped"], true);
+    let calls = fs::read_to_string(&log).unwrap();
+    assert!(calls.contains("tmux detach-client -s det"), "{calls}");
+    assert!(!calls.contains("kill-session"), "{calls}");
+}
+
+#[test]
+fn failed_stop_does_not_mark_the_session() {
+    let data = TempDir::new().unwrap();
+    let repo = repo_with_compose();
+    let (shims, _log) = make_shims();
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["start", "stuck", "-p"])
+        .arg(repo.path())
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shims.path()))
+        .assert()
+        .success();
+    write_exe(
+        &shims.path().join("docker"),
+        "#!/usr/bin/env bash\nfor a in \"$@\"; do [ \"$a\" = stop ] && exit 1; done\nexit 0\n",
+    );
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["stop", "stuck"])
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shims.path()))
+        .assert()
+        .code(7)
+        .stderr(predicate::str::contains("stop failed"));
+    let doc = ls_json(data.path(), shims.path());
+    assert_eq!(doc["sessions"][0]["stopped"], false);
+}
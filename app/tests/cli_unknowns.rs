@@ -10,8 +10,9 @@ fn rm_unknown_session() {
         .args(["rm", "nope"])
         .env("BELLJAR_DATA_DIR", data.path())
         .assert()
-        .success()
-        .stdout(predicate::str::contains("no such session: nope"));
+        .failure()
+        .code(3)
+        .stderr(predicate::str::contains("no such session: nope"));
 }
 
 #[test]
@@ -22,8 +23,9 @@ fn open_unknown_session() {
         .args(["open", "nope"])
         .env("BELLJAR_DATA_DIR", data.path())
         .assert()
-        .success()
-        .stdout(predicate::str::contains("no such session: nope"));
+        .failure()
+        .code(3)
+        .stderr(predicate::str::contains("no such session: nope"));
 }
 
 #[test]
@@ -34,6 +36,7 @@ fn send_unknown_session() {
         .args(["send", "nope", "echo"])
         .env("BELLJAR_DATA_DIR", data.path())
         .assert()
-        .success()
-        .stdout(predicate::str::contains("no such session: nope"));
+        .failure()
+        .code(3)
+        .stderr(predicate::str::contains("no such session: nope"));
 }
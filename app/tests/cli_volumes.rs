@@ -0,0 +1,213 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+fn prepend_path(dir: &Path) -> String {
+    let old = std::env::var("PATH").unwrap_or_default();
+    format!("{}:{}", dir.display(), old)
+}
+
+/// docker shim that logs calls and reports two volumes for every project:
+/// `<project>_pgdata` and a fixed-name `shared-cache`. `run` fails when
+/// FAIL_RUN is set.
+fn make_docker_shim() -> (TempDir, PathBuf) {
+    let dir = TempDir::new().unwrap();
+    let log = dir.path().join("docker.log");
+    let shim = dir.path().join("docker");
+    let script = format!(
+        r#"#!/usr/bin/env bash
+echo "$@" >> {}
+case " $* " in
+  *' --services '*) echo db ;;
+  *' config '*) echo '{{"services":{{}}}}' ;;
+  *' volume ls '*)
+    for a in "$@"; do
+      case "$a" in label=com.docker.compose.project=*) p="${{a##*=}}" ;; esac
+    done
+    printf '%s_pgdata\tpgdata\nshared-cache\tcache\n' "$p" ;;
+  ' run '*) [ -n "$FAIL_RUN" ] && exit 1 ;;
+esac
+exit 0
+"#,
+        log.display()
+    );
+    fs::write(&shim, script).unwrap();
+    let mut perm = fs::metadata(&shim).unwrap().permissions();
+    perm.set_mode(0o755);
+    fs::set_permissions(&shim, perm).unwrap();
+    (dir, log)
+}
+
+fn belljar(data: &Path, shim: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("belljar").unwrap();
+    cmd.env("BELLJAR_DATA_DIR", data)
+        .env("BELLJAR_RUNTIME", "docker")
+        .env("PATH", prepend_path(shim));
+    cmd
+}
+
+fn project_of(data: &Path, shim: &Path, label: &str) -> String {
+    let out = belljar(data, shim)
+        .args(["--json", "status", label])
+        .assert()
+        .success();
+    let doc: serde_json::Value = serde_json::from_slice(&out.get_output().stdout).unwrap();
+    doc["session"]["compose_project"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+fn make_template(data: &Path, shim: &Path, repo: &Path) -> String {
+    belljar(data, shim)
+        .args(["start", "tpl", "-p"])
+        .arg(repo)
+        .assert()
+        .success();
+    belljar(data, shim)
+        .args(["template", "tpl"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("marked tpl as a template"));
+    project_of(data, shim, "tpl")
+}
+
+#[test]
+fn from_volumes_copies_template_volumes_before_up() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    fs::write(repo.path().join("docker-compose.yml"), "services: {}\n").unwrap();
+    let (shim, log) = make_docker_shim();
+    let tpl = make_template(data.path(), shim.path(), repo.path());
+    fs::write(&log, "").unwrap();
+
+    belljar(data.path(), shim.path())
+        .args(["start", "b", "--from-volumes", "tpl", "-p"])
+        .arg(repo.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("volumes copied: pgdata"));
+    let b = project_of(data.path(), shim.path(), "b");
+
+    let calls = fs::read_to_string(&log).unwrap();
+    let lines: Vec<&str> = calls.lines().collect();
+    let pos = |needle: &str| {
+        lines
+            .iter()
+            .position(|l| l.contains(needle))
+            .unwrap_or_else(|| panic!("{needle} not in {calls}"))
+    };
+    let create = pos(&format!(
+        "volume create --label com.docker.compose.project={b} --label com.docker.compose.volume=pgdata {b}_pgdata"
+    ));
+    let copy = pos(&format!(
+        "run --rm -v {tpl}_pgdata:/from:ro -v {b}_pgdata:/to busybox cp -a /from/. /to/"
+    ));
+    let pause = pos(&format!("-p {tpl} "));
+    let up = pos(" up ");
+    assert!(lines[pause].ends_with(" pause"), "{calls}");
+    assert!(pause < create && create < copy && copy < up, "{calls}");
+    assert!(
+        lines[..up].iter().any(|l| l.ends_with(" unpause")),
+        "{calls}"
+    );
+    // Fixed-name volumes are shared, not copied
+    assert!(!calls.contains("shared-cache:/from"), "{calls}");
+
+    belljar(data.path(), shim.path())
+        .arg("ls")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("template, "));
+}
+
+#[test]
+fn failed_copy_rolls_back_created_volumes() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    fs::write(repo.path().join("docker-compose.yml"), "services: {}\n").unwrap();
+    let (shim, log) = make_docker_shim();
+    make_template(data.path(), shim.path(), repo.path());
+    fs::write(&log, "").unwrap();
+
+    belljar(data.path(), shim.path())
+        .env("FAIL_RUN", "1")
+        .args(["start", "b", "--from-volumes", "tpl", "-p"])
+        .arg(repo.path())
+        .assert()
+        .code(7)
+        .stderr(predicate::str::contains("copying volumes failed"));
+    let calls = fs::read_to_string(&log).unwrap();
+    assert!(calls.contains("volume rm -f belljar_"), "{calls}");
+    assert!(!calls.contains(" up "), "{calls}");
+    belljar(data.path(), shim.path())
+        .args(["status", "b"])
+        .assert()
+        .code(3);
+}
+
+#[test]
+fn from_volumes_needs_a_marked_template() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    fs::write(repo.path().join("docker-compose.yml"), "services: {}\n").unwrap();
+    let (shim, _log) = make_docker_shim();
+    make_template(data.path(), shim.path(), repo.path());
+    belljar(data.path(), shim.path())
+        .args(["template", "tpl", "--unset"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("tpl is no longer a template"));
+
+    belljar(data.path(), shim.path())
+        .args(["start", "b", "--from-volumes", "tpl", "-p"])
+        .arg(repo.path())
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("no such template: tpl"));
+}
+
+#[test]
+fn from_volumes_needs_compose_files_on_both_sides() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    fs::write(repo.path().join("docker-compose.yml"), "services: {}\n").unwrap();
+    let plain = TempDir::new().unwrap();
+    let (shim, _log) = make_docker_shim();
+    make_template(data.path(), shim.path(), repo.path());
+
+    belljar(data.path(), shim.path())
+        .args(["start", "b", "--from-volumes", "tpl", "-p"])
+        .arg(plain.path())
+        .assert()
+        .code(10)
+        .stderr(predicate::str::contains(
+            "session b has no compose files to seed volumes for",
+        ));
+    belljar(data.path(), shim.path())
+        .args(["status", "b"])
+        .assert()
+        .code(3);
+
+    belljar(data.path(), shim.path())
+        .args(["start", "bare", "-p"])
+        .arg(plain.path())
+        .assert()
+        .success();
+    belljar(data.path(), shim.path())
+        .args(["template", "bare"])
+        .assert()
+        .success();
+    belljar(data.path(), shim.path())
+        .args(["start", "c", "--from-volumes", "bare", "-p"])
+        .arg(repo.path())
+        .assert()
+        .code(10)
+        .stderr(predicate::str::contains(
+            "template bare has no compose files",
+        ));
+}
@@ -0,0 +1,151 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+fn prepend_path(dir: &Path) -> String {
+    let old = std::env::var("PATH").unwrap_or_default();
+    format!("{}:{}", dir.display(), old)
+}
+
+/// docker shim defining db and web. `ps` reports web running and db running
+/// with the health given by successive lines of `db_health` (the last line
+/// repeats); every invocation is logged.
+fn make_docker_shim(db_health: &[&str]) -> (TempDir, PathBuf) {
+    let dir = TempDir::new().unwrap();
+    let log = dir.path().join("docker.log");
+    let health = dir.path().join("db_health");
+    let count = dir.path().join("ps_count");
+    fs::write(&health, db_health.join("\n") + "\n").unwrap();
+    let shim = dir.path().join("docker");
+    let script = format!(
+        r#"#!/usr/bin/env bash
+echo "$@" >> {log}
+for a in "$@"; do
+  case "$a" in
+    config) printf 'db\nweb\n'; exit 0;;
+    ps)
+      n=$(cat {count} 2>/dev/null || echo 0); echo $((n+1)) > {count}
+      h=$(sed -n "$((n+1))p" {health}); [ -z "$h" ] && h=$(tail -n1 {health})
+      echo "{{\"Service\":\"db\",\"Name\":\"p-db-1\",\"State\":\"running\",\"Health\":\"$h\",\"ExitCode\":0}}"
+      echo '{{"Service":"web","Name":"p-web-1","State":"running","Health":"","ExitCode":0}}'
+      exit 0;;
+  esac
+done
+exit 0
+"#,
+        log = log.display(),
+        count = count.display(),
+        health = health.display(),
+    );
+    fs::write(&shim, script).unwrap();
+    let mut perm = fs::metadata(&shim).unwrap().permissions();
+    perm.set_mode(0o755);
+    fs::set_permissions(&shim, perm).unwrap();
+    (dir, log)
+}
+
+fn repo_with_compose() -> TempDir {
+    let repo = TempDir::new().unwrap();
+    fs::write(repo.path().join("docker-compose.yml"), "services: {}\n").unwrap();
+    repo
+}
+
+#[test]
+fn wait_reports_progress_until_healthy() {
+    let data = TempDir::new().unwrap();
+    let repo = repo_with_compose();
+    let (shim, _log) = make_docker_shim(&["starting", "healthy"]);
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["start", "w", "--wait", "-p"])
+        .arg(repo.path())
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim.path()))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("waiting for services of w..."))
+        .stdout(predicate::str::contains("  db: running (starting)"))
+        .stdout(predicate::str::contains("  db: running (healthy)"))
+        .stdout(predicate::str::contains("  web: running"))
+        .stdout(predicate::str::contains("created session: w"));
+}
+
+#[test]
+fn unhealthy_service_fails_and_rolls_back() {
+    let data = TempDir::new().unwrap();
+    let repo = repo_with_compose();
+    let (shim, log) = make_docker_shim(&["starting", "unhealthy"]);
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["start", "sick", "--wait=30", "-p"])
+        .arg(repo.path())
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim.path()))
+        .assert()
+        .code(7)
+        .stderr(predicate::str::contains(
+            "waiting for services failed: service db is not ready: unhealthy (session sick rolled back)",
+        ));
+    let calls = fs::read_to_string(&log).unwrap();
+    assert!(calls.contains("down -v"), "{calls}");
+
+    let out = Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["--json", "ls"])
+        .env("BELLJAR_DATA_DIR", data.path())
+        .assert()
+        .success();
+    let doc: serde_json::Value = serde_json::from_slice(&out.get_output().stdout).unwrap();
+    assert_eq!(doc["sessions"], serde_json::json!([]));
+}
+
+#[test]
+fn wait_times_out_naming_the_service() {
+    let data = TempDir::new().unwrap();
+    let repo = repo_with_compose();
+    let (shim, _log) = make_docker_shim(&["starting"]);
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args([
+            "--json",
+            "start",
+            "slow",
+            "--wait=1",
+            "--keep-on-failure",
+            "-p",
+        ])
+        .arg(repo.path())
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim.path()))
+        .assert()
+        .code(7)
+        .stdout(predicate::str::contains(
+            "service db is not ready: still starting after 1s",
+        ))
+        .stdout(predicate::str::contains("waiting for services of").not());
+}
+
+#[test]
+fn wait_only_covers_selected_services() {
+    let data = TempDir::new().unwrap();
+    let repo = repo_with_compose();
+    let (shim, _log) = make_docker_shim(&["unhealthy"]);
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["start", "webonly", "--with", "web", "--wait", "-p"])
+        .arg(repo.path())
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim.path()))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("  web: running"))
+        .stdout(predicate::str::contains("db:").not());
+}
@@ -0,0 +1,113 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+fn prepend_path(dir: &Path) -> String {
+    let old = std::env::var("PATH").unwrap_or_default();
+    format!("{}:{}", dir.display(), old)
+}
+
+/// docker shim that defines db, redis and web and logs every invocation
+fn make_docker_shim() -> (TempDir, PathBuf) {
+    let dir = TempDir::new().unwrap();
+    let log = dir.path().join("docker.log");
+    let shim = dir.path().join("docker");
+    let script = format!(
+        "#!/usr/bin/env bash\necho \"$@\" >> {}\nfor a in \"$@\"; do if [ \"$a\" = config ]; then printf 'db\\nredis\\nweb\\n'; fi; done\nexit 0\n",
+        log.display()
+    );
+    fs::write(&shim, script).unwrap();
+    let mut perm = fs::metadata(&shim).unwrap().permissions();
+    perm.set_mode(0o755);
+    fs::set_permissions(&shim, perm).unwrap();
+    (dir, log)
+}
+
+fn repo_with_compose() -> TempDir {
+    let repo = TempDir::new().unwrap();
+    fs::write(repo.path().join("docker-compose.yml"), "services: {}\n").unwrap();
+    repo
+}
+
+#[test]
+fn start_with_selected_services() {
+    let data = TempDir::new().unwrap();
+    let repo = repo_with_compose();
+    let (shim, log) = make_docker_shim();
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["start", "sel", "--with", "db,redis,db", "-p"])
+        .arg(repo.path())
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim.path()))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[compose up: db,redis]"));
+    let calls = fs::read_to_string(&log).unwrap();
+    assert!(calls.contains("config --services"), "{calls}");
+    assert!(calls.contains("up -d db redis\n"), "{calls}");
+
+    let out = Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["--json", "ls"])
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim.path()))
+        .assert()
+        .success();
+    let doc: serde_json::Value = serde_json::from_slice(&out.get_output().stdout).unwrap();
+    assert_eq!(
+        doc["sessions"][0]["services"],
+        serde_json::json!(["db", "redis"])
+    );
+}
+
+#[test]
+fn unknown_service_is_rejected_before_anything_is_created() {
+    let data = TempDir::new().unwrap();
+    let repo = repo_with_compose();
+    let (shim, log) = make_docker_shim();
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["start", "bad", "--with", "db,postgres", "-p"])
+        .arg(repo.path())
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim.path()))
+        .assert()
+        .failure()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "session setup failed: unknown service(s): postgres (compose files define: db, redis, web)",
+        ));
+    assert!(!fs::read_to_string(&log).unwrap().contains(" up "));
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .arg("ls")
+        .env("BELLJAR_DATA_DIR", data.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("no sessions"));
+}
+
+#[test]
+fn with_requires_compose_files() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["start", "none", "--with", "db", "-p"])
+        .arg(repo.path())
+        .env("BELLJAR_DATA_DIR", data.path())
+        .assert()
+        .failure()
+        .code(7)
+        .stderr(predicate::str::contains(
+            "session setup failed: no compose files found",
+        ));
+}
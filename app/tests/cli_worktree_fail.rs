@@ -10,11 +10,7 @@ fn prepend_path(dir: &Path) -> String {
     format!("{}:{}", dir.display(), old)
 }
 
-#[test]
-fn worktree_setup_failure_is_warned() {
-    let data = TempDir::new().unwrap();
-    let repo = TempDir::new().unwrap();
-
+fn make_failing_git_shim() -> TempDir {
     // git shim: rev-parse succeeds -> considered a git repo; worktree add fails
     let shim_dir = TempDir::new().unwrap();
     let git = shim_dir.path().join("git");
@@ -23,6 +19,14 @@ fn worktree_setup_failure_is_warned() {
     let mut perm = fs::metadata(&git).unwrap().permissions();
     perm.set_mode(0o755);
     fs::set_permissions(&git, perm).unwrap();
+    shim_dir
+}
+
+#[test]
+fn worktree_setup_failure_rolls_back() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    let shim_dir = make_failing_git_shim();
 
     Command::cargo_bin("belljar")
         .unwrap()
@@ -31,6 +35,40 @@ fn worktree_setup_failure_is_warned() {
         .env("BELLJAR_DATA_DIR", data.path())
         .env("PATH", prepend_path(shim_dir.path()))
         .assert()
+        .failure()
+        .stderr(predicate::str::contains("worktree setup failed"))
+        .stderr(predicate::str::contains("rolled back"));
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .arg("ls")
+        .env("BELLJAR_DATA_DIR", data.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("no sessions"));
+}
+
+#[test]
+fn keep_on_failure_leaves_session() {
+    let data = TempDir::new().unwrap();
+    let repo = TempDir::new().unwrap();
+    let shim_dir = make_failing_git_shim();
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .args(["start", "s1", "--keep-on-failure", "--path"])
+        .arg(repo.path())
+        .env("BELLJAR_DATA_DIR", data.path())
+        .env("PATH", prepend_path(shim_dir.path()))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("partial session s1 kept"));
+
+    Command::cargo_bin("belljar")
+        .unwrap()
+        .arg("ls")
+        .env("BELLJAR_DATA_DIR", data.path())
+        .assert()
         .success()
-        .stderr(predicate::str::contains("worktree setup failed"));
+        .stdout(predicate::str::contains("s1\t"));
 }
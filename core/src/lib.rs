@@ -3,11 +3,16 @@
 use directories::ProjectDirs;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
+use std::os::fd::AsFd;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
 use std::{
-    fs::{self, File},
+    fs::{self, File, OpenOptions},
     io::{Read, Write},
     path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
 };
 use thiserror::Error;
 use time::OffsetDateTime;
@@ -30,10 +35,50 @@ pub enum CoreError {
     Compose(String),
     #[error("no compose files found in repository")]
     NoComposeFiles,
+    #[error("git error: {0}")]
+    Git(String),
+    #[error("no such {kind}: {label}")]
+    NotFound { kind: &'static str, label: String },
     #[error("tmux not found in PATH")]
     TmuxNotFound,
     #[error("tmux error: {0}")]
     Tmux(String),
+    #[error("timed out after {0:?} waiting for registry lock (another belljar may be running)")]
+    RegistryLocked(Duration),
+    #[error(
+        "registry schema version {found} is newer than this belljar supports ({supported}); upgrade belljar"
+    )]
+    RegistryTooNew { found: u32, supported: u32 },
+    #[error("unknown service(s): {} (compose files define: {})", unknown.join(", "), available.join(", "))]
+    UnknownServices {
+        unknown: Vec<String>,
+        available: Vec<String>,
+    },
+    #[error("service {service} is not ready: {reason}")]
+    ServiceNotReady { service: String, reason: String },
+    #[error(
+        "unknown stack: {stack} (.belljar/compose/ defines: {})",
+        or_none(available)
+    )]
+    UnknownStack {
+        stack: String,
+        available: Vec<String>,
+    },
+    #[error("invalid {kind} name: {name}")]
+    InvalidName { kind: &'static str, name: String },
+    #[error("invalid resource limit: {0}")]
+    InvalidLimit(String),
+    #[error("{kind} label already in use: {label}")]
+    LabelConflict { kind: &'static str, label: String },
+    #[error("refusing to remove session {label}: {reason}")]
+    UnsafeRemoval { label: String, reason: String },
+    #[error("config error: {0}")]
+    Config(String),
+    #[error("{step} failed: {source}")]
+    StepFailed {
+        step: lifecycle::Step,
+        source: Box<CoreError>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -47,6 +92,41 @@ pub struct Session {
     pub services: Vec<String>,
     pub tmux_session: String,
     pub created_at: String,
+    /// Host ports allocated to this session's published compose ports.
+    #[serde(default)]
+    pub ports: Vec<ports::PortMapping>,
+    /// Set by `stop`: containers are stopped but kept, along with volumes.
+    #[serde(default)]
+    pub stopped: bool,
+    /// Compose files `up` was run with, reused by every later compose
+    /// command. Empty until the first `up`.
+    #[serde(default)]
+    pub compose_files: Vec<PathBuf>,
+    /// Stack under `.belljar/compose/<stack>/` the files were taken from.
+    #[serde(default)]
+    pub stack: Option<String>,
+    /// Compose profiles enabled for every compose command.
+    #[serde(default)]
+    pub profiles: Vec<String>,
+    /// CPU and memory caps applied through a generated compose override.
+    #[serde(default)]
+    pub limits: Vec<limits::Limit>,
+    /// Marked by `belljar template`: new sessions may copy its volumes.
+    #[serde(default)]
+    pub template: bool,
+    /// Docker context the session's containers run on.
+    #[serde(default)]
+    pub docker_context: Option<String>,
+    /// Engine address (`DOCKER_HOST`) the session's containers run on.
+    #[serde(default)]
+    pub docker_host: Option<String>,
+}
+
+impl Session {
+    /// The session's worktree, or the repo itself when it has none.
+    pub fn work_dir(&self) -> &Path {
+        self.worktree_path.as_deref().unwrap_or(&self.repo_path)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -59,13 +139,146 @@ pub struct Workspace {
     pub created_at: String,
 }
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+fn or_none(items: &[String]) -> String {
+    if items.is_empty() {
+        "none".into()
+    } else {
+        items.join(", ")
+    }
+}
+
+/// Current on-disk registry schema version. Bump it (and add a step to
+/// `migrations::STEPS`) whenever the persisted shape changes.
+pub const REGISTRY_SCHEMA_VERSION: u32 = 8;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Registry {
+    #[serde(default)]
+    pub schema_version: u32,
     pub sessions: Vec<Session>,
     #[serde(default)]
     pub workspaces: Vec<Workspace>,
 }
 
+impl Default for Registry {
+    fn default() -> Self {
+        Self {
+            schema_version: REGISTRY_SCHEMA_VERSION,
+            sessions: Vec::new(),
+            workspaces: Vec::new(),
+        }
+    }
+}
+
+mod migrations {
+    use super::{CoreError, REGISTRY_SCHEMA_VERSION};
+    use serde_json::{json, Value};
+
+    type Step = fn(&mut Value);
+
+    /// `STEPS[n]` upgrades a registry from schema version `n` to `n + 1`.
+    const STEPS: &[Step] = &[
+        v0_to_v1, v1_to_v2, v2_to_v3, v3_to_v4, v4_to_v5, v5_to_v6, v6_to_v7, v7_to_v8,
+    ];
+
+    /// Registries written before versioning may lack `workspaces`.
+    fn v0_to_v1(raw: &mut Value) {
+        if let Some(obj) = raw.as_object_mut() {
+            obj.entry("workspaces").or_insert_with(|| json!([]));
+        }
+    }
+
+    /// Sessions gained allocated host `ports`.
+    fn v1_to_v2(raw: &mut Value) {
+        if let Some(sessions) = raw.get_mut("sessions").and_then(Value::as_array_mut) {
+            for s in sessions.iter_mut().filter_map(Value::as_object_mut) {
+                s.entry("ports").or_insert_with(|| json!([]));
+            }
+        }
+    }
+
+    /// Sessions gained the `stopped` flag.
+    fn v2_to_v3(raw: &mut Value) {
+        if let Some(sessions) = raw.get_mut("sessions").and_then(Value::as_array_mut) {
+            for s in sessions.iter_mut().filter_map(Value::as_object_mut) {
+                s.entry("stopped").or_insert_with(|| json!(false));
+            }
+        }
+    }
+
+    /// Sessions gained the recorded `compose_files`.
+    fn v3_to_v4(raw: &mut Value) {
+        if let Some(sessions) = raw.get_mut("sessions").and_then(Value::as_array_mut) {
+            for s in sessions.iter_mut().filter_map(Value::as_object_mut) {
+                s.entry("compose_files").or_insert_with(|| json!([]));
+            }
+        }
+    }
+
+    /// Sessions gained `stack` and `profiles`.
+    fn v4_to_v5(raw: &mut Value) {
+        if let Some(sessions) = raw.get_mut("sessions").and_then(Value::as_array_mut) {
+            for s in sessions.iter_mut().filter_map(Value::as_object_mut) {
+                s.entry("stack").or_insert(Value::Null);
+                s.entry("profiles").or_insert_with(|| json!([]));
+            }
+        }
+    }
+
+    /// Sessions gained resource `limits`.
+    fn v5_to_v6(raw: &mut Value) {
+        if let Some(sessions) = raw.get_mut("sessions").and_then(Value::as_array_mut) {
+            for s in sessions.iter_mut().filter_map(Value::as_object_mut) {
+                s.entry("limits").or_insert_with(|| json!([]));
+            }
+        }
+    }
+
+    /// Sessions gained the `template` flag.
+    fn v6_to_v7(raw: &mut Value) {
+        if let Some(sessions) = raw.get_mut("sessions").and_then(Value::as_array_mut) {
+            for s in sessions.iter_mut().filter_map(Value::as_object_mut) {
+                s.entry("template").or_insert_with(|| json!(false));
+            }
+        }
+    }
+
+    /// Sessions gained an optional Docker context and engine address.
+    fn v7_to_v8(raw: &mut Value) {
+        if let Some(sessions) = raw.get_mut("sessions").and_then(Value::as_array_mut) {
+            for s in sessions.iter_mut().filter_map(Value::as_object_mut) {
+                s.entry("docker_context").or_insert(Value::Null);
+                s.entry("docker_host").or_insert(Value::Null);
+            }
+        }
+    }
+
+    pub fn version_of(raw: &Value) -> u32 {
+        raw.get("schema_version")
+            .and_then(Value::as_u64)
+            .map(|v| v as u32)
+            .unwrap_or(0)
+    }
+
+    /// Upgrade `raw` in place to the current schema version.
+    pub fn migrate(raw: &mut Value) -> Result<(), CoreError> {
+        let found = version_of(raw);
+        if found > REGISTRY_SCHEMA_VERSION {
+            return Err(CoreError::RegistryTooNew {
+                found,
+                supported: REGISTRY_SCHEMA_VERSION,
+            });
+        }
+        for step in &STEPS[found as usize..] {
+            step(raw);
+        }
+        if let Some(obj) = raw.as_object_mut() {
+            obj.insert("schema_version".into(), json!(REGISTRY_SCHEMA_VERSION));
+        }
+        Ok(())
+    }
+}
+
 static DATA_DIR_OVERRIDE: Lazy<Mutex<Option<PathBuf>>> = Lazy::new(|| Mutex::new(None));
 
 fn data_dir() -> Result<PathBuf, CoreError> {
@@ -90,26 +303,136 @@ fn registry_path() -> Result<PathBuf, CoreError> {
     Ok(data_dir()?.join("registry.json"))
 }
 
-pub fn load_registry() -> Result<Registry, CoreError> {
+const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn lock_timeout() -> Duration {
+    std::env::var("BELLJAR_LOCK_TIMEOUT_MS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_LOCK_TIMEOUT)
+}
+
+/// Exclusive advisory lock on the registry, released when dropped.
+///
+/// Every read-modify-write of `registry.json` happens while holding this lock
+/// so concurrent belljar invocations don't lose each other's updates.
+pub struct RegistryLock {
+    _file: File,
+}
+
+pub fn lock_registry() -> Result<RegistryLock, CoreError> {
+    let path = data_dir()?.join("registry.lock");
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)?;
+    let timeout = lock_timeout();
+    let deadline = Instant::now() + timeout;
+    loop {
+        match file.try_lock() {
+            Ok(()) => return Ok(RegistryLock { _file: file }),
+            Err(fs::TryLockError::WouldBlock) => {
+                if Instant::now() >= deadline {
+                    return Err(CoreError::RegistryLocked(timeout));
+                }
+                thread::sleep(Duration::from_millis(25));
+            }
+            Err(fs::TryLockError::Error(e)) => return Err(CoreError::Io(e)),
+        }
+    }
+}
+
+fn read_raw_registry() -> Result<Option<(String, serde_json::Value)>, CoreError> {
     let path = registry_path()?;
     if !path.exists() {
-        return Ok(Registry::default());
+        return Ok(None);
     }
     let mut f = File::open(path)?;
     let mut s = String::new();
     f.read_to_string(&mut s)?;
-    let reg: Registry = serde_json::from_str(&s)?;
+    let raw = serde_json::from_str(&s)?;
+    Ok(Some((s, raw)))
+}
+
+/// Load the registry, migrating an older schema on disk if needed. Callers
+/// must hold the lock. The pre-migration file is kept next to the registry
+/// as `registry.json.v<old>.bak`.
+fn load_registry_locked() -> Result<Registry, CoreError> {
+    let Some((text, mut raw)) = read_raw_registry()? else {
+        return Ok(Registry::default());
+    };
+    let found = migrations::version_of(&raw);
+    migrations::migrate(&mut raw)?;
+    let reg: Registry = serde_json::from_value(raw)?;
+    if found < REGISTRY_SCHEMA_VERSION {
+        let backup = registry_path()?.with_file_name(format!("registry.json.v{found}.bak"));
+        fs::write(backup, text)?;
+        write_registry(&reg)?;
+    }
     Ok(reg)
 }
 
-pub fn save_registry(reg: &Registry) -> Result<(), CoreError> {
+pub fn load_registry() -> Result<Registry, CoreError> {
+    let Some((_, mut raw)) = read_raw_registry()? else {
+        return Ok(Registry::default());
+    };
+    if migrations::version_of(&raw) < REGISTRY_SCHEMA_VERSION {
+        // Upgrade on disk once, under the lock, so concurrent readers agree
+        let _lock = lock_registry()?;
+        return load_registry_locked();
+    }
+    migrations::migrate(&mut raw)?;
+    Ok(serde_json::from_value(raw)?)
+}
+
+/// Write the registry to a temp file and rename it over `registry.json`, so
+/// readers never observe a partially written file. Callers must hold the lock.
+fn write_registry(reg: &Registry) -> Result<(), CoreError> {
     let path = registry_path()?;
-    let mut f = File::create(path)?;
+    let tmp = path.with_file_name(format!(".registry.json.{}.tmp", std::process::id()));
     let s = serde_json::to_string_pretty(reg)?;
-    f.write_all(s.as_bytes())?;
+    let res = (|| {
+        let mut f = File::create(&tmp)?;
+        f.write_all(s.as_bytes())?;
+        f.sync_all()?;
+        fs::rename(&tmp, &path)
+    })();
+    if let Err(e) = res {
+        let _ = fs::remove_file(&tmp);
+        return Err(e.into());
+    }
     Ok(())
 }
 
+pub fn save_registry(reg: &Registry) -> Result<(), CoreError> {
+    if reg.schema_version > REGISTRY_SCHEMA_VERSION {
+        return Err(CoreError::RegistryTooNew {
+            found: reg.schema_version,
+            supported: REGISTRY_SCHEMA_VERSION,
+        });
+    }
+    let _lock = lock_registry()?;
+    write_registry(&Registry {
+        schema_version: REGISTRY_SCHEMA_VERSION,
+        ..reg.clone()
+    })
+}
+
+/// Run a read-modify-write cycle on the registry under the registry lock.
+///
+/// The registry is only written back if `f` succeeds.
+pub fn update_registry<T>(
+    f: impl FnOnce(&mut Registry) -> Result<T, CoreError>,
+) -> Result<T, CoreError> {
+    let _lock = lock_registry()?;
+    let mut reg = load_registry_locked()?;
+    let out = f(&mut reg)?;
+    write_registry(&reg)?;
+    Ok(out)
+}
+
 #[cfg(feature = "testing")]
 pub fn set_data_dir_override_for_testing<P: Into<PathBuf>>(p: P) {
     *DATA_DIR_OVERRIDE.lock().unwrap() = Some(p.into());
@@ -120,20 +443,476 @@ pub fn clear_data_dir_override_for_testing() {
     *DATA_DIR_OVERRIDE.lock().unwrap() = None;
 }
 
+/// Send the stdout of git, compose and tmux to our stderr instead, so a
+/// caller printing a JSON document keeps stdout to itself.
+static CHILD_STDOUT_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+pub fn set_child_stdout_to_stderr(on: bool) {
+    CHILD_STDOUT_TO_STDERR.store(on, Ordering::Relaxed);
+}
+
+/// Stdout for a child process whose output belljar does not read.
+fn child_stdout() -> Stdio {
+    if CHILD_STDOUT_TO_STDERR.load(Ordering::Relaxed) {
+        if let Ok(fd) = std::io::stderr().as_fd().try_clone_to_owned() {
+            return Stdio::from(fd);
+        }
+    }
+    Stdio::inherit()
+}
+
+/// Layered configuration: built-in defaults, the user config, the repo's
+/// `.belljar/config.toml`, `BELLJAR_*` environment variables and CLI
+/// overrides, each overriding the previous layer.
+pub mod config {
+    use super::CoreError;
+    use directories::ProjectDirs;
+    use once_cell::sync::Lazy;
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+    use std::fmt;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::sync::Mutex;
+
+    static CLI_OVERRIDES: Lazy<Mutex<Vec<(String, String)>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+    /// Where a setting's effective value came from.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum Source {
+        Default,
+        User(PathBuf),
+        Repo(PathBuf),
+        Env(&'static str),
+        Cli,
+    }
+
+    impl fmt::Display for Source {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Source::Default => f.write_str("default"),
+                Source::User(p) => write!(f, "user ({})", p.display()),
+                Source::Repo(p) => write!(f, "repo ({})", p.display()),
+                Source::Env(var) => write!(f, "env ({var})"),
+                Source::Cli => f.write_str("cli"),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct Setting<T> {
+        pub value: T,
+        pub source: Source,
+    }
+
+    impl<T> Setting<T> {
+        fn default(value: T) -> Self {
+            Self {
+                value,
+                source: Source::Default,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct Config {
+        /// Base branch for `belljar new`.
+        pub base_branch: Setting<String>,
+        /// Directory holding session worktrees, relative to the repo unless absolute.
+        pub worktree_dir: Setting<PathBuf>,
+        /// Prefix for generated compose project names.
+        pub project_prefix: Setting<String>,
+        /// tmux layout for control-center and workspaces.
+        pub layout: Setting<String>,
+        /// Compose runtime (`auto` or a `runtime::Kind` name).
+        pub runtime: Setting<String>,
+        /// Docker CLI binary.
+        pub docker: Setting<String>,
+        /// First host port handed out to sessions.
+        pub port_base: Setting<u16>,
+        /// Number of host ports reserved per session.
+        pub port_block: Setting<u16>,
+        /// File in the worktree to write the session environment to; empty
+        /// to write none.
+        pub env_file: Setting<String>,
+        /// Default CPU caps, as `[SERVICE=]CPUS` entries separated by commas.
+        pub cpus: Setting<String>,
+        /// Default memory caps, as `[SERVICE=]SIZE` entries separated by commas.
+        pub memory: Setting<String>,
+        /// Image of the throwaway containers that copy volume data.
+        pub helper_image: Setting<String>,
+        /// Docker context new sessions run on; empty for the default engine.
+        pub docker_context: Setting<String>,
+        /// Engine address (`DOCKER_HOST`) new sessions run on; empty for the
+        /// default engine.
+        pub docker_host: Setting<String>,
+        /// Extra session variables (`[env]` tables); values are templates.
+        pub env: BTreeMap<String, Setting<String>>,
+    }
+
+    impl Default for Config {
+        fn default() -> Self {
+            Self {
+                base_branch: Setting::default("main".into()),
+                worktree_dir: Setting::default(PathBuf::from(".belljar/worktrees")),
+                project_prefix: Setting::default("belljar_".into()),
+                layout: Setting::default("tiled".into()),
+                runtime: Setting::default("auto".into()),
+                docker: Setting::default("docker".into()),
+                port_base: Setting::default(20000),
+                port_block: Setting::default(100),
+                env_file: Setting::default(String::new()),
+                cpus: Setting::default(String::new()),
+                memory: Setting::default(String::new()),
+                helper_image: Setting::default("busybox".into()),
+                docker_context: Setting::default(String::new()),
+                docker_host: Setting::default(String::new()),
+                env: BTreeMap::new(),
+            }
+        }
+    }
+
+    /// One layer as read from a config file or assembled from overrides.
+    #[derive(Debug, Default, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Layer {
+        base_branch: Option<String>,
+        worktree_dir: Option<PathBuf>,
+        project_prefix: Option<String>,
+        layout: Option<String>,
+        runtime: Option<String>,
+        docker: Option<String>,
+        port_base: Option<u16>,
+        port_block: Option<u16>,
+        env_file: Option<String>,
+        cpus: Option<String>,
+        memory: Option<String>,
+        helper_image: Option<String>,
+        docker_context: Option<String>,
+        docker_host: Option<String>,
+        #[serde(default)]
+        env: BTreeMap<String, String>,
+    }
+
+    /// Setting keys and the environment variables that override them.
+    pub const KEYS: &[(&str, &str)] = &[
+        ("base_branch", "BELLJAR_BASE_BRANCH"),
+        ("worktree_dir", "BELLJAR_WORKTREE_DIR"),
+        ("project_prefix", "BELLJAR_PROJECT_PREFIX"),
+        ("layout", "BELLJAR_LAYOUT"),
+        ("runtime", "BELLJAR_RUNTIME"),
+        ("docker", "BELLJAR_DOCKER"),
+        ("port_base", "BELLJAR_PORTS_BASE"),
+        ("port_block", "BELLJAR_PORTS_BLOCK"),
+        ("env_file", "BELLJAR_ENV_FILE"),
+        ("cpus", "BELLJAR_CPUS"),
+        ("memory", "BELLJAR_MEMORY"),
+        ("helper_image", "BELLJAR_HELPER_IMAGE"),
+        ("docker_context", "BELLJAR_DOCKER_CONTEXT"),
+        ("docker_host", "BELLJAR_DOCKER_HOST"),
+    ];
+
+    /// Whether `name` is a valid environment variable name.
+    pub fn is_var_name(name: &str) -> bool {
+        let mut chars = name.chars();
+        chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    }
+
+    fn parse_port(key: &str, value: &str) -> Result<u16, CoreError> {
+        value
+            .parse()
+            .map_err(|_| CoreError::Config(format!("{key} must be a port number, got: {value}")))
+    }
+
+    fn check_runtime(value: &str) -> Result<(), String> {
+        if value != "auto" && super::runtime::Kind::parse(value).is_none() {
+            return Err(format!(
+                "runtime must be auto or one of {}, got: {value}",
+                super::runtime::Kind::ALL.map(|k| k.name()).join(", ")
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_limits(key: &str, value: &str) -> Result<(), String> {
+        super::limits::parse_list(key, value)
+            .map(|_| ())
+            .map_err(|e| format!("{key}: {e}"))
+    }
+
+    impl Layer {
+        fn set(&mut self, key: &str, value: &str) -> Result<(), CoreError> {
+            let v = Some(value.to_string());
+            match key {
+                "base_branch" => self.base_branch = v,
+                "worktree_dir" => self.worktree_dir = Some(PathBuf::from(value)),
+                "project_prefix" => self.project_prefix = v,
+                "layout" => self.layout = v,
+                "runtime" => {
+                    check_runtime(value).map_err(CoreError::Config)?;
+                    self.runtime = v;
+                }
+                "docker" => self.docker = v,
+                "port_base" => self.port_base = Some(parse_port(key, value)?),
+                "port_block" => self.port_block = Some(parse_port(key, value)?),
+                "env_file" => self.env_file = v,
+                "helper_image" => self.helper_image = v,
+                "docker_context" => self.docker_context = v,
+                "docker_host" => self.docker_host = v,
+                "cpus" | "memory" => {
+                    check_limits(key, value).map_err(CoreError::Config)?;
+                    if key == "cpus" {
+                        self.cpus = v;
+                    } else {
+                        self.memory = v;
+                    }
+                }
+                _ => match key.strip_prefix("env.") {
+                    Some(var) if is_var_name(var) => {
+                        self.env.insert(var.to_string(), value.to_string());
+                    }
+                    _ => return Err(CoreError::Config(format!("unknown config key: {key}"))),
+                },
+            }
+            Ok(())
+        }
+
+        fn read(path: &Path) -> Result<Option<Layer>, CoreError> {
+            if !path.exists() {
+                return Ok(None);
+            }
+            let text = fs::read_to_string(path)?;
+            let layer: Layer = toml::from_str(&text)
+                .map_err(|e| CoreError::Config(format!("{}: {e}", path.display())))?;
+            if let Some(bad) = layer.env.keys().find(|k| !is_var_name(k)) {
+                return Err(CoreError::Config(format!(
+                    "{}: invalid env variable name: {bad}",
+                    path.display()
+                )));
+            }
+            // The same checks `set` makes; port numbers are typed already
+            let checked = layer
+                .runtime
+                .as_deref()
+                .map_or(Ok(()), check_runtime)
+                .and(
+                    layer
+                        .cpus
+                        .as_deref()
+                        .map_or(Ok(()), |v| check_limits("cpus", v)),
+                )
+                .and(
+                    layer
+                        .memory
+                        .as_deref()
+                        .map_or(Ok(()), |v| check_limits("memory", v)),
+                );
+            checked.map_err(|e| CoreError::Config(format!("{}: {e}", path.display())))?;
+            Ok(Some(layer))
+        }
+    }
+
+    impl Config {
+        fn apply(&mut self, layer: Layer, source: Source) {
+            fn put<T>(slot: &mut Setting<T>, v: Option<T>, source: &Source) {
+                if let Some(value) = v {
+                    *slot = Setting {
+                        value,
+                        source: source.clone(),
+                    };
+                }
+            }
+            put(&mut self.base_branch, layer.base_branch, &source);
+            put(&mut self.worktree_dir, layer.worktree_dir, &source);
+            put(&mut self.project_prefix, layer.project_prefix, &source);
+            put(&mut self.layout, layer.layout, &source);
+            put(&mut self.runtime, layer.runtime, &source);
+            put(&mut self.docker, layer.docker, &source);
+            put(&mut self.port_base, layer.port_base, &source);
+            put(&mut self.port_block, layer.port_block, &source);
+            put(&mut self.env_file, layer.env_file, &source);
+            put(&mut self.cpus, layer.cpus, &source);
+            put(&mut self.memory, layer.memory, &source);
+            put(&mut self.helper_image, layer.helper_image, &source);
+            put(&mut self.docker_context, layer.docker_context, &source);
+            put(&mut self.docker_host, layer.docker_host, &source);
+            for (key, value) in layer.env {
+                self.env.insert(
+                    key,
+                    Setting {
+                        value,
+                        source: source.clone(),
+                    },
+                );
+            }
+        }
+
+        /// Effective `(key, value, source)` triples in `KEYS` order, then
+        /// `env.<NAME>` for each session variable.
+        pub fn entries(&self) -> Vec<(String, String, &Source)> {
+            let fixed = [
+                (
+                    "base_branch",
+                    self.base_branch.value.clone(),
+                    &self.base_branch.source,
+                ),
+                (
+                    "worktree_dir",
+                    self.worktree_dir.value.display().to_string(),
+                    &self.worktree_dir.source,
+                ),
+                (
+                    "project_prefix",
+                    self.project_prefix.value.clone(),
+                    &self.project_prefix.source,
+                ),
+                ("layout", self.layout.value.clone(), &self.layout.source),
+                ("runtime", self.runtime.value.clone(), &self.runtime.source),
+                ("docker", self.docker.value.clone(), &self.docker.source),
+                (
+                    "port_base",
+                    self.port_base.value.to_string(),
+                    &self.port_base.source,
+                ),
+                (
+                    "port_block",
+                    self.port_block.value.to_string(),
+                    &self.port_block.source,
+                ),
+                (
+                    "env_file",
+                    self.env_file.value.clone(),
+                    &self.env_file.source,
+                ),
+                ("cpus", self.cpus.value.clone(), &self.cpus.source),
+                ("memory", self.memory.value.clone(), &self.memory.source),
+                (
+                    "helper_image",
+                    self.helper_image.value.clone(),
+                    &self.helper_image.source,
+                ),
+                (
+                    "docker_context",
+                    self.docker_context.value.clone(),
+                    &self.docker_context.source,
+                ),
+                (
+                    "docker_host",
+                    self.docker_host.value.clone(),
+                    &self.docker_host.source,
+                ),
+            ];
+            fixed
+                .into_iter()
+                .map(|(key, value, source)| (key.to_string(), value, source))
+                .chain(
+                    self.env
+                        .iter()
+                        .map(|(k, v)| (format!("env.{k}"), v.value.clone(), &v.source)),
+                )
+                .collect()
+        }
+
+        /// Directory that holds the worktrees for sessions of `repo`.
+        pub fn worktree_root(&self, repo: &Path) -> PathBuf {
+            repo.join(&self.worktree_dir.value)
+        }
+    }
+
+    /// Path of the user config file (`BELLJAR_CONFIG_DIR` overrides the
+    /// platform config dir).
+    pub fn user_config_path() -> Option<PathBuf> {
+        if let Ok(dir) = std::env::var("BELLJAR_CONFIG_DIR") {
+            if !dir.is_empty() {
+                return Some(PathBuf::from(dir).join("config.toml"));
+            }
+        }
+        ProjectDirs::from("dev", "belljar", "belljar").map(|d| d.config_dir().join("config.toml"))
+    }
+
+    pub fn repo_config_path(repo: &Path) -> PathBuf {
+        repo.join(".belljar").join("config.toml")
+    }
+
+    /// Record `key=value` overrides given on the command line; they apply to
+    /// every subsequent `load` in this process.
+    pub fn set_cli_overrides(overrides: &[String]) -> Result<(), CoreError> {
+        let mut parsed = Vec::new();
+        let mut check = Layer::default();
+        for o in overrides {
+            let (k, v) = o
+                .split_once('=')
+                .ok_or_else(|| CoreError::Config(format!("expected KEY=VALUE, got: {o}")))?;
+            check.set(k.trim(), v.trim())?;
+            parsed.push((k.trim().to_string(), v.trim().to_string()));
+        }
+        *CLI_OVERRIDES.lock().unwrap() = parsed;
+        Ok(())
+    }
+
+    /// Build the effective configuration, including `repo`'s config file
+    /// when a repository is given.
+    pub fn load(repo: Option<&Path>) -> Result<Config, CoreError> {
+        let mut cfg = Config::default();
+        if let Some(path) = user_config_path() {
+            if let Some(layer) = Layer::read(&path)? {
+                cfg.apply(layer, Source::User(path));
+            }
+        }
+        if let Some(repo) = repo {
+            let path = repo_config_path(repo);
+            if let Some(layer) = Layer::read(&path)? {
+                cfg.apply(layer, Source::Repo(path));
+            }
+        }
+        for (key, var) in KEYS {
+            if let Ok(v) = std::env::var(var) {
+                if !v.is_empty() {
+                    let mut layer = Layer::default();
+                    layer.set(key, &v)?;
+                    cfg.apply(layer, Source::Env(var));
+                }
+            }
+        }
+        let mut layer = Layer::default();
+        for (k, v) in CLI_OVERRIDES.lock().unwrap().iter() {
+            layer.set(k, v)?;
+        }
+        cfg.apply(layer, Source::Cli);
+        Ok(cfg)
+    }
+}
+
 pub fn create_session(
     label: &str,
     repo_path: &Path,
     branch: Option<String>,
     services: Vec<String>,
 ) -> Result<Session, CoreError> {
-    let mut reg = load_registry()?;
+    let session = new_session(label, repo_path, branch, services)?;
+    insert_session(&session)?;
+    Ok(session)
+}
+
+/// Build a session record with a fresh id and the default project and tmux
+/// names, without persisting it.
+fn new_session(
+    label: &str,
+    repo_path: &Path,
+    branch: Option<String>,
+    services: Vec<String>,
+) -> Result<Session, CoreError> {
     let id = Uuid::new_v4().to_string();
-    let compose_project = format!("belljar_{}", &id[..8]);
+    let prefix = config::load(Some(repo_path))?.project_prefix.value;
+    let compose_project = format!("{prefix}{}", &id[..8]);
     let tmux_session = label.to_string();
     let created_at = OffsetDateTime::now_utc()
         .format(&time::format_description::well_known::Rfc3339)
         .unwrap_or_default();
-    let session = Session {
+    Ok(Session {
         id,
         label: label.to_string(),
         repo_path: repo_path.to_path_buf(),
@@ -143,10 +922,29 @@ pub fn create_session(
         services,
         tmux_session,
         created_at,
-    };
-    reg.sessions.push(session.clone());
-    save_registry(&reg)?;
-    Ok(session)
+        ports: Vec::new(),
+        stopped: false,
+        compose_files: Vec::new(),
+        stack: None,
+        profiles: Vec::new(),
+        limits: Vec::new(),
+        template: false,
+        docker_context: None,
+        docker_host: None,
+    })
+}
+
+fn insert_session(session: &Session) -> Result<(), CoreError> {
+    update_registry(|reg| {
+        if reg.sessions.iter().any(|s| s.label == session.label) {
+            return Err(CoreError::LabelConflict {
+                kind: "session",
+                label: session.label.clone(),
+            });
+        }
+        reg.sessions.push(session.clone());
+        Ok(())
+    })
 }
 
 pub mod git {
@@ -159,6 +957,21 @@ pub mod git {
         "git"
     }
 
+    /// Output of `git --version`.
+    pub fn version() -> Result<String, CoreError> {
+        let out = Command::new(git())
+            .arg("--version")
+            .output()
+            .map_err(|e| CoreError::Git(e.to_string()))?;
+        if !out.status.success() {
+            return Err(CoreError::Git(format!(
+                "git --version failed with status {}",
+                out.status
+            )));
+        }
+        Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+    }
+
     pub fn is_git_repo(path: &Path) -> bool {
         Command::new(git())
             .arg("-C")
@@ -170,12 +983,50 @@ pub mod git {
             .unwrap_or(false)
     }
 
+    /// Add `file` (relative to the top of `dir`'s checkout) to the
+    /// repository's `info/exclude`, so it never shows as an untracked change.
+    pub fn exclude(dir: &Path, file: &Path) -> Result<(), CoreError> {
+        let out = Command::new(git())
+            .arg("-C")
+            .arg(dir)
+            .args(["rev-parse", "--git-path", "info/exclude"])
+            .output()
+            .map_err(|e| CoreError::Git(e.to_string()))?;
+        if !out.status.success() {
+            return Err(CoreError::Git("rev-parse --git-path failed".into()));
+        }
+        // Relative to `dir` unless git printed an absolute path
+        let path = dir.join(String::from_utf8_lossy(&out.stdout).trim());
+        let pattern = format!("/{}", file.display());
+        let mut text = fs::read_to_string(&path).unwrap_or_default();
+        if text.lines().any(|l| l == pattern) {
+            return Ok(());
+        }
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text.push_str(&pattern);
+        text.push('\n');
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, text)?;
+        Ok(())
+    }
+
+    /// Location of the worktree belljar manages for `label` inside `repo`.
+    pub fn worktree_dir(repo: &Path, label: &str) -> Result<PathBuf, CoreError> {
+        Ok(super::config::load(Some(repo))?
+            .worktree_root(repo)
+            .join(label))
+    }
+
     pub fn ensure_worktree(
         repo: &Path,
         label: &str,
         branch: &Option<String>,
     ) -> Result<PathBuf, CoreError> {
-        let wt_dir = repo.join(".belljar").join("worktrees").join(label);
+        let wt_dir = worktree_dir(repo, label)?;
         if let Some(parent) = wt_dir.parent() {
             fs::create_dir_all(parent)?;
         }
@@ -185,8 +1036,9 @@ pub mod git {
                 .args(["-C"])
                 .arg(repo)
                 .args(args)
+                .stdout(super::child_stdout())
                 .status()
-                .map_err(|e| CoreError::Compose(format!("git failed: {e}")))?;
+                .map_err(|e| CoreError::Git(e.to_string()))?;
             Ok(st.success())
         };
 
@@ -207,12 +1059,39 @@ pub mod git {
             None => run(&["worktree", "add", wt_dir.to_str().unwrap()]).unwrap_or(false),
         };
 
-        if !ok && !wt_dir.exists() {
-            return Err(CoreError::Compose("git worktree add failed".into()));
+        if !ok {
+            check_existing(repo, &wt_dir, branch.as_deref())?;
         }
         Ok(wt_dir)
     }
 
+    /// After `worktree add` failed, accept `wt_dir` only if it already is a
+    /// worktree of `repo` on `branch` (on any branch when `None`).
+    fn check_existing(repo: &Path, wt_dir: &Path, branch: Option<&str>) -> Result<(), CoreError> {
+        if !wt_dir.exists() {
+            return Err(CoreError::Git("worktree add failed".into()));
+        }
+        let wanted = wt_dir.canonicalize()?;
+        let entry = list_worktrees(repo)?
+            .into_iter()
+            .find(|w| w.path.canonicalize().is_ok_and(|p| p == wanted))
+            .ok_or_else(|| {
+                CoreError::Git(format!(
+                    "{} exists but is not a worktree of {}",
+                    wt_dir.display(),
+                    repo.display()
+                ))
+            })?;
+        match (branch, entry.branch.as_deref()) {
+            (Some(want), have) if have != Some(want) => Err(CoreError::Git(format!(
+                "worktree {} is on {}, not {want}",
+                wt_dir.display(),
+                have.unwrap_or("a detached HEAD")
+            ))),
+            _ => Ok(()),
+        }
+    }
+
     /// Create a worktree with a new branch based on a specific base ref.
     ///
     /// If the new branch already exists, this falls back to adding a worktree
@@ -223,7 +1102,7 @@ pub mod git {
         new_branch: &str,
         base: &str,
     ) -> Result<PathBuf, CoreError> {
-        let wt_dir = repo.join(".belljar").join("worktrees").join(label);
+        let wt_dir = worktree_dir(repo, label)?;
         if let Some(parent) = wt_dir.parent() {
             fs::create_dir_all(parent)?;
         }
@@ -233,8 +1112,9 @@ pub mod git {
                 .args(["-C"])
                 .arg(repo)
                 .args(args)
+                .stdout(super::child_stdout())
                 .status()
-                .map_err(|e| CoreError::Compose(format!("git failed: {e}")))?;
+                .map_err(|e| CoreError::Git(e.to_string()))?;
             Ok(st.success())
         };
 
@@ -249,72 +1129,2509 @@ pub mod git {
         .unwrap_or(false)
             || run(&["worktree", "add", wt_dir.to_str().unwrap(), new_branch]).unwrap_or(false);
 
-        if !ok && !wt_dir.exists() {
-            return Err(CoreError::Compose("git worktree add failed".into()));
+        if !ok {
+            check_existing(repo, &wt_dir, Some(new_branch))?;
         }
         Ok(wt_dir)
     }
 
-    pub fn set_session_worktree(session: &mut Session, path: PathBuf) -> Result<(), CoreError> {
-        session.worktree_path = Some(path);
-        // Persist the change
-        let mut reg = super::load_registry()?;
-        if let Some(s) = reg.sessions.iter_mut().find(|s| s.id == session.id) {
-            s.worktree_path = session.worktree_path.clone();
+    pub fn branch_exists(repo: &Path, branch: &str) -> bool {
+        Command::new(git())
+            .arg("-C")
+            .arg(repo)
+            .args(["rev-parse", "--verify", "--quiet"])
+            .arg(format!("refs/heads/{branch}"))
+            .output()
+            .map(|o| o.status.success())
+            .unwrap_or(false)
+    }
+
+    pub fn remove_worktree(repo: &Path, path: &Path, force: bool) -> Result<(), CoreError> {
+        let mut cmd = Command::new(git());
+        cmd.arg("-C").arg(repo).args(["worktree", "remove"]);
+        if force {
+            cmd.arg("--force");
+        }
+        let st = cmd
+            .arg(path)
+            .stdout(super::child_stdout())
+            .status()
+            .map_err(|e| CoreError::Git(e.to_string()))?;
+        if !st.success() {
+            return Err(CoreError::Git("worktree remove failed".into()));
         }
-        super::save_registry(&reg)?;
         Ok(())
     }
-}
 
-pub fn remove_session(label_or_id: &str) -> Result<Option<Session>, CoreError> {
-    let mut reg = load_registry()?;
-    if let Some(idx) = reg
-        .sessions
-        .iter()
-        .position(|s| s.label == label_or_id || s.id == label_or_id)
-    {
-        let s = reg.sessions.remove(idx);
-        save_registry(&reg)?;
-        return Ok(Some(s));
+    pub fn delete_branch(repo: &Path, branch: &str, force: bool) -> Result<(), CoreError> {
+        let st = Command::new(git())
+            .arg("-C")
+            .arg(repo)
+            .args(["branch", if force { "-D" } else { "-d" }, branch])
+            .stdout(super::child_stdout())
+            .status()
+            .map_err(|e| CoreError::Git(e.to_string()))?;
+        if !st.success() {
+            return Err(CoreError::Git("branch delete failed".into()));
+        }
+        Ok(())
     }
-    Ok(None)
-}
 
-pub fn find_session(label_or_id: &str) -> Result<Option<Session>, CoreError> {
-    let reg = load_registry()?;
-    Ok(reg
-        .sessions
-        .into_iter()
-        .find(|s| s.label == label_or_id || s.id == label_or_id))
-}
+    /// Checked-out branch of the worktree at `path`, `None` when detached.
+    pub fn current_branch(path: &Path) -> Option<String> {
+        let out = Command::new(git())
+            .arg("-C")
+            .arg(path)
+            .args(["symbolic-ref", "--quiet", "--short", "HEAD"])
+            .output()
+            .ok()?;
+        if !out.status.success() {
+            return None;
+        }
+        Some(String::from_utf8_lossy(&out.stdout).trim().to_string())
+    }
 
-pub mod compose {
-    use super::{CoreError, Session};
-    use std::fs;
-    use std::path::{Path, PathBuf};
-    use std::process::Command;
+    /// Whether the worktree at `path` has uncommitted or untracked changes.
+    pub fn is_dirty(path: &Path) -> Result<bool, CoreError> {
+        let out = Command::new(git())
+            .arg("-C")
+            .arg(path)
+            .args(["status", "--porcelain"])
+            .output()
+            .map_err(|e| CoreError::Git(e.to_string()))?;
+        if !out.status.success() {
+            return Err(CoreError::Git("status failed".into()));
+        }
+        Ok(!out.stdout.is_empty())
+    }
 
-    fn discover_files(repo_path: &Path) -> Result<Vec<PathBuf>, CoreError> {
+    /// Number of commits on `path`'s HEAD that no other branch or remote
+    /// contains, i.e. work that would be lost with the worktree and branch.
+    pub fn unpushed_commits(path: &Path) -> Result<usize, CoreError> {
+        let mut cmd = Command::new(git());
+        cmd.arg("-C")
+            .arg(path)
+            .args(["rev-list", "--count", "HEAD", "--not"]);
+        if let Some(br) = current_branch(path) {
+            // Patterns are relative to refs/heads/ for --branches
+            cmd.arg(format!("--exclude={br}"));
+        }
+        let out = cmd
+            .args(["--branches", "--remotes"])
+            .output()
+            .map_err(|e| CoreError::Git(e.to_string()))?;
+        if !out.status.success() {
+            return Err(CoreError::Git("rev-list failed".into()));
+        }
+        Ok(String::from_utf8_lossy(&out.stdout)
+            .trim()
+            .parse()
+            .unwrap_or(0))
+    }
+
+    /// An entry from `git worktree list --porcelain`.
+    #[derive(Debug, Clone)]
+    pub struct WorktreeEntry {
+        pub path: PathBuf,
+        /// Short branch name, or `None` for a detached HEAD.
+        pub branch: Option<String>,
+    }
+
+    pub fn list_worktrees(repo: &Path) -> Result<Vec<WorktreeEntry>, CoreError> {
+        let out = Command::new(git())
+            .arg("-C")
+            .arg(repo)
+            .args(["worktree", "list", "--porcelain"])
+            .output()
+            .map_err(|e| CoreError::Git(e.to_string()))?;
+        if !out.status.success() {
+            return Err(CoreError::Git("worktree list failed".into()));
+        }
+        let mut entries = Vec::new();
+        for line in String::from_utf8_lossy(&out.stdout).lines() {
+            if let Some(p) = line.strip_prefix("worktree ") {
+                entries.push(WorktreeEntry {
+                    path: PathBuf::from(p),
+                    branch: None,
+                });
+            } else if let Some(r) = line.strip_prefix("branch ") {
+                if let Some(e) = entries.last_mut() {
+                    e.branch = Some(r.trim_start_matches("refs/heads/").to_string());
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Drop git's metadata for worktrees whose directories no longer exist.
+    pub fn prune_worktrees(repo: &Path) -> Result<(), CoreError> {
+        let st = Command::new(git())
+            .arg("-C")
+            .arg(repo)
+            .args(["worktree", "prune"])
+            .stdout(super::child_stdout())
+            .status()
+            .map_err(|e| CoreError::Git(e.to_string()))?;
+        if !st.success() {
+            return Err(CoreError::Git("worktree prune failed".into()));
+        }
+        Ok(())
+    }
+
+    pub fn set_session_worktree(session: &mut Session, path: PathBuf) -> Result<(), CoreError> {
+        session.worktree_path = Some(path);
+        // Persist the change
+        super::update_registry(|reg| {
+            if let Some(s) = reg.sessions.iter_mut().find(|s| s.id == session.id) {
+                s.worktree_path = session.worktree_path.clone();
+            }
+            Ok(())
+        })
+    }
+}
+
+pub fn remove_session(label_or_id: &str) -> Result<Option<Session>, CoreError> {
+    let removed = update_registry(|reg| {
+        Ok(reg
+            .sessions
+            .iter()
+            .position(|s| s.label == label_or_id || s.id == label_or_id)
+            .map(|idx| reg.sessions.remove(idx)))
+    })?;
+    if let Some(s) = &removed {
+        ports::remove_override(s);
+        limits::remove_override(s);
+    }
+    Ok(removed)
+}
+
+pub fn find_session(label_or_id: &str) -> Result<Option<Session>, CoreError> {
+    let reg = load_registry()?;
+    Ok(reg
+        .sessions
+        .into_iter()
+        .find(|s| s.label == label_or_id || s.id == label_or_id))
+}
+
+/// Mark or unmark a session as a template whose volumes new sessions may
+/// copy.
+pub fn set_template(label_or_id: &str, template: bool) -> Result<Session, CoreError> {
+    update_registry(|reg| {
+        let s = reg
+            .sessions
+            .iter_mut()
+            .find(|s| s.label == label_or_id || s.id == label_or_id)
+            .ok_or_else(|| CoreError::NotFound {
+                kind: "session",
+                label: label_or_id.to_string(),
+            })?;
+        s.template = template;
+        Ok(s.clone())
+    })
+}
+
+/// Staged session provisioning: registry, worktree, compose, tmux.
+pub mod lifecycle {
+    use super::{compose, git, runtime, tmux, volumes, CoreError, Session};
+    use std::fmt;
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Step {
+        Worktree,
+        /// Limits, compose files, overrides and the env file.
+        Setup,
+        Volumes,
+        Compose,
+        Ready,
+        Tmux,
+    }
+
+    impl fmt::Display for Step {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(match self {
+                Step::Worktree => "worktree setup",
+                Step::Setup => "session setup",
+                Step::Volumes => "copying volumes",
+                Step::Compose => "compose up",
+                Step::Ready => "waiting for services",
+                Step::Tmux => "tmux session",
+            })
+        }
+    }
+
+    /// How the session's worktree is prepared.
+    #[derive(Debug, Clone)]
+    pub enum Worktree {
+        /// No worktree (e.g. the repo path is not a git repository).
+        Skip,
+        /// Check out `Session.branch` (creating it from HEAD if needed).
+        Checkout,
+        /// Create a new branch named `Session.branch` from `base`.
+        FromBase(String),
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct Plan {
+        pub label: String,
+        pub repo_path: PathBuf,
+        pub branch: Option<String>,
+        pub services: Vec<String>,
+        /// Stack under `.belljar/compose/` to take compose files from.
+        pub stack: Option<String>,
+        /// Compose profiles to enable.
+        pub profiles: Vec<String>,
+        /// Resource caps given on the command line, on top of the repo's
+        /// `cpus`/`memory` settings.
+        pub limits: Vec<super::limits::Limit>,
+        /// Template session (or `<label>@<snapshot>`) whose compose volumes
+        /// are copied before `up`.
+        pub volumes_from: Option<String>,
+        /// Engine to run the containers on; `None` takes the repo's
+        /// `docker_context`/`docker_host` settings.
+        pub target: Option<runtime::Target>,
+        pub worktree: Worktree,
+        /// After `up`, wait up to this long for the services to be ready.
+        pub wait: Option<Duration>,
+        /// Create a detached tmux session once everything else is up.
+        pub tmux: bool,
+        /// Leave partially created state in place when a step fails.
+        pub keep_on_failure: bool,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct Provisioned {
+        pub session: Session,
+        /// False when the repo has no compose files and `up` was skipped.
+        pub compose_up: bool,
+        /// False when tmux was not requested or is not installed.
+        pub tmux: bool,
+        /// Volumes filled from `Plan.volumes_from`, by compose volume key.
+        pub copied_volumes: Vec<String>,
+    }
+
+    /// Undo log for the steps that have completed so far.
+    #[derive(Default)]
+    struct Undo {
+        session_id: Option<String>,
+        worktree: Option<PathBuf>,
+        branch: Option<String>,
+        compose: Option<Session>,
+        volumes: Vec<String>,
+        tmux: Option<String>,
+    }
+
+    impl Undo {
+        fn rollback(self, repo: &Path) {
+            if let Some(name) = self.tmux {
+                let _ = tmux::kill_session(&name);
+            }
+            if let Some(s) = self.compose {
+                let _ = compose::down(&s);
+                // Copies of volumes the session's files no longer declare
+                super::volumes::remove(&s, &self.volumes);
+            }
+            if let Some(wt) = self.worktree {
+                let _ = git::remove_worktree(repo, &wt, true);
+            }
+            if let Some(br) = self.branch {
+                let _ = git::delete_branch(repo, &br, true);
+            }
+            if let Some(id) = self.session_id {
+                let _ = super::remove_session(&id);
+            }
+        }
+    }
+
+    /// What `teardown` removes besides the registry entry and compose project.
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct Teardown {
+        /// Remove even if the worktree has uncommitted or unpushed work.
+        pub force: bool,
+        /// Run `git worktree remove` on the session's worktree.
+        pub remove_worktree: bool,
+        /// Delete the worktree's branch after removing it.
+        pub delete_branch: bool,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum TeardownStep {
+        TmuxKill,
+        ComposeDown,
+        WorktreeRemove,
+        BranchDelete,
+    }
+
+    impl fmt::Display for TeardownStep {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(match self {
+                TeardownStep::TmuxKill => "tmux kill",
+                TeardownStep::ComposeDown => "compose down",
+                TeardownStep::WorktreeRemove => "worktree remove",
+                TeardownStep::BranchDelete => "branch delete",
+            })
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Removed {
+        pub session: Session,
+        /// Best-effort steps that failed; the registry entry is gone regardless.
+        pub failures: Vec<(TeardownStep, CoreError)>,
+    }
+
+    /// Refuse to discard a worktree holding uncommitted or unpushed work.
+    fn check_removable(session: &Session) -> Result<(), CoreError> {
+        let Some(wt) = session.worktree_path.as_deref().filter(|p| p.exists()) else {
+            return Ok(());
+        };
+        let refuse = |reason: String| CoreError::UnsafeRemoval {
+            label: session.label.clone(),
+            reason,
+        };
+        if git::is_dirty(wt)? {
+            return Err(refuse(format!(
+                "worktree {} has uncommitted changes",
+                wt.display()
+            )));
+        }
+        let n = git::unpushed_commits(wt)?;
+        if n > 0 {
+            return Err(refuse(format!(
+                "worktree {} has {n} unpushed commit(s)",
+                wt.display()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Tear a session down: kill its tmux session, bring its compose project
+    /// down, optionally remove its worktree and branch, and drop it from the
+    /// registry. Fails without touching anything when the worktree would be
+    /// removed with unsaved work and `opts.force` is not set.
+    pub fn teardown(session: &Session, opts: Teardown) -> Result<Removed, CoreError> {
+        if opts.remove_worktree && !opts.force {
+            check_removable(session)?;
+        }
+        let mut failures = Vec::new();
+
+        if let Ok(true) = tmux::has_session(&session.tmux_session) {
+            if let Err(e) = tmux::kill_session(&session.tmux_session) {
+                failures.push((TeardownStep::TmuxKill, e));
+            }
+        }
+        match compose::down(session) {
+            Ok(()) | Err(CoreError::NoComposeFiles) => {}
+            Err(e) => failures.push((TeardownStep::ComposeDown, e)),
+        }
+        if opts.remove_worktree {
+            if let Some(wt) = &session.worktree_path {
+                let branch = git::current_branch(wt);
+                let res = if wt.exists() {
+                    git::remove_worktree(&session.repo_path, wt, opts.force)
+                } else {
+                    git::prune_worktrees(&session.repo_path)
+                };
+                match res {
+                    Err(e) => failures.push((TeardownStep::WorktreeRemove, e)),
+                    Ok(()) if opts.delete_branch => {
+                        if let Some(br) = branch.or_else(|| session.branch.clone()) {
+                            // Unsaved work was ruled out above (or --force given)
+                            if let Err(e) = git::delete_branch(&session.repo_path, &br, true) {
+                                failures.push((TeardownStep::BranchDelete, e));
+                            }
+                        }
+                    }
+                    Ok(()) => {}
+                }
+            }
+        }
+
+        super::remove_session(&session.id)?;
+        Ok(Removed {
+            session: session.clone(),
+            failures,
+        })
+    }
+
+    /// What `stop` does with the session's tmux session.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum StopTmux {
+        /// Leave it running.
+        #[default]
+        Keep,
+        /// Detach attached clients but keep the shells.
+        Detach,
+        /// Kill it; `open` recreates it later.
+        Kill,
+    }
+
+    fn set_stopped(session: &Session, stopped: bool) -> Result<Session, CoreError> {
+        super::update_registry(|reg| {
+            let s = reg
+                .sessions
+                .iter_mut()
+                .find(|s| s.id == session.id)
+                .ok_or_else(|| CoreError::NotFound {
+                    kind: "session",
+                    label: session.label.clone(),
+                })?;
+            s.stopped = stopped;
+            Ok(s.clone())
+        })
+    }
+
+    /// Stop the session's containers without removing them or their volumes,
+    /// handle its tmux session per `tmux_mode`, and mark it stopped.
+    pub fn stop(session: &Session, tmux_mode: StopTmux) -> Result<Session, CoreError> {
+        if tmux::has_session(&session.tmux_session).unwrap_or(false) {
+            match tmux_mode {
+                StopTmux::Keep => {}
+                StopTmux::Detach => tmux::detach_clients(&session.tmux_session)?,
+                StopTmux::Kill => tmux::kill_session(&session.tmux_session)?,
+            }
+        }
+        match compose::stop(session) {
+            Ok(()) | Err(CoreError::NoComposeFiles) => {}
+            Err(e) => return Err(e),
+        }
+        set_stopped(session, true)
+    }
+
+    /// Bring a stopped session's compose project back up with the volumes it
+    /// had, optionally waiting for its services, and clear the stopped mark.
+    pub fn resume(
+        session: &Session,
+        wait: Option<Duration>,
+        progress: &mut dyn FnMut(&compose::ServiceState),
+    ) -> Result<Session, CoreError> {
+        match compose::up(session) {
+            Ok(()) => {
+                if let Some(timeout) = wait {
+                    compose::wait_ready(session, timeout, progress)?;
+                }
+            }
+            Err(CoreError::NoComposeFiles) => {}
+            Err(e) => return Err(e),
+        }
+        set_stopped(session, false)
+    }
+
+    /// Stop the session's containers, replace its volume data with snapshot
+    /// `name`, and bring it back up unless it is stopped. If they cannot be
+    /// brought back up, the session is marked stopped.
+    pub fn restore(session: &Session, name: &str) -> Result<volumes::Snapshot, CoreError> {
+        let snapshot = volumes::find_snapshot(&session.label, name)?;
+        match compose::stop(session) {
+            Ok(()) | Err(CoreError::NoComposeFiles) => {}
+            Err(e) => return Err(e),
+        }
+        // Bring it back up even when unpacking failed part way
+        let unpacked = volumes::unpack(&snapshot, session, &mut |_| {});
+        if !session.stopped {
+            match compose::up(session) {
+                Ok(()) | Err(CoreError::NoComposeFiles) => {}
+                Err(e) => {
+                    // Its containers are down, so record it as stopped
+                    set_stopped(session, true)?;
+                    return Err(unpacked.err().unwrap_or(e));
+                }
+            }
+        }
+        unpacked?;
+        Ok(snapshot)
+    }
+
+    fn fail(step: Step, e: CoreError) -> CoreError {
+        CoreError::StepFailed {
+            step,
+            source: Box::new(e),
+        }
+    }
+
+    /// Create a session as one staged operation. If a step fails, every
+    /// earlier step is undone (unless `plan.keep_on_failure`) and the error
+    /// names the failing step.
+    pub fn provision(plan: &Plan) -> Result<Provisioned, CoreError> {
+        provision_with_progress(plan, &mut |_| {})
+    }
+
+    /// `provision`, reporting service state changes while `plan.wait` blocks.
+    pub fn provision_with_progress(
+        plan: &Plan,
+        progress: &mut dyn FnMut(&compose::ServiceState),
+    ) -> Result<Provisioned, CoreError> {
+        let mut undo = Undo::default();
+        let res = run(plan, &mut undo, progress);
+        if res.is_err() && !plan.keep_on_failure {
+            undo.rollback(&plan.repo_path);
+        }
+        res
+    }
+
+    fn run(
+        plan: &Plan,
+        undo: &mut Undo,
+        progress: &mut dyn FnMut(&compose::ServiceState),
+    ) -> Result<Provisioned, CoreError> {
+        let repo = plan.repo_path.as_path();
+        let seed = plan
+            .volumes_from
+            .as_deref()
+            .map(super::volumes::seed)
+            .transpose()?;
+        if let Some(super::volumes::Seed::Template(from)) = &seed {
+            if from.compose_files.is_empty() {
+                return Err(CoreError::Config(format!(
+                    "template {} has no compose files, so no volumes to copy",
+                    from.label
+                )));
+            }
+        }
+        let target = match &plan.target {
+            Some(target) => target.clone(),
+            None => runtime::Target::configured(repo)?,
+        };
+        let mut session = super::create_session(
+            &plan.label,
+            repo,
+            plan.branch.clone(),
+            plan.services.clone(),
+        )?;
+        undo.session_id = Some(session.id.clone());
+
+        let wt_dir = git::worktree_dir(repo, &plan.label).map_err(|e| fail(Step::Worktree, e))?;
+        let existed = wt_dir.exists();
+        // Without an explicit branch git names the new branch after the label
+        let branch = plan.branch.clone().unwrap_or_else(|| plan.label.clone());
+        let branch_existed = git::branch_exists(repo, &branch);
+        let wt = match &plan.worktree {
+            Worktree::Skip => None,
+            Worktree::Checkout => Some(git::ensure_worktree(repo, &plan.label, &plan.branch)),
+            Worktree::FromBase(base) => {
+                Some(git::ensure_worktree_from(repo, &plan.label, &branch, base))
+            }
+        };
+        if let Some(wt) = wt {
+            if !existed && wt_dir.exists() {
+                undo.worktree = Some(wt_dir.clone());
+            }
+            if !branch_existed && git::branch_exists(repo, &branch) {
+                undo.branch = Some(branch);
+            }
+            let wt = wt.map_err(|e| fail(Step::Worktree, e))?;
+            git::set_session_worktree(&mut session, wt).map_err(|e| fail(Step::Worktree, e))?;
+        }
+
+        // Compose files come from the worktree, which may be on a branch
+        // that changes them
+        session.stack = plan.stack.clone();
+        session.profiles = plan.profiles.clone();
+        session.limits =
+            super::limits::resolve(repo, &plan.limits).map_err(|e| fail(Step::Setup, e))?;
+        target.record(&mut session);
+        compose::record_files(&mut session).map_err(|e| fail(Step::Setup, e))?;
+        if !plan.services.is_empty() {
+            compose::check_services(&session, &plan.services).map_err(|e| fail(Step::Setup, e))?;
+        }
+        super::limits::write_override(&session).map_err(|e| fail(Step::Setup, e))?;
+        let published = compose::published_ports(&session).map_err(|e| fail(Step::Compose, e))?;
+        if !published.is_empty() {
+            super::ports::assign(&mut session, &published).map_err(|e| fail(Step::Compose, e))?;
+        }
+        super::env::write_file(&session).map_err(|e| fail(Step::Setup, e))?;
+
+        // Register the teardown before `up` so a half-started project is cleaned too
+        undo.compose = Some(session.clone());
+        let mut copied_volumes = Vec::new();
+        if let Some(seed) = &seed {
+            if session.compose_files.is_empty() {
+                return Err(fail(
+                    Step::Volumes,
+                    CoreError::Config(format!(
+                        "session {} has no compose files to seed volumes for",
+                        session.label
+                    )),
+                ));
+            }
+            copied_volumes =
+                super::volumes::fill(seed, &session, &mut |v| undo.volumes.push(v.to_string()))
+                    .map_err(|e| fail(Step::Volumes, e))?;
+        }
+        let compose_up = match compose::up(&session) {
+            Ok(()) => true,
+            Err(CoreError::NoComposeFiles) => {
+                undo.compose = None;
+                false
+            }
+            Err(e) => return Err(fail(Step::Compose, e)),
+        };
+        if let (true, Some(timeout)) = (compose_up, plan.wait) {
+            compose::wait_ready(&session, timeout, progress).map_err(|e| fail(Step::Ready, e))?;
+        }
+
+        let mut tmux_up = false;
+        if plan.tmux {
+            let existed = tmux::has_session(&session.tmux_session).unwrap_or(false);
+            match tmux::ensure_session(&session) {
+                Ok(()) => {
+                    if !existed {
+                        undo.tmux = Some(session.tmux_session.clone());
+                    }
+                    tmux_up = true;
+                }
+                Err(CoreError::TmuxNotFound) => {}
+                Err(e) => return Err(fail(Step::Tmux, e)),
+            }
+        }
+
+        Ok(Provisioned {
+            session,
+            compose_up,
+            tmux: tmux_up,
+            copied_volumes,
+        })
+    }
+}
+
+/// Import pre-existing worktrees, tmux sessions and compose projects as
+/// sessions without creating anything new.
+pub mod adopt {
+    use super::runtime::Target;
+    use super::{compose, git, tmux, CoreError, Session};
+    use serde::Serialize;
+    use std::path::{Path, PathBuf};
+
+    #[derive(Debug, Clone, Serialize)]
+    pub struct Candidate {
+        pub label: String,
+        pub worktree_path: PathBuf,
+        pub branch: Option<String>,
+        /// Running tmux session named after the label or branch.
+        pub tmux_session: Option<String>,
+        /// Compose project whose config files live in the worktree.
+        pub compose_project: Option<String>,
+    }
+
+    fn label_for(path: &Path, branch: Option<&str>) -> String {
+        path.file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .or_else(|| branch.map(str::to_string))
+            .unwrap_or_else(|| path.display().to_string())
+    }
+
+    /// Find worktrees of `repo` (other than the main checkout) that no session
+    /// points at yet, optionally matching them to tmux sessions and compose
+    /// projects.
+    pub fn scan(repo: &Path, tmux: bool, compose: bool) -> Result<Vec<Candidate>, CoreError> {
+        let reg = super::load_registry()?;
+        let known = |p: &Path| {
+            let p = p.canonicalize().unwrap_or_else(|_| p.to_path_buf());
+            reg.sessions.iter().any(|s| {
+                s.worktree_path
+                    .as_ref()
+                    .map(|w| w.canonicalize().unwrap_or_else(|_| w.clone()) == p)
+                    .unwrap_or(false)
+            })
+        };
+        let tmux_sessions = if tmux {
+            tmux::list_sessions()?
+        } else {
+            Vec::new()
+        };
+        let projects = if compose {
+            compose::list_projects(Some(repo), &Target::Local)?
+        } else {
+            Vec::new()
+        };
+
+        let mut out = Vec::new();
+        // The first entry is always the main worktree
+        for wt in git::list_worktrees(repo)?.into_iter().skip(1) {
+            if known(&wt.path) {
+                continue;
+            }
+            let label = label_for(&wt.path, wt.branch.as_deref());
+            let tmux_session = tmux_sessions
+                .iter()
+                .find(|n| **n == label || Some(n.as_str()) == wt.branch.as_deref())
+                .cloned();
+            let compose_project = projects
+                .iter()
+                .filter(|p| !reg.sessions.iter().any(|s| s.compose_project == p.name))
+                .find(|p| p.config_files.iter().any(|f| f.starts_with(&wt.path)))
+                .map(|p| p.name.clone());
+            out.push(Candidate {
+                label,
+                worktree_path: wt.path,
+                branch: wt.branch,
+                tmux_session,
+                compose_project,
+            });
+        }
+        Ok(out)
+    }
+
+    /// Record `c` as a session of `repo`, pointing at what already exists.
+    pub fn adopt(repo: &Path, c: &Candidate) -> Result<Session, CoreError> {
+        let mut session = super::new_session(&c.label, repo, c.branch.clone(), vec![])?;
+        session.worktree_path = Some(c.worktree_path.clone());
+        if let Some(name) = &c.tmux_session {
+            session.tmux_session = name.clone();
+        }
+        if let Some(project) = &c.compose_project {
+            session.compose_project = project.clone();
+        }
+        super::insert_session(&session)?;
+        Ok(session)
+    }
+}
+
+/// Reconcile registry entries against git, tmux and docker.
+pub mod reconcile {
+    use super::runtime::{self, Target};
+    use super::{compose, git, tmux, CoreError, Session, Workspace};
+    use std::collections::{HashMap, HashSet};
+    use std::fmt;
+    use std::path::{Path, PathBuf};
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum Issue {
+        /// The repository (or workspace root) no longer exists.
+        RepoMissing(PathBuf),
+        /// The worktree directory is gone or git no longer tracks it.
+        WorktreeMissing(PathBuf),
+        /// No compose project with the session's name exists.
+        ComposeMissing(String),
+        /// The tmux session is not running; `open` recreates it.
+        TmuxMissing(String),
+    }
+
+    impl fmt::Display for Issue {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Issue::RepoMissing(p) => write!(f, "repository missing: {}", p.display()),
+                Issue::WorktreeMissing(p) => write!(f, "worktree missing: {}", p.display()),
+                Issue::ComposeMissing(p) => write!(f, "compose project not found: {p}"),
+                Issue::TmuxMissing(n) => write!(f, "tmux session not running: {n}"),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Action {
+        /// Remove the registry entry.
+        Prune,
+        /// Bring the compose project back up.
+        Repair,
+        /// Informational only.
+        None,
+    }
+
+    impl fmt::Display for Action {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(match self {
+                Action::Prune => "prune",
+                Action::Repair => "repair",
+                Action::None => "ok",
+            })
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    pub enum Subject {
+        Session(Box<Session>),
+        Workspace(Workspace),
+    }
+
+    impl Subject {
+        pub fn label(&self) -> &str {
+            match self {
+                Subject::Session(s) => &s.label,
+                Subject::Workspace(w) => &w.label,
+            }
+        }
+
+        pub fn kind(&self) -> &'static str {
+            match self {
+                Subject::Session(_) => "session",
+                Subject::Workspace(_) => "workspace",
+            }
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct Finding {
+        pub subject: Subject,
+        pub issue: Issue,
+        pub action: Action,
+    }
+
+    #[derive(Debug, Clone, Default)]
+    pub struct Report {
+        pub findings: Vec<Finding>,
+        /// Compose projects with the configured prefix that no session owns.
+        pub orphan_projects: Vec<String>,
+        /// False when docker was unavailable and compose checks were skipped.
+        pub compose_checked: bool,
+        /// False when tmux was unavailable and tmux checks were skipped.
+        pub tmux_checked: bool,
+        /// False when git could not list some repo's worktrees and those
+        /// sessions' worktree checks were skipped.
+        pub worktrees_checked: bool,
+    }
+
+    impl Report {
+        pub fn is_clean(&self) -> bool {
+            self.orphan_projects.is_empty()
+                && self.findings.iter().all(|f| f.action == Action::None)
+        }
+    }
+
+    fn same_path(a: &Path, b: &Path) -> bool {
+        match (a.canonicalize(), b.canonicalize()) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => a == b,
+        }
+    }
+
+    /// `worktrees` is what git lists for the session's repo, `None` when
+    /// that failed; `projects` the compose projects on its engine, `None`
+    /// when they could not be listed.
+    fn check_session(
+        s: &Session,
+        worktrees: Option<&[git::WorktreeEntry]>,
+        projects: Option<&HashSet<String>>,
+    ) -> Option<(Issue, Action)> {
+        if !s.repo_path.exists() {
+            return Some((Issue::RepoMissing(s.repo_path.clone()), Action::Prune));
+        }
+        if let Some(wt) = &s.worktree_path {
+            let untracked =
+                worktrees.is_some_and(|list| !list.iter().any(|e| same_path(&e.path, wt)));
+            if !wt.exists() || untracked {
+                return Some((Issue::WorktreeMissing(wt.clone()), Action::Prune));
+            }
+        }
+        if let Some(projects) = projects {
+            let has_files = !s.compose_files.is_empty()
+                || compose::discover_files(s.work_dir())
+                    .map(|f| !f.is_empty())
+                    .unwrap_or(false);
+            if has_files && !projects.contains(&s.compose_project) {
+                // `up` would start a session the user stopped; `resume` will
+                let action = if s.stopped {
+                    Action::None
+                } else {
+                    Action::Repair
+                };
+                return Some((Issue::ComposeMissing(s.compose_project.clone()), action));
+            }
+        }
+        None
+    }
+
+    /// Compare every registry entry with what git, tmux and docker report.
+    pub fn scan() -> Result<Report, CoreError> {
+        let reg = super::load_registry()?;
+        let mut report = Report::default();
+
+        let mut by_engine = compose::projects_by_engine(&reg.sessions);
+        let projects: HashMap<compose::Engine, Option<HashSet<String>>> = by_engine
+            .iter()
+            .map(|(engine, list)| {
+                let names = list
+                    .as_ref()
+                    .map(|l| l.iter().map(|p| p.name.clone()).collect());
+                (engine.clone(), names)
+            })
+            .collect();
+        // Orphans are only looked for with the default runtime and engine
+        let local = by_engine
+            .remove(&(runtime::resolve(None)?, Target::Local))
+            .unwrap_or_else(|| compose::list_projects(None, &Target::Local).ok());
+        report.compose_checked = local.is_some();
+        let tmux_ok = tmux::is_available();
+        report.tmux_checked = tmux_ok;
+        report.worktrees_checked = true;
+        let mut worktrees: HashMap<PathBuf, Option<Vec<git::WorktreeEntry>>> = HashMap::new();
+
+        for s in &reg.sessions {
+            let subject = || Subject::Session(Box::new(s.clone()));
+            let listed = match s.worktree_path {
+                Some(_) => worktrees
+                    .entry(s.repo_path.clone())
+                    .or_insert_with(|| git::list_worktrees(&s.repo_path).ok())
+                    .as_deref(),
+                None => None,
+            };
+            if s.worktree_path.is_some() && s.repo_path.exists() && listed.is_none() {
+                report.worktrees_checked = false;
+            }
+            let on_engine = projects.get(&compose::engine(s)?).and_then(Option::as_ref);
+            if let Some((issue, action)) = check_session(s, listed, on_engine) {
+                report.findings.push(Finding {
+                    subject: subject(),
+                    issue,
+                    action,
+                });
+            } else if tmux_ok && !s.stopped && !tmux::has_session(&s.tmux_session).unwrap_or(false)
+            {
+                report.findings.push(Finding {
+                    subject: subject(),
+                    issue: Issue::TmuxMissing(s.tmux_session.clone()),
+                    action: Action::None,
+                });
+            }
+        }
+
+        for w in &reg.workspaces {
+            let subject = || Subject::Workspace(w.clone());
+            if !w.root_path.exists() {
+                report.findings.push(Finding {
+                    subject: subject(),
+                    issue: Issue::RepoMissing(w.root_path.clone()),
+                    action: Action::Prune,
+                });
+            } else if tmux_ok && !tmux::has_session(&w.tmux_session).unwrap_or(false) {
+                report.findings.push(Finding {
+                    subject: subject(),
+                    issue: Issue::TmuxMissing(w.tmux_session.clone()),
+                    action: Action::None,
+                });
+            }
+        }
+
+        if let Some(projects) = local {
+            let owned: HashSet<&str> = reg
+                .sessions
+                .iter()
+                .map(|s| s.compose_project.as_str())
+                .collect();
+            let prefix = super::config::load(None)?.project_prefix.value;
+            let mut orphans: Vec<String> = projects
+                .into_iter()
+                .map(|p| p.name)
+                .filter(|p| p.starts_with(&prefix) && !owned.contains(p.as_str()))
+                .collect();
+            orphans.sort();
+            report.orphan_projects = orphans;
+        }
+        Ok(report)
+    }
+
+    /// Apply the prune/repair actions in `report`. A pruned session's compose
+    /// project is taken down first; if that fails the entry is kept. Returns
+    /// the findings that were acted on; failures are returned as errors per
+    /// finding.
+    pub fn fix(report: &Report) -> Vec<(Finding, Result<(), CoreError>)> {
+        let mut out = Vec::new();
+        for f in &report.findings {
+            let res = match (&f.subject, f.action) {
+                (_, Action::None) => continue,
+                (Subject::Session(s), Action::Prune) => {
+                    // Nothing would record the project once the entry is gone
+                    match compose::down(s) {
+                        Ok(()) | Err(CoreError::NoComposeFiles) => {
+                            if matches!(f.issue, Issue::WorktreeMissing(_)) {
+                                let _ = git::prune_worktrees(&s.repo_path);
+                            }
+                            super::remove_session(&s.id).map(|_| ())
+                        }
+                        Err(e) => Err(e),
+                    }
+                }
+                (Subject::Session(s), Action::Repair) => compose::up(s),
+                (Subject::Workspace(w), _) => super::remove_workspace(&w.id).map(|_| ()),
+            };
+            out.push((f.clone(), res));
+        }
+        out
+    }
+}
+
+/// Per-session host port blocks, so parallel sessions of the same stack can
+/// publish the same container ports without colliding.
+pub mod ports {
+    use super::compose::PublishedPort;
+    use super::{config, runtime, CoreError, Session};
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashSet;
+    use std::fs;
+    use std::net::{TcpListener, UdpSocket};
+    use std::path::PathBuf;
+
+    /// A published container port and the host port belljar gave it.
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct PortMapping {
+        pub service: String,
+        /// Container port.
+        pub target: u16,
+        pub host: u16,
+        pub protocol: String,
+    }
+
+    fn host_free(port: u16, protocol: &str) -> bool {
+        if protocol == "udp" {
+            UdpSocket::bind(("0.0.0.0", port)).is_ok()
+        } else {
+            TcpListener::bind(("0.0.0.0", port)).is_ok()
+        }
+    }
+
+    /// Reserve a block of host ports for every published entry in `wanted`
+    /// and record it on `session` (and in the registry). The first block
+    /// that no other session holds and whose ports are free on this host
+    /// wins; a session on a remote engine binds them there, so this host is
+    /// not checked for it.
+    pub fn assign(session: &mut Session, wanted: &[PublishedPort]) -> Result<(), CoreError> {
+        let cfg = config::load(Some(&session.repo_path))?;
+        let local = runtime::Target::of(session) == runtime::Target::Local;
+        let base = cfg.port_base.value as u32;
+        let size = cfg.port_block.value as u32;
+        // Ranges and unpublished ports are left as the compose file has them
+        let needed: Vec<&PublishedPort> = wanted
+            .iter()
+            .filter(|p| {
+                p.published
+                    .as_deref()
+                    .is_some_and(|v| v.parse::<u16>().is_ok())
+            })
+            .collect();
+        if needed.len() as u32 > size {
+            return Err(CoreError::Config(format!(
+                "port_block ({size}) is smaller than the {} published ports",
+                needed.len()
+            )));
+        }
+        let ports = super::update_registry(|reg| {
+            let taken: HashSet<u32> = reg
+                .sessions
+                .iter()
+                .filter(|s| s.id != session.id)
+                .flat_map(|s| s.ports.iter().map(|p| p.host as u32))
+                .collect();
+            let mut start = base;
+            while start + size <= u16::MAX as u32 + 1 {
+                let block = start..start + size;
+                let clear = !taken.iter().any(|p| block.contains(p))
+                    && needed
+                        .iter()
+                        .enumerate()
+                        .all(|(i, p)| !local || host_free((start + i as u32) as u16, &p.protocol));
+                if clear {
+                    let ports: Vec<PortMapping> = needed
+                        .iter()
+                        .enumerate()
+                        .map(|(i, p)| PortMapping {
+                            service: p.service.clone(),
+                            target: p.target,
+                            host: (start + i as u32) as u16,
+                            protocol: p.protocol.clone(),
+                        })
+                        .collect();
+                    if let Some(s) = reg.sessions.iter_mut().find(|s| s.id == session.id) {
+                        s.ports = ports.clone();
+                    }
+                    return Ok(ports);
+                }
+                start += size;
+            }
+            Err(CoreError::Config(format!(
+                "no free block of {size} host ports at or above {base}"
+            )))
+        })?;
+        session.ports = ports;
+        write_override(session, wanted)?;
+        Ok(())
+    }
+
+    fn override_path(session: &Session) -> Result<PathBuf, CoreError> {
+        Ok(super::data_dir()?
+            .join("ports")
+            .join(format!("{}.yml", session.id)))
+    }
+
+    /// The generated compose override for `session`, if it has one.
+    pub fn override_file(session: &Session) -> Result<Option<PathBuf>, CoreError> {
+        if session.ports.is_empty() {
+            return Ok(None);
+        }
+        let path = override_path(session)?;
+        Ok(path.exists().then_some(path))
+    }
+
+    /// Write an override that replaces the `ports:` of every service with
+    /// published ports, remapping each to the session's host port. Entries
+    /// without a host port are kept as they are.
+    fn write_override(session: &Session, wanted: &[PublishedPort]) -> Result<(), CoreError> {
+        let mut services: Vec<&str> = Vec::new();
+        for p in wanted {
+            if !services.contains(&p.service.as_str()) {
+                services.push(&p.service);
+            }
+        }
+        let mut yaml = format!(
+            "# Generated by belljar for session {}; do not edit.\nservices:\n",
+            session.label
+        );
+        for svc in services {
+            // JSON strings are valid YAML scalars
+            yaml.push_str(&format!(
+                "  {}:\n    ports: !override\n",
+                serde_json::json!(svc)
+            ));
+            for p in wanted.iter().filter(|p| p.service == svc) {
+                let host = session
+                    .ports
+                    .iter()
+                    .find(|m| {
+                        m.service == p.service && m.target == p.target && m.protocol == p.protocol
+                    })
+                    .map(|m| m.host.to_string())
+                    .or_else(|| p.published.clone());
+                let ip = p
+                    .host_ip
+                    .as_deref()
+                    .map(|ip| format!("{ip}:"))
+                    .unwrap_or_default();
+                let entry = match host {
+                    Some(host) => format!("{ip}{host}:{}/{}", p.target, p.protocol),
+                    None => format!("{}/{}", p.target, p.protocol),
+                };
+                yaml.push_str(&format!("      - {}\n", serde_json::json!(entry)));
+            }
+        }
+        let path = override_path(session)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, yaml)?;
+        Ok(())
+    }
+
+    /// Best-effort removal of the session's override file.
+    pub fn remove_override(session: &Session) {
+        if let Ok(path) = override_path(session) {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    fn var_name(service: &str) -> String {
+        service
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect()
+    }
+
+    /// `BELLJAR_PORT_<SERVICE>` (first port of each service) and
+    /// `BELLJAR_PORT_<SERVICE>_<TARGET>` for every allocated port.
+    pub fn env(session: &Session) -> Vec<(String, String)> {
+        let mut vars = Vec::new();
+        let mut seen = HashSet::new();
+        for m in &session.ports {
+            let svc = var_name(&m.service);
+            if seen.insert(svc.clone()) {
+                vars.push((format!("BELLJAR_PORT_{svc}"), m.host.to_string()));
+            }
+            vars.push((
+                format!("BELLJAR_PORT_{svc}_{}", m.target),
+                m.host.to_string(),
+            ));
+        }
+        vars
+    }
+}
+
+/// Copies of compose volumes: from template sessions into new sessions'
+/// projects, and to and from snapshot tarballs in the data dir.
+pub mod volumes {
+    use super::{compose, config, runtime, CoreError, Session};
+    use serde::{Deserialize, Serialize};
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    const PROJECT_LABEL: &str = "com.docker.compose.project";
+    const VOLUME_LABEL: &str = "com.docker.compose.volume";
+    /// Snapshot metadata, next to the tarballs.
+    const META: &str = "snapshot.json";
+
+    /// A volume compose created for a project.
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+    pub struct Volume {
+        pub name: String,
+        /// Key under `volumes:` in the compose files.
+        pub key: String,
+    }
+
+    /// Volume data saved by `belljar snapshot`, one `<key>.tar` per volume.
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct Snapshot {
+        pub label: String,
+        pub name: String,
+        /// Project the volumes were exported from.
+        pub compose_project: String,
+        pub created_at: String,
+        /// Compose volume keys.
+        pub volumes: Vec<String>,
+    }
+
+    /// Where `--from-volumes` takes data from.
+    #[derive(Debug, Clone)]
+    pub enum Seed {
+        Template(Box<Session>),
+        Snapshot(Snapshot),
+    }
+
+    fn run(mut cmd: Command, what: &str) -> Result<(), CoreError> {
+        let out = cmd
+            .output()
+            .map_err(|e| CoreError::Compose(format!("{what}: {e}")))?;
+        if !out.status.success() {
+            return Err(CoreError::Compose(format!(
+                "{what} failed with status {}: {}",
+                out.status,
+                String::from_utf8_lossy(&out.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+
+    /// The session named `name` if it is marked as a template.
+    pub fn template(name: &str) -> Result<Session, CoreError> {
+        super::find_session(name)?
+            .filter(|s| s.template)
+            .ok_or_else(|| CoreError::NotFound {
+                kind: "template",
+                label: name.to_string(),
+            })
+    }
+
+    /// `<label>@<snapshot>` names a snapshot, anything else a template
+    /// session.
+    pub fn seed(name: &str) -> Result<Seed, CoreError> {
+        match name.split_once('@') {
+            Some((label, snapshot)) => find_snapshot(label, snapshot).map(Seed::Snapshot),
+            None => template(name).map(|s| Seed::Template(Box::new(s))),
+        }
+    }
+
+    /// Fill `to`'s volumes from `seed`; see `copy` and `unpack`.
+    pub fn fill(
+        seed: &Seed,
+        to: &Session,
+        created: &mut dyn FnMut(&str),
+    ) -> Result<Vec<String>, CoreError> {
+        match seed {
+            Seed::Template(from) => copy(from, to, created),
+            Seed::Snapshot(snapshot) => unpack(snapshot, to, created),
+        }
+    }
+
+    /// Volumes labelled as belonging to the session's compose project, on
+    /// the session's engine.
+    pub fn list(session: &Session) -> Result<Vec<Volume>, CoreError> {
+        let out = runtime::session_container(session)?
+            .args(["volume", "ls", "--filter"])
+            .arg(format!("label={PROJECT_LABEL}={}", session.compose_project))
+            .arg("--format")
+            .arg(format!("{{{{.Name}}}}\t{{{{.Label \"{VOLUME_LABEL}\"}}}}"))
+            .output()
+            .map_err(|e| CoreError::Compose(format!("volume ls: {e}")))?;
+        if !out.status.success() {
+            return Err(CoreError::Compose(format!(
+                "volume ls failed with status {}",
+                out.status
+            )));
+        }
+        Ok(String::from_utf8_lossy(&out.stdout)
+            .lines()
+            .filter_map(|l| l.split_once('\t'))
+            .filter(|(_, key)| !key.is_empty())
+            .map(|(name, key)| Volume {
+                name: name.to_string(),
+                key: key.to_string(),
+            })
+            .collect())
+    }
+
+    /// The session's volumes named after its project. Volumes with a fixed
+    /// `name:` are shared by every project and are left out.
+    fn project_volumes(session: &Session) -> Result<Vec<Volume>, CoreError> {
+        let prefix = format!("{}_", session.compose_project);
+        Ok(list(session)?
+            .into_iter()
+            .filter(|v| v.name.strip_prefix(&prefix) == Some(v.key.as_str()))
+            .collect())
+    }
+
+    /// Create `<project>_<key>` with the labels compose gives its own
+    /// volumes, so `up` adopts it instead of creating an empty one.
+    fn create(session: &Session, key: &str) -> Result<String, CoreError> {
+        let name = format!("{}_{key}", session.compose_project);
+        let mut cmd = runtime::session_container(session)?;
+        cmd.args(["volume", "create", "--label"])
+            .arg(format!("{PROJECT_LABEL}={}", session.compose_project))
+            .arg("--label")
+            .arg(format!("{VOLUME_LABEL}={key}"))
+            .arg(&name);
+        run(cmd, &format!("volume create {name}"))?;
+        Ok(name)
+    }
+
+    /// Run `f` with the session's running containers paused, so volume data
+    /// is read at a single point in time.
+    fn while_paused<T>(
+        session: &Session,
+        f: impl FnOnce() -> Result<T, CoreError>,
+    ) -> Result<T, CoreError> {
+        let paused = !session.stopped && compose::pause(session, true).is_ok();
+        let result = f();
+        if paused {
+            compose::pause(session, false)?;
+        }
+        result
+    }
+
+    /// Copy each of `from`'s project volumes into a new volume of the same
+    /// key in `to`'s project. A running template is paused for the copy.
+    /// `created` is told each new volume's name. Returns the keys copied.
+    /// Both sessions must run on the same engine; a snapshot carries data
+    /// between engines.
+    pub fn copy(
+        from: &Session,
+        to: &Session,
+        created: &mut dyn FnMut(&str),
+    ) -> Result<Vec<String>, CoreError> {
+        let (source_engine, dest_engine) = (runtime::Target::of(from), runtime::Target::of(to));
+        if source_engine != dest_engine {
+            return Err(CoreError::Config(format!(
+                "template {} runs on {source_engine}, not {dest_engine}; seed from a snapshot of it instead",
+                from.label
+            )));
+        }
+        let image = config::load(Some(&to.repo_path))?.helper_image.value;
+        let source = project_volumes(from)?;
+        while_paused(from, || {
+            let mut copied = Vec::new();
+            for v in &source {
+                let dest = create(to, &v.key)?;
+                created(&dest);
+                let mut cp = runtime::session_container(to)?;
+                cp.args(["run", "--rm", "-v"])
+                    .arg(format!("{}:/from:ro", v.name))
+                    .arg("-v")
+                    .arg(format!("{dest}:/to"))
+                    .arg(&image)
+                    .args(["cp", "-a", "/from/.", "/to/"]);
+                run(cp, &format!("copy {} to {dest}", v.name))?;
+                copied.push(v.key.clone());
+            }
+            Ok(copied)
+        })
+    }
+
+    /// Best-effort removal of volumes by name from the session's engine.
+    pub fn remove(session: &Session, names: &[String]) {
+        if names.is_empty() {
+            return;
+        }
+        let Ok(mut cmd) = runtime::session_container(session) else {
+            return;
+        };
+        cmd.args(["volume", "rm", "-f"]).args(names);
+        let _ = run(cmd, "volume rm");
+    }
+
+    fn snapshots_root() -> Result<PathBuf, CoreError> {
+        Ok(super::data_dir()?.join("snapshots"))
+    }
+
+    fn snapshot_dir(label: &str, name: &str) -> Result<PathBuf, CoreError> {
+        check_name("session", label)?;
+        check_name("snapshot", name)?;
+        Ok(snapshots_root()?.join(label).join(name))
+    }
+
+    /// `name` is usable as one path component under the snapshots root.
+    fn check_name(kind: &'static str, name: &str) -> Result<(), CoreError> {
+        let valid = !name.is_empty()
+            && !name.starts_with('.')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "._-".contains(c));
+        if valid {
+            Ok(())
+        } else {
+            Err(CoreError::InvalidName {
+                kind,
+                name: name.to_string(),
+            })
+        }
+    }
+
+    /// Export the session's project volumes to
+    /// `<data dir>/snapshots/<label>/<name>/<key>.tar`, pausing its running
+    /// containers meanwhile. `name` defaults to the UTC time.
+    pub fn snapshot(session: &Session, name: Option<&str>) -> Result<Snapshot, CoreError> {
+        let now = time::OffsetDateTime::now_utc();
+        let name = match name {
+            Some(name) => {
+                check_name("snapshot", name)?;
+                name.to_string()
+            }
+            None => now
+                .format(time::macros::format_description!(
+                    "[year][month][day]T[hour][minute][second]Z"
+                ))
+                .unwrap_or_default(),
+        };
+        let dir = snapshot_dir(&session.label, &name)?;
+        if dir.exists() {
+            return Err(CoreError::LabelConflict {
+                kind: "snapshot",
+                label: format!("{}@{name}", session.label),
+            });
+        }
+        let source = project_volumes(session)?;
+        if source.is_empty() {
+            return Err(CoreError::Compose(format!(
+                "project {} has no volumes to snapshot",
+                session.compose_project
+            )));
+        }
+        let image = config::load(Some(&session.repo_path))?.helper_image.value;
+        fs::create_dir_all(&dir)?;
+        // Streamed through the CLI, so this also works with a remote engine
+        let exported = while_paused(session, || {
+            for v in &source {
+                let file = fs::File::create(dir.join(format!("{}.tar", v.key)))?;
+                let mut cmd = runtime::session_container(session)?;
+                cmd.args(["run", "--rm", "-v"])
+                    .arg(format!("{}:/from:ro", v.name))
+                    .arg(&image)
+                    .args(["tar", "-cf", "-", "-C", "/from", "."])
+                    .stdout(file);
+                run(cmd, &format!("export {}", v.name))?;
+            }
+            Ok(())
+        });
+        if let Err(e) = exported {
+            let _ = fs::remove_dir_all(&dir);
+            return Err(e);
+        }
+        let snapshot = Snapshot {
+            label: session.label.clone(),
+            name,
+            compose_project: session.compose_project.clone(),
+            created_at: now
+                .format(&time::format_description::well_known::Rfc3339)
+                .unwrap_or_default(),
+            volumes: source.into_iter().map(|v| v.key).collect(),
+        };
+        fs::write(dir.join(META), serde_json::to_string_pretty(&snapshot)?)?;
+        Ok(snapshot)
+    }
+
+    /// Snapshots of sessions labelled `label` (every label when `None`),
+    /// oldest first.
+    pub fn snapshots(label: Option<&str>) -> Result<Vec<Snapshot>, CoreError> {
+        let root = snapshots_root()?;
+        let labels: Vec<PathBuf> = match label {
+            Some(label) => {
+                check_name("session", label)?;
+                vec![root.join(label)]
+            }
+            None if root.is_dir() => fs::read_dir(&root)?
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .collect(),
+            None => Vec::new(),
+        };
+        let mut found = Vec::new();
+        for dir in labels.iter().filter(|d| d.is_dir()) {
+            for entry in fs::read_dir(dir)?.filter_map(Result::ok) {
+                // Half-written snapshots have no metadata yet
+                let Ok(text) = fs::read_to_string(entry.path().join(META)) else {
+                    continue;
+                };
+                if let Ok(snapshot) = serde_json::from_str::<Snapshot>(&text) {
+                    found.push(snapshot);
+                }
+            }
+        }
+        found.sort_by(|a, b| (&a.label, &a.created_at).cmp(&(&b.label, &b.created_at)));
+        Ok(found)
+    }
+
+    pub fn find_snapshot(label: &str, name: &str) -> Result<Snapshot, CoreError> {
+        snapshots(Some(label))?
+            .into_iter()
+            .find(|s| s.name == name)
+            .ok_or_else(|| CoreError::NotFound {
+                kind: "snapshot",
+                label: format!("{label}@{name}"),
+            })
+    }
+
+    /// Delete a snapshot's tarballs.
+    pub fn remove_snapshot(label: &str, name: &str) -> Result<Snapshot, CoreError> {
+        let snapshot = find_snapshot(label, name)?;
+        fs::remove_dir_all(snapshot_dir(label, name)?)?;
+        Ok(snapshot)
+    }
+
+    /// Replace the contents of `to`'s volumes with the snapshot's tarballs,
+    /// creating any volume `to` does not have yet; `created` is told their
+    /// names. Volumes not in the snapshot are left alone. Returns the keys
+    /// restored.
+    pub fn unpack(
+        snapshot: &Snapshot,
+        to: &Session,
+        created: &mut dyn FnMut(&str),
+    ) -> Result<Vec<String>, CoreError> {
+        let image = config::load(Some(&to.repo_path))?.helper_image.value;
+        let dir = snapshot_dir(&snapshot.label, &snapshot.name)?;
+        let existing = project_volumes(to)?;
+        for key in &snapshot.volumes {
+            let dest = match existing.iter().find(|v| &v.key == key) {
+                Some(v) => v.name.clone(),
+                None => {
+                    let name = create(to, key)?;
+                    created(&name);
+                    name
+                }
+            };
+            let file = fs::File::open(dir.join(format!("{key}.tar")))?;
+            let mut cmd = runtime::session_container(to)?;
+            cmd.args(["run", "--rm", "-i", "-v"])
+                .arg(format!("{dest}:/to"))
+                .arg(&image)
+                .args([
+                    "sh",
+                    "-c",
+                    "find /to -mindepth 1 -delete && tar -xf - -C /to",
+                ])
+                .stdin(file);
+            run(cmd, &format!("restore {dest}"))?;
+        }
+        Ok(snapshot.volumes.clone())
+    }
+}
+
+/// CPU and memory caps for a session's containers, applied through a
+/// generated compose override layered after the session's compose files.
+pub mod limits {
+    use super::{compose, config, CoreError, Session};
+    use serde::{Deserialize, Serialize};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    /// Caps for one service, or for each service of the session.
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct Limit {
+        /// Service capped; `None` applies to every service without its own
+        /// value.
+        pub service: Option<String>,
+        /// Number of CPUs, e.g. `1.5`.
+        pub cpus: Option<String>,
+        /// Memory with an optional `b`, `k`, `m` or `g` suffix, e.g. `512m`.
+        pub memory: Option<String>,
+    }
+
+    impl Limit {
+        /// `cpus=1.5 memory=512m`, or `none`.
+        pub fn summary(&self) -> String {
+            let mut parts = Vec::new();
+            if let Some(cpus) = &self.cpus {
+                parts.push(format!("cpus={cpus}"));
+            }
+            if let Some(memory) = &self.memory {
+                parts.push(format!("memory={memory}"));
+            }
+            if parts.is_empty() {
+                "none".into()
+            } else {
+                parts.join(" ")
+            }
+        }
+    }
+
+    fn check_cpus(value: &str) -> Result<(), CoreError> {
+        match value.parse::<f64>() {
+            Ok(n) if n > 0.0 && n.is_finite() => Ok(()),
+            _ => Err(CoreError::InvalidLimit(format!(
+                "cpus must be a positive number, got: {value}"
+            ))),
+        }
+    }
+
+    fn check_memory(value: &str) -> Result<(), CoreError> {
+        let lower = value.to_ascii_lowercase();
+        let digits = lower.trim_end_matches(['b', 'k', 'm', 'g']);
+        let suffix = &lower[digits.len()..];
+        let valid = matches!(suffix, "" | "b" | "k" | "m" | "g" | "kb" | "mb" | "gb")
+            && digits.parse::<u64>().is_ok_and(|n| n > 0);
+        if valid {
+            Ok(())
+        } else {
+            Err(CoreError::InvalidLimit(format!(
+                "memory must be a size such as 512m or 2g, got: {value}"
+            )))
+        }
+    }
+
+    /// Parse one `[SERVICE=]VALUE` entry for `resource` (`cpus` or
+    /// `memory`).
+    pub fn parse(resource: &str, entry: &str) -> Result<Limit, CoreError> {
+        let (service, value) = match entry.split_once('=') {
+            Some((service, value)) => (Some(service.trim()), value.trim()),
+            None => (None, entry.trim()),
+        };
+        if service == Some("") {
+            return Err(CoreError::InvalidLimit(format!(
+                "missing service name in {entry}"
+            )));
+        }
+        let service = service.map(str::to_string);
+        match resource {
+            "cpus" => {
+                check_cpus(value)?;
+                Ok(Limit {
+                    service,
+                    cpus: Some(value.to_string()),
+                    memory: None,
+                })
+            }
+            "memory" => {
+                check_memory(value)?;
+                Ok(Limit {
+                    service,
+                    cpus: None,
+                    memory: Some(value.to_string()),
+                })
+            }
+            _ => Err(CoreError::InvalidLimit(format!(
+                "unknown resource: {resource}"
+            ))),
+        }
+    }
+
+    /// Parse comma-separated `[SERVICE=]VALUE` entries, as in the `cpus`
+    /// and `memory` settings.
+    pub fn parse_list(resource: &str, list: &str) -> Result<Vec<Limit>, CoreError> {
+        list.split(',')
+            .filter(|e| !e.trim().is_empty())
+            .map(|e| parse(resource, e))
+            .collect()
+    }
+
+    /// Add `limit` to `limits`, replacing the values it sets for the same
+    /// service.
+    pub fn merge(limits: &mut Vec<Limit>, limit: Limit) {
+        match limits.iter_mut().find(|l| l.service == limit.service) {
+            Some(existing) => {
+                if limit.cpus.is_some() {
+                    existing.cpus = limit.cpus;
+                }
+                if limit.memory.is_some() {
+                    existing.memory = limit.memory;
+                }
+            }
+            None => limits.push(limit),
+        }
+    }
+
+    /// The repo's `cpus`/`memory` settings with `extra` applied on top;
+    /// session-wide caps first.
+    pub fn resolve(repo: &Path, extra: &[Limit]) -> Result<Vec<Limit>, CoreError> {
+        let cfg = config::load(Some(repo))?;
+        let mut limits = Vec::new();
+        let setting = |key: &str, list: &str| {
+            parse_list(key, list).map_err(|e| CoreError::Config(format!("{key}: {e}")))
+        };
+        let configured = setting("cpus", &cfg.cpus.value)?
+            .into_iter()
+            .chain(setting("memory", &cfg.memory.value)?);
+        for limit in configured.chain(extra.iter().cloned()) {
+            merge(&mut limits, limit);
+        }
+        limits.sort_by(|a, b| a.service.cmp(&b.service));
+        Ok(limits)
+    }
+
+    /// Caps in effect for `service`: its own values, falling back to the
+    /// session-wide ones.
+    pub fn effective(session: &Session, service: &str) -> Limit {
+        let own = session
+            .limits
+            .iter()
+            .find(|l| l.service.as_deref() == Some(service));
+        let all = session.limits.iter().find(|l| l.service.is_none());
+        let pick = |f: fn(&Limit) -> &Option<String>| {
+            own.and_then(|l| f(l).clone())
+                .or_else(|| all.and_then(|l| f(l).clone()))
+        };
+        Limit {
+            service: Some(service.to_string()),
+            cpus: pick(|l| &l.cpus),
+            memory: pick(|l| &l.memory),
+        }
+    }
+
+    fn override_path(session: &Session) -> Result<PathBuf, CoreError> {
+        Ok(super::data_dir()?
+            .join("limits")
+            .join(format!("{}.yml", session.id)))
+    }
+
+    /// The generated compose override for `session`, if it has one.
+    pub fn override_file(session: &Session) -> Result<Option<PathBuf>, CoreError> {
+        if session.limits.is_empty() {
+            return Ok(None);
+        }
+        let path = override_path(session)?;
+        Ok(path.exists().then_some(path))
+    }
+
+    /// Write an override setting `cpus` and `mem_limit` on every service of
+    /// the session that has a cap. Fails if a cap names an unknown service.
+    pub fn write_override(session: &Session) -> Result<(), CoreError> {
+        if session.limits.is_empty() {
+            return Ok(());
+        }
+        let named: Vec<String> = session
+            .limits
+            .iter()
+            .filter_map(|l| l.service.clone())
+            .collect();
+        compose::check_services(session, &named)?;
+        let mut yaml = format!(
+            "# Generated by belljar for session {}; do not edit.\nservices:\n",
+            session.label
+        );
+        for svc in compose::services(session)? {
+            let limit = effective(session, &svc);
+            if limit.cpus.is_none() && limit.memory.is_none() {
+                continue;
+            }
+            // JSON strings are valid YAML scalars
+            yaml.push_str(&format!("  {}:\n", serde_json::json!(svc)));
+            if let Some(cpus) = &limit.cpus {
+                yaml.push_str(&format!("    cpus: {cpus}\n"));
+            }
+            if let Some(memory) = &limit.memory {
+                yaml.push_str(&format!("    mem_limit: {}\n", serde_json::json!(memory)));
+            }
+        }
+        let path = override_path(session)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, yaml)?;
+        Ok(())
+    }
+
+    /// Best-effort removal of the session's override file.
+    pub fn remove_override(session: &Session) {
+        if let Ok(path) = override_path(session) {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// The environment each session exposes to its tmux shells, to compose
+/// interpolation and, when `env_file` is set, to a file in its worktree.
+pub mod env {
+    use super::{config, git, ports, CoreError, Session};
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// First line of generated env files; files without it are never
+    /// overwritten.
+    const HEADER: &str = "# Generated by belljar";
+
+    /// Lower-case label with every other character replaced by `_`, usable
+    /// in database and other identifiers.
+    fn slug(label: &str) -> String {
+        label
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_lowercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect()
+    }
+
+    /// Expand `{name}` in the template for `key`, where `name` is one of
+    /// `placeholders` or a variable in `vars`. Port variables not (yet)
+    /// allocated expand to nothing.
+    fn expand(
+        key: &str,
+        template: &str,
+        placeholders: &[(&str, String)],
+        vars: &[(String, String)],
+    ) -> Result<String, CoreError> {
+        let mut out = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            out.push_str(&rest[..start]);
+            let Some(len) = rest[start..].find('}') else {
+                break;
+            };
+            let name = &rest[start + 1..start + len];
+            let value = placeholders
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, v)| v)
+                .or_else(|| vars.iter().find(|(n, _)| n == name).map(|(_, v)| v));
+            match value {
+                Some(value) => out.push_str(value),
+                // Ports are allocated after compose first reads the config
+                None if name.starts_with("BELLJAR_PORT_") => {}
+                None => {
+                    return Err(CoreError::Config(format!(
+                        "env.{key}: unknown placeholder {{{name}}}"
+                    )))
+                }
+            }
+            rest = &rest[start + len + 1..];
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+
+    /// `BELLJAR_SESSION`, `BELLJAR_SESSION_ID`, `BELLJAR_WORKTREE`,
+    /// `COMPOSE_PROJECT_NAME`, the variables selecting the session's engine
+    /// and the allocated ports, followed by the repo's `[env]` templates in
+    /// name order.
+    pub fn build(session: &Session) -> Result<Vec<(String, String)>, CoreError> {
+        let mut vars: Vec<(String, String)> = vec![
+            ("BELLJAR_SESSION".into(), session.label.clone()),
+            ("BELLJAR_SESSION_ID".into(), session.id.clone()),
+            (
+                "BELLJAR_WORKTREE".into(),
+                session.work_dir().display().to_string(),
+            ),
+            (
+                "COMPOSE_PROJECT_NAME".into(),
+                session.compose_project.clone(),
+            ),
+        ];
+        vars.extend(super::runtime::session_env(session)?);
+        vars.extend(ports::env(session));
+        let placeholders = [
+            ("label", session.label.clone()),
+            ("slug", slug(&session.label)),
+            ("id", session.id[..8.min(session.id.len())].to_string()),
+            ("project", session.compose_project.clone()),
+            ("branch", session.branch.clone().unwrap_or_default()),
+            ("worktree", session.work_dir().display().to_string()),
+        ];
+        let builtin = vars.len();
+        let cfg = config::load(Some(&session.repo_path))?;
+        for (key, setting) in &cfg.env {
+            if vars.iter().any(|(n, _)| n == key) {
+                return Err(CoreError::Config(format!(
+                    "env.{key} would replace a variable set by belljar"
+                )));
+            }
+            let value = expand(key, &setting.value, &placeholders, &vars[..builtin])?;
+            vars.push((key.clone(), value));
+        }
+        Ok(vars)
+    }
+
+    /// Where `env_file` puts the session's environment, if it is set.
+    pub fn file_path(session: &Session) -> Result<Option<PathBuf>, CoreError> {
+        let name = config::load(Some(&session.repo_path))?.env_file.value;
+        Ok((!name.is_empty()).then(|| session.work_dir().join(name)))
+    }
+
+    fn dotenv_value(value: &str) -> String {
+        let plain = value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-./:@,+".contains(c));
+        if plain {
+            value.to_string()
+        } else {
+            format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+        }
+    }
+
+    /// Write the session's environment to its `env_file`, unless that file
+    /// exists and was not generated by belljar, and keep it out of `git
+    /// status` so the worktree does not look dirty. Returns the path written.
+    pub fn write_file(session: &Session) -> Result<Option<PathBuf>, CoreError> {
+        let Some(path) = file_path(session)? else {
+            return Ok(None);
+        };
+        if let Ok(existing) = fs::read_to_string(&path) {
+            if !existing.starts_with(HEADER) {
+                return Err(CoreError::Config(format!(
+                    "refusing to overwrite {}: not generated by belljar",
+                    path.display()
+                )));
+            }
+        }
+        let mut text = format!("{HEADER} for session {}; do not edit\n", session.label);
+        for (k, v) in build(session)? {
+            text.push_str(&format!("{k}={}\n", dotenv_value(&v)));
+        }
+        fs::write(&path, text)?;
+        let dir = session.work_dir();
+        if git::is_git_repo(dir) {
+            if let Ok(file) = path.strip_prefix(dir) {
+                git::exclude(dir, file)?;
+            }
+        }
+        Ok(Some(path))
+    }
+}
+
+/// Live state of sessions as reported by git, tmux and docker.
+pub mod status {
+    use super::{compose, git, tmux, Session};
+    use serde::Serialize;
+    use std::collections::{HashMap, HashSet};
+    use std::fmt;
+
+    /// What is actually running for a session. `None` means the tool needed
+    /// to check was unavailable.
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+    pub struct Live {
+        /// The worktree (or repo, when there is no worktree) exists on disk.
+        pub worktree: bool,
+        /// The tmux session is running.
+        pub tmux: Option<bool>,
+        /// `docker compose ls` status such as `running(2)`, or `absent`.
+        pub compose: Option<String>,
+    }
+
+    impl fmt::Display for Live {
+        /// One-line summary for `ls`, e.g. `running(2), tmux`.
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let mut parts: Vec<&str> = Vec::new();
+            if !self.worktree {
+                parts.push("worktree missing");
+            }
+            parts.push(self.compose.as_deref().unwrap_or("compose ?"));
+            parts.push(match self.tmux {
+                Some(true) => "tmux",
+                Some(false) => "no tmux",
+                None => "tmux ?",
+            });
+            f.write_str(&parts.join(", "))
+        }
+    }
+
+    /// Everything `belljar status` reports for one session.
+    #[derive(Debug, Clone, Serialize)]
+    pub struct Detail {
+        pub live: Live,
+        /// Branch checked out in the worktree; `None` when detached or absent.
+        pub branch: Option<String>,
+        /// Uncommitted changes in the worktree; `None` when it can't be checked.
+        pub dirty: Option<bool>,
+        /// Per-service state; `None` when docker was unavailable.
+        pub services: Option<Vec<compose::ServiceState>>,
+    }
+
+    pub fn detail(session: &Session) -> Detail {
+        let live = probe(std::slice::from_ref(session)).remove(0);
+        let dir = session.work_dir();
+        let (branch, dirty) = if live.worktree && git::is_git_repo(dir) {
+            (git::current_branch(dir), git::is_dirty(dir).ok())
+        } else {
+            (None, None)
+        };
+        Detail {
+            branch,
+            dirty,
+            services: compose::ps(session).ok(),
+            live,
+        }
+    }
+
+    /// Probe tmux and each engine once and report the live state of each
+    /// session.
+    pub fn probe(sessions: &[Session]) -> Vec<Live> {
+        let projects: HashMap<compose::Engine, Option<HashMap<String, String>>> =
+            compose::projects_by_engine(sessions)
+                .into_iter()
+                .map(|(engine, list)| {
+                    let statuses =
+                        list.map(|l| l.into_iter().map(|p| (p.name, p.status)).collect());
+                    (engine, statuses)
+                })
+                .collect();
+        let running: Option<HashSet<String>> = if tmux::is_available() {
+            tmux::list_sessions().ok().map(|l| l.into_iter().collect())
+        } else {
+            None
+        };
+        sessions
+            .iter()
+            .map(|s| Live {
+                worktree: s.work_dir().exists(),
+                tmux: running.as_ref().map(|r| r.contains(&s.tmux_session)),
+                // Unknown when the session's repo config does not load
+                compose: compose::engine(s)
+                    .ok()
+                    .and_then(|e| projects.get(&e))
+                    .and_then(Option::as_ref)
+                    .map(|p| {
+                        p.get(&s.compose_project)
+                            .cloned()
+                            .unwrap_or_else(|| "absent".into())
+                    }),
+            })
+            .collect()
+    }
+}
+
+/// The container runtime whose compose implementation runs session
+/// projects: configured with the `runtime` setting or auto-detected.
+pub mod runtime {
+    use super::{config, CoreError, Session};
+    use once_cell::sync::Lazy;
+    use serde::Serialize;
+    use std::fmt;
+    use std::path::Path;
+    use std::process::{Command, Stdio};
+    use std::sync::Mutex;
+    use which::which;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub enum Kind {
+        /// `docker compose` (Compose v2 plugin).
+        Docker,
+        /// Standalone `docker-compose`, including v1.
+        DockerCompose,
+        /// `podman compose`.
+        Podman,
+        /// Standalone `podman-compose`.
+        PodmanCompose,
+        /// `nerdctl compose`.
+        Nerdctl,
+    }
+
+    impl Kind {
+        /// Every runtime, in auto-detection order.
+        pub const ALL: [Kind; 5] = [
+            Kind::Docker,
+            Kind::DockerCompose,
+            Kind::Podman,
+            Kind::PodmanCompose,
+            Kind::Nerdctl,
+        ];
+
+        /// Name used by the `runtime` setting.
+        pub fn name(self) -> &'static str {
+            match self {
+                Kind::Docker => "docker",
+                Kind::DockerCompose => "docker-compose",
+                Kind::Podman => "podman",
+                Kind::PodmanCompose => "podman-compose",
+                Kind::Nerdctl => "nerdctl",
+            }
+        }
+
+        pub fn parse(name: &str) -> Option<Kind> {
+            Kind::ALL.into_iter().find(|k| k.name() == name)
+        }
+
+        /// Subcommand selecting compose, for runtimes where it is not a
+        /// binary of its own.
+        fn subcommand(self) -> Option<&'static str> {
+            match self {
+                Kind::Docker | Kind::Podman | Kind::Nerdctl => Some("compose"),
+                Kind::DockerCompose | Kind::PodmanCompose => None,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    pub struct Runtime {
+        pub kind: Kind,
+        /// Binary to run (the `docker` setting for `Kind::Docker`).
+        pub program: String,
+        /// Container CLI for volume and container commands: the `docker`
+        /// setting, `podman` or `nerdctl`.
+        pub engine: String,
+        /// Chosen by auto-detection rather than the `runtime` setting.
+        pub detected: bool,
+    }
+
+    impl fmt::Display for Runtime {
+        /// The compose invocation, e.g. `docker compose` or `podman-compose`.
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self.kind.subcommand() {
+                Some(sub) => write!(f, "{} {sub}", self.program),
+                None => f.write_str(&self.program),
+            }
+        }
+    }
+
+    impl Runtime {
+        fn new(kind: Kind, docker: &str, detected: bool) -> Self {
+            let program = match kind {
+                Kind::Docker => docker.to_string(),
+                other => other.name().to_string(),
+            };
+            let engine = match kind {
+                Kind::Docker | Kind::DockerCompose => docker.to_string(),
+                Kind::Podman | Kind::PodmanCompose => "podman".to_string(),
+                Kind::Nerdctl => "nerdctl".to_string(),
+            };
+            Self {
+                kind,
+                program,
+                engine,
+                detected,
+            }
+        }
+
+        /// A command running the runtime's container CLI.
+        pub fn container(&self) -> Command {
+            Command::new(&self.engine)
+        }
+
+        /// A command running the runtime's compose implementation; further
+        /// arguments are compose arguments.
+        pub fn compose(&self) -> Command {
+            let mut cmd = Command::new(&self.program);
+            if let Some(sub) = self.kind.subcommand() {
+                cmd.arg(sub);
+            }
+            cmd
+        }
+
+        /// First line of `<compose> version`.
+        pub fn version(&self) -> Result<String, CoreError> {
+            let out = self
+                .compose()
+                .arg("version")
+                .output()
+                .map_err(|e| CoreError::Compose(format!("{self}: {e}")))?;
+            if !out.status.success() {
+                return Err(CoreError::Compose(format!(
+                    "{self} version failed with status {}",
+                    out.status
+                )));
+            }
+            Ok(String::from_utf8_lossy(&out.stdout)
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_string())
+        }
+    }
+
+    /// Engine a session's containers run on.
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+    pub enum Target {
+        /// Whatever the runtime's CLI talks to by default.
+        #[default]
+        Local,
+        /// A named Docker context (a podman system connection for podman).
+        Context(String),
+        /// An engine address such as `ssh://build-box` or `tcp://10.0.0.5:2376`.
+        Host(String),
+    }
+
+    impl fmt::Display for Target {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Target::Local => f.write_str("default engine"),
+                Target::Context(name) => write!(f, "context {name}"),
+                Target::Host(host) => write!(f, "host {host}"),
+            }
+        }
+    }
+
+    impl Target {
+        /// The target recorded on `session`.
+        pub fn of(session: &Session) -> Target {
+            match (&session.docker_context, &session.docker_host) {
+                (Some(name), _) => Target::Context(name.clone()),
+                (None, Some(host)) => Target::Host(host.clone()),
+                (None, None) => Target::Local,
+            }
+        }
+
+        /// The `docker_context` or `docker_host` setting for `repo`.
+        pub fn configured(repo: &Path) -> Result<Target, CoreError> {
+            let cfg = config::load(Some(repo))?;
+            match (cfg.docker_context.value, cfg.docker_host.value) {
+                (name, host) if !name.is_empty() && !host.is_empty() => Err(CoreError::Config(
+                    format!("docker_context ({name}) and docker_host ({host}) are both set"),
+                )),
+                (name, _) if !name.is_empty() => Ok(Target::Context(name)),
+                (_, host) if !host.is_empty() => Ok(Target::Host(host)),
+                _ => Ok(Target::Local),
+            }
+        }
+
+        /// Record the target on `session`.
+        pub fn record(&self, session: &mut Session) {
+            session.docker_context = None;
+            session.docker_host = None;
+            match self {
+                Target::Local => {}
+                Target::Context(name) => session.docker_context = Some(name.clone()),
+                Target::Host(host) => session.docker_host = Some(host.clone()),
+            }
+        }
+    }
+
+    impl Runtime {
+        /// Variables pointing the runtime's CLIs at `target`; nothing for
+        /// `Target::Local`.
+        pub fn target_env(&self, target: &Target) -> Result<Vec<(String, String)>, CoreError> {
+            let mut env = Vec::new();
+            match (self.kind, target) {
+                (_, Target::Local) => {}
+                // Docker prefers DOCKER_HOST over DOCKER_CONTEXT and treats
+                // an empty value as unset, so clear the one not chosen
+                (Kind::Docker | Kind::DockerCompose, Target::Context(name)) => {
+                    env.push(("DOCKER_CONTEXT".into(), name.clone()));
+                    env.push(("DOCKER_HOST".into(), String::new()));
+                }
+                (Kind::Docker | Kind::DockerCompose, Target::Host(host)) => {
+                    env.push(("DOCKER_CONTEXT".into(), String::new()));
+                    env.push(("DOCKER_HOST".into(), host.clone()));
+                }
+                (Kind::Podman | Kind::PodmanCompose, Target::Context(name)) => {
+                    env.push(("CONTAINER_CONNECTION".into(), name.clone()));
+                }
+                (Kind::Podman | Kind::PodmanCompose, Target::Host(host)) => {
+                    env.push(("CONTAINER_HOST".into(), host.clone()));
+                }
+                (Kind::Nerdctl, _) => {
+                    return Err(CoreError::Config(format!(
+                        "the nerdctl runtime cannot run sessions on {target}"
+                    )))
+                }
+            }
+            Ok(env)
+        }
+    }
+
+    /// `target_env` for the session's recorded target, using the runtime
+    /// configured for its repo.
+    pub fn session_env(session: &Session) -> Result<Vec<(String, String)>, CoreError> {
+        resolve(Some(&session.repo_path))?.target_env(&Target::of(session))
+    }
+
+    /// The container CLI of the session's runtime, pointed at its engine.
+    pub fn session_container(session: &Session) -> Result<Command, CoreError> {
+        let mut cmd = resolve(Some(&session.repo_path))?.container();
+        cmd.envs(session_env(session)?);
+        Ok(cmd)
+    }
+
+    /// Detected kind per `docker` binary, so detection runs once per process.
+    static DETECTED: Lazy<Mutex<Vec<(String, Kind)>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+    fn usable(kind: Kind, docker: &str) -> bool {
+        let rt = Runtime::new(kind, docker, true);
+        if which(&rt.program).is_err() {
+            return false;
+        }
+        // Standalone binaries are compose; the others may lack the plugin
+        kind.subcommand().is_none()
+            || rt
+                .compose()
+                .arg("version")
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .map(|s| s.success())
+                .unwrap_or(false)
+    }
+
+    /// The first runtime in `Kind::ALL` order that is installed, falling back
+    /// to `docker compose` so errors name the usual tool.
+    pub fn detect(docker: &str) -> Kind {
+        let mut cache = DETECTED.lock().unwrap();
+        if let Some((_, kind)) = cache.iter().find(|(d, _)| d == docker) {
+            return *kind;
+        }
+        let kind = Kind::ALL
+            .into_iter()
+            .find(|k| usable(*k, docker))
+            .unwrap_or(Kind::Docker);
+        cache.push((docker.to_string(), kind));
+        kind
+    }
+
+    /// The runtime configured for `repo` (or globally), detecting one when
+    /// the `runtime` setting is `auto`.
+    pub fn resolve(repo: Option<&Path>) -> Result<Runtime, CoreError> {
+        let cfg = config::load(repo)?;
+        let docker = cfg.docker.value;
+        Ok(match Kind::parse(&cfg.runtime.value) {
+            Some(kind) => Runtime::new(kind, &docker, false),
+            None => Runtime::new(detect(&docker), &docker, true),
+        })
+    }
+}
+
+/// Environment checks for `belljar doctor`.
+pub mod doctor {
+    use super::{git, runtime, tmux, CoreError};
+    use std::path::Path;
+
+    /// One tool belljar shells out to.
+    #[derive(Debug)]
+    pub struct Check {
+        pub name: &'static str,
+        /// belljar cannot create sessions without it.
+        pub required: bool,
+        /// The tool's version, or why it is unusable.
+        pub result: Result<String, CoreError>,
+    }
+
+    #[derive(Debug)]
+    pub struct Report {
+        pub runtime: runtime::Runtime,
+        pub checks: Vec<Check>,
+    }
+
+    impl Report {
+        /// The first required check that failed.
+        pub fn failure(&self) -> Option<&CoreError> {
+            self.checks
+                .iter()
+                .filter(|c| c.required)
+                .find_map(|c| c.result.as_ref().err())
+        }
+    }
+
+    /// Compose features belljar relies on and what is lost without them.
+    const LS: &str = "ls --format json (compose state shows as unknown)";
+    const PS: &str = "ps --format json (--wait fails, services show as unknown)";
+    const CONFIG: &str = "config --format json (published ports are not remapped)";
+    const OVERRIDE: &str = "!override, Compose 2.24.4+ (remapped ports clash with the originals)";
+
+    /// `major.minor.patch` from a version line such as
+    /// `Docker Compose version v2.24.6` or `docker-compose version 1.29.2, build 5becea4c`.
+    fn semver(line: &str) -> Option<(u32, u32, u32)> {
+        let word = line
+            .split_whitespace()
+            .map(|w| w.trim_start_matches('v').trim_end_matches(','))
+            .find(|w| w.starts_with(|c: char| c.is_ascii_digit()))?;
+        let mut parts = word
+            .split(|c: char| !c.is_ascii_digit())
+            .map(|n| n.parse().unwrap_or(0));
+        Some((
+            parts.next()?,
+            parts.next().unwrap_or(0),
+            parts.next().unwrap_or(0),
+        ))
+    }
+
+    /// Features missing from a `kind` runtime whose `compose version`
+    /// printed `version`. `podman compose` is judged by the provider it runs.
+    pub fn missing_features(kind: runtime::Kind, version: &str) -> Vec<&'static str> {
+        use runtime::Kind;
+        match kind {
+            Kind::PodmanCompose => vec![LS, PS, CONFIG, OVERRIDE],
+            Kind::Podman if version.contains("podman-compose") => vec![LS, PS, CONFIG, OVERRIDE],
+            Kind::Nerdctl => vec![LS, CONFIG],
+            Kind::Docker | Kind::DockerCompose | Kind::Podman => match semver(version) {
+                Some((major, ..)) if major < 2 => vec![LS, PS, CONFIG, OVERRIDE],
+                Some(v) if v < (2, 24, 4) => vec![OVERRIDE],
+                _ => Vec::new(),
+            },
+        }
+    }
+
+    /// Check the runtime configured for `repo` and the other tools.
+    pub fn run(repo: Option<&Path>) -> Result<Report, CoreError> {
+        let runtime = runtime::resolve(repo)?;
+        let version = runtime.version();
+        let features = match &version {
+            Ok(v) => match missing_features(runtime.kind, v).as_slice() {
+                [] => Ok("ls, ps and config --format json, !override".to_string()),
+                missing => Err(CoreError::Compose(format!(
+                    "{runtime} lacks {}",
+                    missing.join("; ")
+                ))),
+            },
+            Err(_) => Err(CoreError::Compose(format!("{runtime} is unusable"))),
+        };
+        let checks = vec![
+            Check {
+                name: "compose",
+                required: true,
+                result: version,
+            },
+            Check {
+                name: "features",
+                required: false,
+                result: features,
+            },
+            Check {
+                name: "git",
+                required: true,
+                result: git::version(),
+            },
+            Check {
+                name: "tmux",
+                required: false,
+                result: tmux::version(),
+            },
+        ];
+        Ok(Report { runtime, checks })
+    }
+}
+
+pub mod compose {
+    use super::{runtime, CoreError, Session};
+    use serde::Serialize;
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::process::{Command, ExitStatus};
+    use std::time::{Duration, Instant};
+
+    /// `*.yml` and `*.yaml` files directly in `dir`, sorted.
+    fn yaml_files(dir: &Path) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| {
+                p.is_file()
+                    && p.extension()
+                        .is_some_and(|ext| ext == "yml" || ext == "yaml")
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    pub(crate) fn discover_files(repo_path: &Path) -> Result<Vec<PathBuf>, CoreError> {
         let mut files: Vec<PathBuf> = Vec::new();
 
         // Prefer per-repo belljar-specific directory if present
-        let bj_dir = repo_path.join(".belljar").join("compose");
-        if bj_dir.is_dir() {
-            let mut entries: Vec<PathBuf> = fs::read_dir(&bj_dir)?
-                .filter_map(|e| e.ok().map(|e| e.path()))
-                .filter(|p| {
-                    if let Some(ext) = p.extension() {
-                        ext == "yml" || ext == "yaml"
-                    } else {
-                        false
-                    }
-                })
-                .collect();
-            entries.sort();
-            if !entries.is_empty() {
-                return Ok(entries);
-            }
+        let entries = yaml_files(&repo_path.join(".belljar").join("compose"));
+        if !entries.is_empty() {
+            return Ok(entries);
         }
 
         // Fallback to common compose filenames in repo root
@@ -338,46 +3655,608 @@ pub mod compose {
         discover_files(repo_path).unwrap_or_default()
     }
 
-    pub fn up(session: &Session) -> Result<(), CoreError> {
-        let files = discover_files(&session.repo_path)?;
+    /// A project reported by `docker compose ls`.
+    #[derive(Debug, Clone)]
+    pub struct ProjectInfo {
+        pub name: String,
+        pub status: String,
+        pub config_files: Vec<PathBuf>,
+    }
+
+    /// The configured runtime's compose command.
+    fn compose(repo: Option<&Path>) -> Result<Command, CoreError> {
+        Ok(runtime::resolve(repo)?.compose())
+    }
+
+    /// `<runtime> <what> failed with status <status>`.
+    fn failed(repo: Option<&Path>, what: &str, status: ExitStatus) -> CoreError {
+        // The command ran, so the config that picked the runtime loaded
+        let runtime = runtime::resolve(repo).map_or_else(|_| "compose".into(), |r| r.to_string());
+        CoreError::Compose(format!("{runtime} {what} failed with status {status}"))
+    }
+
+    /// The runtime (as configured for its repo) and engine a session's
+    /// project runs on.
+    pub type Engine = (runtime::Runtime, runtime::Target);
+
+    pub fn engine(session: &Session) -> Result<Engine, CoreError> {
+        Ok((
+            runtime::resolve(Some(&session.repo_path))?,
+            runtime::Target::of(session),
+        ))
+    }
+
+    /// List all compose projects known to the engine at `target`, using the
+    /// runtime configured for `repo`, including stopped ones.
+    pub fn list_projects(
+        repo: Option<&Path>,
+        target: &runtime::Target,
+    ) -> Result<Vec<ProjectInfo>, CoreError> {
+        let runtime = runtime::resolve(repo)?;
+        let out = runtime
+            .compose()
+            .envs(runtime.target_env(target)?)
+            .args(["ls", "--all", "--format", "json"])
+            .output()
+            .map_err(|e| CoreError::Compose(e.to_string()))?;
+        if !out.status.success() {
+            return Err(failed(repo, "ls", out.status));
+        }
+        let text = String::from_utf8_lossy(&out.stdout);
+        if text.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        let raw: Vec<serde_json::Value> = serde_json::from_str(text.trim())?;
+        Ok(raw
+            .iter()
+            .filter_map(|p| {
+                Some(ProjectInfo {
+                    name: p.get("Name")?.as_str()?.to_string(),
+                    status: p
+                        .get("Status")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                    config_files: p
+                        .get("ConfigFiles")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or_default()
+                        .split(',')
+                        .filter(|f| !f.is_empty())
+                        .map(PathBuf::from)
+                        .collect(),
+                })
+            })
+            .collect())
+    }
+
+    /// `list_projects` once for each runtime and engine `sessions` run on;
+    /// `None` for those that could not be listed. Sessions whose repo config
+    /// does not load are left out.
+    pub fn projects_by_engine(sessions: &[Session]) -> HashMap<Engine, Option<Vec<ProjectInfo>>> {
+        let mut found = HashMap::new();
+        for s in sessions {
+            let Ok(engine) = engine(s) else {
+                continue;
+            };
+            found
+                .entry(engine)
+                .or_insert_with_key(|(_, t)| list_projects(Some(&s.repo_path), t).ok());
+        }
+        found
+    }
+
+    /// Service names defined by the session's compose files and enabled by
+    /// its profiles, as reported by `docker compose config --services`.
+    pub fn services(session: &Session) -> Result<Vec<String>, CoreError> {
+        let out = session_command(session)?
+            .args(["config", "--services"])
+            .output()
+            .map_err(|e| CoreError::Compose(e.to_string()))?;
+        if !out.status.success() {
+            return Err(failed(Some(&session.repo_path), "config", out.status));
+        }
+        Ok(String::from_utf8_lossy(&out.stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Fail with `UnknownServices` unless every name in `wanted` is defined
+    /// by the session's compose files.
+    pub fn check_services(session: &Session, wanted: &[String]) -> Result<(), CoreError> {
+        let available = services(session)?;
+        let unknown: Vec<String> = wanted
+            .iter()
+            .filter(|w| !available.contains(w))
+            .cloned()
+            .collect();
+        if unknown.is_empty() {
+            Ok(())
+        } else {
+            Err(CoreError::UnknownServices { unknown, available })
+        }
+    }
+
+    /// Port entries of every service of the session that publishes to the
+    /// host, as normalized by `docker compose config --format json`. Only the
+    /// session's selected services are considered unless it has none.
+    /// Without compose files, or when compose cannot render the config as
+    /// JSON (`up` will report any real problem), this is empty.
+    pub fn published_ports(session: &Session) -> Result<Vec<PublishedPort>, CoreError> {
+        let selected = &session.services;
+        let mut cmd = match session_command(session) {
+            Ok(cmd) => cmd,
+            Err(CoreError::NoComposeFiles) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let out = match cmd.args(["config", "--format", "json"]).output() {
+            Ok(out) if out.status.success() => out,
+            _ => return Ok(Vec::new()),
+        };
+        let Ok(raw) = serde_json::from_slice::<serde_json::Value>(&out.stdout) else {
+            return Ok(Vec::new());
+        };
+        let mut ports = Vec::new();
+        let Some(services) = raw.get("services").and_then(|v| v.as_object()) else {
+            return Ok(ports);
+        };
+        for (service, def) in services {
+            if !selected.is_empty() && !selected.contains(service) {
+                continue;
+            }
+            let entries = def.get("ports").and_then(|v| v.as_array());
+            for p in entries.into_iter().flatten() {
+                let Some(target) = p.get("target").and_then(|v| v.as_u64()) else {
+                    continue;
+                };
+                ports.push(PublishedPort {
+                    service: service.clone(),
+                    target: target as u16,
+                    published: p.get("published").and_then(|v| match v {
+                        serde_json::Value::String(s) => Some(s.clone()),
+                        serde_json::Value::Number(n) => Some(n.to_string()),
+                        _ => None,
+                    }),
+                    host_ip: p
+                        .get("host_ip")
+                        .and_then(|v| v.as_str())
+                        .map(str::to_string),
+                    protocol: p
+                        .get("protocol")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("tcp")
+                        .to_string(),
+                });
+            }
+        }
+        Ok(ports)
+    }
+
+    /// A `ports:` entry of a compose service.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct PublishedPort {
+        pub service: String,
+        pub target: u16,
+        /// Host port (or range) from the compose file; `None` when unpublished.
+        pub published: Option<String>,
+        pub host_ip: Option<String>,
+        pub protocol: String,
+    }
+
+    /// Stacks defined in `dir`: subdirectories of `.belljar/compose/` that
+    /// hold compose files.
+    pub fn stacks(dir: &Path) -> Vec<String> {
+        let root = dir.join(".belljar").join("compose");
+        let mut names: Vec<String> = fs::read_dir(&root)
+            .into_iter()
+            .flatten()
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| !yaml_files(p).is_empty())
+            .filter_map(|p| Some(p.file_name()?.to_string_lossy().into_owned()))
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Compose files for `stack` in `dir` (`.belljar/compose/<stack>/*.yml`),
+    /// or the default discovery when no stack is named.
+    pub fn stack_files(dir: &Path, stack: Option<&str>) -> Result<Vec<PathBuf>, CoreError> {
+        let Some(stack) = stack else {
+            return discover_files(dir);
+        };
+        let files = yaml_files(&dir.join(".belljar").join("compose").join(stack));
+        if files.is_empty() {
+            return Err(CoreError::UnknownStack {
+                stack: stack.to_string(),
+                available: stacks(dir),
+            });
+        }
+        Ok(files)
+    }
+
+    /// Discover the compose files for the session's stack in its worktree
+    /// and record them, with its stack, profiles, limits and engine, so later
+    /// commands (including `down`) use the same selection.
+    pub fn record_files(session: &mut Session) -> Result<(), CoreError> {
+        session.compose_files = stack_files(session.work_dir(), session.stack.as_deref())?;
+        let recorded = session.clone();
+        super::update_registry(|reg| {
+            if let Some(s) = reg.sessions.iter_mut().find(|s| s.id == recorded.id) {
+                s.compose_files = recorded.compose_files;
+                s.stack = recorded.stack;
+                s.profiles = recorded.profiles;
+                s.limits = recorded.limits;
+                s.docker_context = recorded.docker_context;
+                s.docker_host = recorded.docker_host;
+            }
+            Ok(())
+        })
+    }
+
+    /// The recorded compose files, or those currently in the worktree for a
+    /// session that was never brought up.
+    fn session_files(session: &Session) -> Result<Vec<PathBuf>, CoreError> {
+        if session.compose_files.is_empty() {
+            stack_files(session.work_dir(), session.stack.as_deref())
+        } else {
+            Ok(session.compose_files.clone())
+        }
+    }
+
+    /// `docker compose -p <project> -f ...` for a session, including its
+    /// port override file and `BELLJAR_PORT_*` variables.
+    fn session_command(session: &Session) -> Result<Command, CoreError> {
+        let files = session_files(session)?;
         if files.is_empty() {
             return Err(CoreError::NoComposeFiles);
         }
-        let mut cmd = Command::new("docker");
-        cmd.arg("compose").arg("-p").arg(&session.compose_project);
+        let mut cmd = compose(Some(&session.repo_path))?;
+        cmd.arg("-p").arg(&session.compose_project);
         for f in &files {
             cmd.arg("-f").arg(f);
         }
-        cmd.arg("up").arg("-d");
+        for p in &session.profiles {
+            cmd.arg("--profile").arg(p);
+        }
+        if let Some(path) = super::ports::override_file(session)? {
+            cmd.arg("-f").arg(path);
+        }
+        if let Some(path) = super::limits::override_file(session)? {
+            cmd.arg("-f").arg(path);
+        }
+        cmd.envs(super::env::build(session)?);
+        Ok(cmd)
+    }
+
+    pub fn up(session: &Session) -> Result<(), CoreError> {
+        let mut cmd = session_command(session)?;
+        // An empty selection starts every service
+        cmd.arg("up").arg("-d").args(&session.services);
         let status = cmd
+            .stdout(super::child_stdout())
             .status()
             .map_err(|e| CoreError::Compose(e.to_string()))?;
         if !status.success() {
-            return Err(CoreError::Compose(format!(
-                "docker compose up failed with status {status}"
-            )));
+            return Err(failed(Some(&session.repo_path), "up", status));
         }
         Ok(())
     }
 
-    pub fn down(session: &Session) -> Result<(), CoreError> {
-        let files = discover_files(&session.repo_path)?;
-        let mut cmd = Command::new("docker");
-        cmd.arg("compose").arg("-p").arg(&session.compose_project);
-        if files.is_empty() {
-            return Err(CoreError::NoComposeFiles);
+    /// Run `command` in the session's `service` container and return its exit
+    /// code (128 + signal when killed by a signal). Without `tty`, `-T` is
+    /// passed so output can be piped.
+    pub fn exec(
+        session: &Session,
+        service: &str,
+        command: &[String],
+        tty: bool,
+    ) -> Result<i32, CoreError> {
+        let mut cmd = session_command(session)?;
+        cmd.arg("exec");
+        if !tty {
+            cmd.arg("-T");
+        }
+        let status = cmd
+            .arg(service)
+            .args(command)
+            .status()
+            .map_err(|e| CoreError::Compose(e.to_string()))?;
+        Ok(status.code().unwrap_or_else(|| {
+            use std::os::unix::process::ExitStatusExt;
+            128 + status.signal().unwrap_or(0)
+        }))
+    }
+
+    /// One container of a session as reported by `docker compose ps`.
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+    pub struct ServiceState {
+        pub service: String,
+        pub container: String,
+        /// `running`, `exited`, `restarting`, ...
+        pub state: String,
+        /// `healthy`, `unhealthy` or `starting`; empty without a healthcheck.
+        pub health: String,
+        /// Exit code of the main process; meaningful only once `exited`.
+        pub exit_code: i64,
+        /// Published ports as `host_ip:host->target/protocol`.
+        pub ports: Vec<String>,
+    }
+
+    fn service_state(v: &serde_json::Value) -> Option<ServiceState> {
+        let text = |key: &str| {
+            v.get(key)
+                .and_then(|x| x.as_str())
+                .unwrap_or_default()
+                .to_string()
+        };
+        let ports = v
+            .get("Publishers")
+            .and_then(|p| p.as_array())
+            .into_iter()
+            .flatten()
+            .filter(|p| p.get("PublishedPort").and_then(|x| x.as_u64()).unwrap_or(0) != 0)
+            .map(|p| {
+                format!(
+                    "{}:{}->{}/{}",
+                    p.get("URL").and_then(|x| x.as_str()).unwrap_or("0.0.0.0"),
+                    p.get("PublishedPort").and_then(|x| x.as_u64()).unwrap_or(0),
+                    p.get("TargetPort").and_then(|x| x.as_u64()).unwrap_or(0),
+                    p.get("Protocol").and_then(|x| x.as_str()).unwrap_or("tcp"),
+                )
+            })
+            .collect();
+        Some(ServiceState {
+            service: v.get("Service")?.as_str()?.to_string(),
+            container: text("Name"),
+            state: text("State"),
+            health: text("Health"),
+            exit_code: v.get("ExitCode").and_then(|x| x.as_i64()).unwrap_or(0),
+            ports,
+        })
+    }
+
+    /// Containers of the session's project, including stopped ones.
+    pub fn ps(session: &Session) -> Result<Vec<ServiceState>, CoreError> {
+        let out = compose(Some(&session.repo_path))?
+            .envs(runtime::session_env(session)?)
+            .args(["-p", &session.compose_project])
+            .args(["ps", "--all", "--format", "json"])
+            .output()
+            .map_err(|e| CoreError::Compose(e.to_string()))?;
+        if !out.status.success() {
+            return Err(failed(Some(&session.repo_path), "ps", out.status));
+        }
+        let text = String::from_utf8_lossy(&out.stdout);
+        let text = text.trim();
+        // Older compose prints one JSON array, newer one object per line
+        let raw: Vec<serde_json::Value> = if text.starts_with('[') {
+            serde_json::from_str(text)?
+        } else {
+            text.lines()
+                .filter(|l| !l.trim().is_empty())
+                .map(serde_json::from_str)
+                .collect::<Result<_, _>>()?
+        };
+        let mut states: Vec<ServiceState> = raw.iter().filter_map(service_state).collect();
+        states.sort_by(|a, b| {
+            a.service
+                .cmp(&b.service)
+                .then(a.container.cmp(&b.container))
+        });
+        Ok(states)
+    }
+
+    /// How long `wait_ready` sleeps between `ps` polls.
+    const WAIT_POLL: Duration = Duration::from_millis(500);
+
+    /// `Ok(true)` once a container is up (healthy when it has a healthcheck,
+    /// or exited 0 for one-shot services), `Ok(false)` while it is still
+    /// coming up, and why it failed otherwise.
+    fn readiness(s: &ServiceState) -> Result<bool, String> {
+        match (s.state.as_str(), s.health.as_str()) {
+            ("running", "" | "healthy") => Ok(true),
+            ("running", "unhealthy") => Err("unhealthy".into()),
+            ("exited", _) if s.exit_code == 0 => Ok(true),
+            ("exited", _) => Err(format!("exited with code {}", s.exit_code)),
+            ("dead", _) => Err("dead".into()),
+            _ => Ok(false),
+        }
+    }
+
+    /// Block until every selected service of the session (all of its
+    /// containers when none were selected) is ready, calling `progress`
+    /// whenever a container's state or health changes. Fails as soon as a
+    /// service turns unhealthy or exits with an error, or when one is still
+    /// not ready after `timeout`.
+    pub fn wait_ready(
+        session: &Session,
+        timeout: Duration,
+        progress: &mut dyn FnMut(&ServiceState),
+    ) -> Result<(), CoreError> {
+        let deadline = Instant::now() + timeout;
+        let mut seen: HashMap<String, (String, String)> = HashMap::new();
+        loop {
+            let states = ps(session)?;
+            let mut wanted = session.services.clone();
+            if wanted.is_empty() {
+                wanted = states.iter().map(|s| s.service.clone()).collect();
+                wanted.dedup();
+            }
+            let mut pending = None;
+            for service in &wanted {
+                let containers: Vec<&ServiceState> =
+                    states.iter().filter(|s| &s.service == service).collect();
+                if containers.is_empty() {
+                    pending.get_or_insert((service, "not created".to_string()));
+                }
+                for c in containers {
+                    let now = (c.state.clone(), c.health.clone());
+                    if seen.get(&c.container) != Some(&now) {
+                        progress(c);
+                        seen.insert(c.container.clone(), now);
+                    }
+                    match readiness(c) {
+                        Ok(true) => {}
+                        Ok(false) => {
+                            let what = if c.health.is_empty() {
+                                &c.state
+                            } else {
+                                &c.health
+                            };
+                            pending.get_or_insert((service, what.clone()));
+                        }
+                        Err(reason) => {
+                            return Err(CoreError::ServiceNotReady {
+                                service: service.clone(),
+                                reason,
+                            })
+                        }
+                    }
+                }
+            }
+            let Some((service, what)) = pending else {
+                return Ok(());
+            };
+            if Instant::now() >= deadline {
+                return Err(CoreError::ServiceNotReady {
+                    service: service.clone(),
+                    reason: format!("still {what} after {}s", timeout.as_secs()),
+                });
+            }
+            std::thread::sleep(WAIT_POLL);
+        }
+    }
+
+    /// Options for `logs`.
+    #[derive(Debug, Clone, Default)]
+    pub struct LogsOptions {
+        /// Only these services (default: all).
+        pub services: Vec<String>,
+        pub follow: bool,
+        /// Passed to `--since` (e.g. `10m`, an RFC 3339 timestamp).
+        pub since: Option<String>,
+        /// Number of lines per service to show from the end.
+        pub tail: Option<String>,
+    }
+
+    /// Stream `docker compose logs` for the session to stdout/stderr and
+    /// return the exit code.
+    pub fn logs(session: &Session, opts: &LogsOptions) -> Result<i32, CoreError> {
+        let mut cmd = session_command(session)?;
+        cmd.arg("logs");
+        if opts.follow {
+            cmd.arg("--follow");
+        }
+        if let Some(since) = &opts.since {
+            cmd.arg("--since").arg(since);
+        }
+        if let Some(tail) = &opts.tail {
+            cmd.arg("--tail").arg(tail);
+        }
+        let status = cmd
+            .args(&opts.services)
+            .status()
+            .map_err(|e| CoreError::Compose(e.to_string()))?;
+        Ok(status.code().unwrap_or(1))
+    }
+
+    fn saved_logs_root(label: &str) -> Result<PathBuf, CoreError> {
+        Ok(super::data_dir()?.join("logs").join(label))
+    }
+
+    /// Write each service's full log to
+    /// `<data dir>/logs/<label>/<UTC timestamp>-<id>/<service>.log`.
+    pub fn save_logs(session: &Session) -> Result<PathBuf, CoreError> {
+        let stamp = time::OffsetDateTime::now_utc()
+            .format(time::macros::format_description!(
+                "[year][month][day]T[hour][minute][second]Z"
+            ))
+            .unwrap_or_default();
+        let services = services(session)?;
+        let dir = saved_logs_root(&session.label)?.join(format!("{stamp}-{}", &session.id[..8]));
+        fs::create_dir_all(&dir)?;
+        for service in services {
+            let out = session_command(session)?
+                .args(["logs", "--no-color", "--timestamps"])
+                .arg(&service)
+                .output()
+                .map_err(|e| CoreError::Compose(e.to_string()))?;
+            let mut text = out.stdout;
+            text.extend_from_slice(&out.stderr);
+            fs::write(dir.join(format!("{service}.log")), text)?;
         }
-        for p in files {
-            cmd.arg("-f").arg(p);
+        Ok(dir)
+    }
+
+    /// Most recent logs saved at teardown for sessions named `label`.
+    pub fn latest_saved_logs(label: &str) -> Result<Option<PathBuf>, CoreError> {
+        let root = saved_logs_root(label)?;
+        if !root.is_dir() {
+            return Ok(None);
         }
-        cmd.arg("down").arg("-v");
+        let mut dirs: Vec<PathBuf> = fs::read_dir(root)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.is_dir())
+            .collect();
+        // Directory names start with a sortable UTC timestamp
+        dirs.sort();
+        Ok(dirs.pop())
+    }
+
+    /// `stop` the session's containers, keeping them and their volumes.
+    pub fn stop(session: &Session) -> Result<(), CoreError> {
+        let mut cmd = session_command(session)?;
         let status = cmd
+            .arg("stop")
+            .stdout(super::child_stdout())
             .status()
             .map_err(|e| CoreError::Compose(e.to_string()))?;
         if !status.success() {
-            return Err(CoreError::Compose(format!(
-                "docker compose down failed with status {status}"
-            )));
+            return Err(failed(Some(&session.repo_path), "stop", status));
+        }
+        Ok(())
+    }
+
+    /// `pause` (or `unpause`) the session's running containers.
+    pub fn pause(session: &Session, paused: bool) -> Result<(), CoreError> {
+        let what = if paused { "pause" } else { "unpause" };
+        let status = session_command(session)?
+            .arg(what)
+            .stdout(super::child_stdout())
+            .status()
+            .map_err(|e| CoreError::Compose(e.to_string()))?;
+        if !status.success() {
+            return Err(failed(Some(&session.repo_path), what, status));
+        }
+        Ok(())
+    }
+
+    /// Save the final logs (best effort), then `down -v`. When a recorded
+    /// compose file has since been deleted, the project is taken down by name
+    /// alone.
+    pub fn down(session: &Session) -> Result<(), CoreError> {
+        let mut cmd = if session.compose_files.iter().all(|f| f.exists()) {
+            let cmd = session_command(session)?;
+            let _ = save_logs(session);
+            cmd
+        } else {
+            let mut cmd = compose(Some(&session.repo_path))?;
+            cmd.envs(runtime::session_env(session)?)
+                .arg("-p")
+                .arg(&session.compose_project);
+            cmd
+        };
+        // Services renamed or dropped from the files since `up` are orphans
+        cmd.args(["down", "-v", "--remove-orphans"]);
+        let status = cmd
+            .stdout(super::child_stdout())
+            .status()
+            .map_err(|e| CoreError::Compose(e.to_string()))?;
+        if !status.success() {
+            return Err(failed(Some(&session.repo_path), "down", status));
         }
         Ok(())
     }
@@ -388,7 +4267,6 @@ pub fn create_workspace(
     root: &Path,
     repos: Vec<PathBuf>,
 ) -> Result<Workspace, CoreError> {
-    let mut reg = load_registry()?;
     let id = Uuid::new_v4().to_string();
     let tmux_session = format!("ws-{label}");
     let created_at = OffsetDateTime::now_utc()
@@ -402,8 +4280,16 @@ pub fn create_workspace(
         tmux_session,
         created_at,
     };
-    reg.workspaces.push(ws.clone());
-    save_registry(&reg)?;
+    update_registry(|reg| {
+        if reg.workspaces.iter().any(|w| w.label == label) {
+            return Err(CoreError::LabelConflict {
+                kind: "workspace",
+                label: label.to_string(),
+            });
+        }
+        reg.workspaces.push(ws.clone());
+        Ok(())
+    })?;
     Ok(ws)
 }
 
@@ -420,17 +4306,13 @@ pub fn find_workspace(label_or_id: &str) -> Result<Option<Workspace>, CoreError>
 }
 
 pub fn remove_workspace(label_or_id: &str) -> Result<Option<Workspace>, CoreError> {
-    let mut reg = load_registry()?;
-    if let Some(idx) = reg
-        .workspaces
-        .iter()
-        .position(|w| w.label == label_or_id || w.id == label_or_id)
-    {
-        let w = reg.workspaces.remove(idx);
-        save_registry(&reg)?;
-        return Ok(Some(w));
-    }
-    Ok(None)
+    update_registry(|reg| {
+        Ok(reg
+            .workspaces
+            .iter()
+            .position(|w| w.label == label_or_id || w.id == label_or_id)
+            .map(|idx| reg.workspaces.remove(idx)))
+    })
 }
 
 pub mod tmux {
@@ -445,25 +4327,112 @@ pub mod tmux {
             .map_err(|_| CoreError::TmuxNotFound)
     }
 
+    pub fn is_available() -> bool {
+        tmux_bin().is_ok()
+    }
+
+    /// Output of `tmux -V`.
+    pub fn version() -> Result<String, CoreError> {
+        let out = Command::new(tmux_bin()?)
+            .arg("-V")
+            .output()
+            .map_err(|e| CoreError::Tmux(e.to_string()))?;
+        if !out.status.success() {
+            return Err(CoreError::Tmux(format!(
+                "tmux -V failed with status {}",
+                out.status
+            )));
+        }
+        Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+    }
+
+    /// Names of all running tmux sessions (empty when no server is running).
+    pub fn list_sessions() -> Result<Vec<String>, CoreError> {
+        let tmux = tmux_bin()?;
+        let out = Command::new(tmux)
+            .args(["list-sessions", "-F", "#{session_name}"])
+            .output()
+            .map_err(|e| CoreError::Tmux(e.to_string()))?;
+        if !out.status.success() {
+            return Ok(Vec::new());
+        }
+        Ok(String::from_utf8_lossy(&out.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect())
+    }
+
     pub fn has_session(name: &str) -> Result<bool, CoreError> {
         let tmux = tmux_bin()?;
         let status = Command::new(tmux)
             .args(["has-session", "-t", name])
+            .stdout(super::child_stdout())
             .status()
             .map_err(|e| CoreError::Tmux(e.to_string()))?;
         Ok(status.success())
     }
 
-    pub fn new_detached(name: &str, cwd: &Path) -> Result<(), CoreError> {
+    pub fn kill_session(name: &str) -> Result<(), CoreError> {
         let tmux = tmux_bin()?;
         let status = Command::new(tmux)
+            .args(["kill-session", "-t", name])
+            .stdout(super::child_stdout())
+            .status()
+            .map_err(|e| CoreError::Tmux(e.to_string()))?;
+        if !status.success() {
+            return Err(CoreError::Tmux("failed to kill session".into()));
+        }
+        Ok(())
+    }
+
+    /// Detach every client attached to the session `name`.
+    pub fn detach_clients(name: &str) -> Result<(), CoreError> {
+        let tmux = tmux_bin()?;
+        let status = Command::new(tmux)
+            .args(["detach-client", "-s", name])
+            .stdout(super::child_stdout())
+            .status()
+            .map_err(|e| CoreError::Tmux(e.to_string()))?;
+        if !status.success() {
+            return Err(CoreError::Tmux("failed to detach clients".into()));
+        }
+        Ok(())
+    }
+
+    pub fn new_detached(name: &str, cwd: &Path) -> Result<(), CoreError> {
+        new_detached_with_env(name, cwd, &[])
+    }
+
+    /// Like `new_detached`, with `env` set in the session's environment.
+    /// `new-session -e` needs tmux 3.2, so the variables are set afterwards
+    /// and the first pane is respawned to pick them up.
+    pub fn new_detached_with_env(
+        name: &str,
+        cwd: &Path,
+        env: &[(String, String)],
+    ) -> Result<(), CoreError> {
+        let tmux = tmux_bin()?;
+        let status = Command::new(&tmux)
             .args(["new-session", "-d", "-s", name, "-c"])
             .arg(cwd)
+            .stdout(super::child_stdout())
             .status()
             .map_err(|e| CoreError::Tmux(e.to_string()))?;
         if !status.success() {
             return Err(CoreError::Tmux("failed to create session".into()));
         }
+        if env.is_empty() {
+            return Ok(());
+        }
+        set_environment(name, env)?;
+        let status = Command::new(&tmux)
+            .args(["respawn-pane", "-k", "-t", name])
+            .stdout(super::child_stdout())
+            .status()
+            .map_err(|e| CoreError::Tmux(e.to_string()))?;
+        if !status.success() {
+            return Err(CoreError::Tmux("failed to respawn pane".into()));
+        }
         Ok(())
     }
 
@@ -479,9 +4448,29 @@ pub mod tmux {
         Ok(())
     }
 
+    /// Set `env` in the session's environment, seen by new panes and windows.
+    pub fn set_environment(name: &str, env: &[(String, String)]) -> Result<(), CoreError> {
+        let tmux = tmux_bin()?;
+        for (k, v) in env {
+            let status = Command::new(&tmux)
+                .args(["set-environment", "-t", name, k, v])
+                .stdout(super::child_stdout())
+                .status()
+                .map_err(|e| CoreError::Tmux(e.to_string()))?;
+            if !status.success() {
+                return Err(CoreError::Tmux(format!("failed to set {k}")));
+            }
+        }
+        Ok(())
+    }
+
     pub fn ensure_session(session: &Session) -> Result<(), CoreError> {
-        if !has_session(&session.tmux_session)? {
-            new_detached(&session.tmux_session, &session.repo_path)?;
+        let env = super::env::build(session)?;
+        if has_session(&session.tmux_session)? {
+            // Refresh values (e.g. ports) for shells opened from now on
+            set_environment(&session.tmux_session, &env)?;
+        } else {
+            new_detached_with_env(&session.tmux_session, &session.repo_path, &env)?;
         }
         // Best-effort tagging so users can style/status belljar sessions in tmux.
         let _ = tag_belljar_session(&session.tmux_session);
@@ -496,6 +4485,7 @@ pub mod tmux {
             .arg(name)
             .arg(command)
             .arg("C-m")
+            .stdout(super::child_stdout())
             .status()
             .map_err(|e| CoreError::Tmux(e.to_string()))?;
         if !status.success() {
@@ -518,6 +4508,7 @@ pub mod tmux {
         let status = Command::new(tmux)
             .args(["new-window", "-t", session_name, "-n", window_name, "-c"])
             .arg(cwd)
+            .stdout(super::child_stdout())
             .status()
             .map_err(|e| CoreError::Tmux(e.to_string()))?;
         if !status.success() {
@@ -530,6 +4521,7 @@ pub mod tmux {
         let tmux = tmux_bin()?;
         let status = Command::new(tmux)
             .args(["select-layout", "-t", session_name, layout])
+            .stdout(super::child_stdout())
             .status()
             .map_err(|e| CoreError::Tmux(e.to_string()))?;
         if !status.success() {
@@ -542,6 +4534,7 @@ pub mod tmux {
         let tmux = tmux_bin()?;
         let status = Command::new(tmux)
             .args(["set-option", "-t", name, "@belljar", "1"])
+            .stdout(super::child_stdout())
             .status()
             .map_err(|e| CoreError::Tmux(e.to_string()))?;
         if !status.success() {
@@ -554,6 +4547,7 @@ pub mod tmux {
         let tmux = tmux_bin()?;
         let status = Command::new(tmux)
             .args(["switch-client", "-t", name])
+            .stdout(super::child_stdout())
             .status()
             .map_err(|e| CoreError::Tmux(e.to_string()))?;
         if !status.success() {
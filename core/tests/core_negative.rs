@@ -33,6 +33,15 @@ fn tmux_not_found() {
         services: vec![],
         tmux_session: "sess".into(),
         created_at: "now".into(),
+        ports: vec![],
+        stopped: false,
+        compose_files: vec![],
+        stack: None,
+        profiles: vec![],
+        limits: vec![],
+        template: false,
+        docker_context: None,
+        docker_host: None,
     };
     let e = belljar_core::tmux::ensure_session(&s).unwrap_err();
     match e {
@@ -33,6 +33,15 @@ fn new_window_and_select_layout_errors() {
         services: vec![],
         tmux_session: "sess".into(),
         created_at: "now".into(),
+        ports: vec![],
+        stopped: false,
+        compose_files: vec![],
+        stack: None,
+        profiles: vec![],
+        limits: vec![],
+        template: false,
+        docker_context: None,
+        docker_host: None,
     };
 
     // new_window should error
@@ -101,7 +101,7 @@ fn cli_checkout_creates_session() {
     assert!(Command::new("git")
         .arg("-C")
         .arg(repo.path())
-        .args(["checkout", "-b", "fx"])
+        .args(["branch", "fx"])
         .status()
         .unwrap()
         .success());
@@ -0,0 +1,55 @@
+use belljar_core::config::{self, Source};
+use std::fs;
+use tempfile::TempDir;
+
+#[test]
+fn layers_override_in_order_and_record_sources() {
+    let user = TempDir::new().unwrap();
+    std::env::set_var("BELLJAR_CONFIG_DIR", user.path());
+    let repo = TempDir::new().unwrap();
+
+    let cfg = config::load(Some(repo.path())).unwrap();
+    assert_eq!(cfg.base_branch.value, "main");
+    assert_eq!(cfg.base_branch.source, Source::Default);
+
+    fs::write(
+        user.path().join("config.toml"),
+        "base_branch = \"develop\"\nlayout = \"even-horizontal\"\nproject_prefix = \"u_\"\n",
+    )
+    .unwrap();
+    fs::create_dir_all(repo.path().join(".belljar")).unwrap();
+    fs::write(
+        repo.path().join(".belljar/config.toml"),
+        "base_branch = \"trunk\"\nworktree_dir = \"../wt\"\n",
+    )
+    .unwrap();
+    std::env::set_var("BELLJAR_LAYOUT", "main-vertical");
+    config::set_cli_overrides(&["docker=podman".to_string()]).unwrap();
+
+    let cfg = config::load(Some(repo.path())).unwrap();
+    assert_eq!(cfg.base_branch.value, "trunk");
+    assert!(matches!(cfg.base_branch.source, Source::Repo(_)));
+    assert_eq!(cfg.project_prefix.value, "u_");
+    assert!(matches!(cfg.project_prefix.source, Source::User(_)));
+    assert_eq!(cfg.layout.value, "main-vertical");
+    assert_eq!(cfg.layout.source, Source::Env("BELLJAR_LAYOUT"));
+    assert_eq!(cfg.docker.value, "podman");
+    assert_eq!(cfg.docker.source, Source::Cli);
+    assert_eq!(cfg.worktree_root(repo.path()), repo.path().join("../wt"));
+
+    // Without a repo only the user layer applies
+    let cfg = config::load(None).unwrap();
+    assert_eq!(cfg.base_branch.value, "develop");
+
+    // Unknown keys and malformed overrides are rejected
+    assert!(config::set_cli_overrides(&["nope=1".to_string()]).is_err());
+    assert!(config::set_cli_overrides(&["layout".to_string()]).is_err());
+    fs::write(repo.path().join(".belljar/config.toml"), "typo = 1\n").unwrap();
+    assert!(matches!(
+        config::load(Some(repo.path())),
+        Err(belljar_core::CoreError::Config(_))
+    ));
+
+    config::set_cli_overrides(&[]).unwrap();
+    std::env::remove_var("BELLJAR_LAYOUT");
+}
@@ -0,0 +1,129 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::Command;
+use std::sync::{Mutex, OnceLock};
+use tempfile::TempDir;
+
+use belljar_core::lifecycle::{self, Plan, Step, Worktree};
+
+static ENV_LOCK: OnceLock<Mutex<()>> = OnceLock::new();
+
+fn prepend_path(dir: &Path) -> String {
+    let old = std::env::var("PATH").unwrap_or_default();
+    format!("{}:{}", dir.display(), old)
+}
+
+fn init_git_repo_with_compose() -> TempDir {
+    let td = TempDir::new().unwrap();
+    let repo = td.path();
+    for args in [
+        vec!["init"],
+        vec!["config", "user.email", "ci@example.com"],
+        vec!["config", "user.name", "CI"],
+    ] {
+        assert!(Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(&args)
+            .status()
+            .unwrap()
+            .success());
+    }
+    fs::write(repo.join("README.md"), "init\n").unwrap();
+    fs::write(repo.join(".gitignore"), ".belljar/worktrees/\n").unwrap();
+    fs::create_dir_all(repo.join(".belljar/compose")).unwrap();
+    fs::write(repo.join(".belljar/compose/svc.yml"), "services: {}\n").unwrap();
+    for args in [vec!["add", "."], vec!["commit", "-m", "init"]] {
+        assert!(Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(&args)
+            .status()
+            .unwrap()
+            .success());
+    }
+    td
+}
+
+/// docker shim that logs calls and fails `up`
+fn make_failing_up_shim() -> (TempDir, std::path::PathBuf) {
+    let dir = TempDir::new().unwrap();
+    let log = dir.path().join("docker.log");
+    let shim = dir.path().join("docker");
+    let script = format!(
+        "#!/usr/bin/env bash\necho \"$@\" >> {}\nfor a in \"$@\"; do [ \"$a\" = up ] && exit 1; done\nexit 0\n",
+        log.display()
+    );
+    fs::write(&shim, script).unwrap();
+    let mut perm = fs::metadata(&shim).unwrap().permissions();
+    perm.set_mode(0o755);
+    fs::set_permissions(&shim, perm).unwrap();
+    (dir, log)
+}
+
+fn plan(repo: &Path, keep_on_failure: bool) -> Plan {
+    Plan {
+        label: "tx".into(),
+        repo_path: repo.to_path_buf(),
+        branch: Some("tx-branch".into()),
+        services: vec![],
+        stack: None,
+        profiles: vec![],
+        limits: vec![],
+        volumes_from: None,
+        target: None,
+        worktree: Worktree::Checkout,
+        wait: None,
+        tmux: false,
+        keep_on_failure,
+    }
+}
+
+#[test]
+fn compose_failure_rolls_back_earlier_steps() {
+    let _guard = ENV_LOCK.get_or_init(|| Mutex::new(())).lock().unwrap();
+    let data = TempDir::new().unwrap();
+    belljar_core::set_data_dir_override_for_testing(data.path());
+    let repo = init_git_repo_with_compose();
+    let (shim_dir, log) = make_failing_up_shim();
+    let orig_path = std::env::var("PATH").unwrap_or_default();
+    std::env::set_var("PATH", prepend_path(shim_dir.path()));
+
+    let err = lifecycle::provision(&plan(repo.path(), false)).unwrap_err();
+    std::env::set_var("PATH", orig_path);
+
+    match err {
+        belljar_core::CoreError::StepFailed { step, .. } => assert_eq!(step, Step::Compose),
+        other => panic!("expected StepFailed, got {other:?}"),
+    }
+    assert!(belljar_core::load_registry().unwrap().sessions.is_empty());
+    assert!(!belljar_core::git::worktree_dir(repo.path(), "tx")
+        .unwrap()
+        .exists());
+    assert!(!belljar_core::git::branch_exists(repo.path(), "tx-branch"));
+    // Half-started project is torn down too
+    assert!(fs::read_to_string(log).unwrap().contains("down -v"));
+}
+
+#[test]
+fn keep_on_failure_leaves_partial_state() {
+    let _guard = ENV_LOCK.get_or_init(|| Mutex::new(())).lock().unwrap();
+    let data = TempDir::new().unwrap();
+    belljar_core::set_data_dir_override_for_testing(data.path());
+    let repo = init_git_repo_with_compose();
+    let (shim_dir, _log) = make_failing_up_shim();
+    let orig_path = std::env::var("PATH").unwrap_or_default();
+    std::env::set_var("PATH", prepend_path(shim_dir.path()));
+
+    let res = lifecycle::provision(&plan(repo.path(), true));
+    std::env::set_var("PATH", orig_path);
+
+    assert!(res.is_err());
+    let reg = belljar_core::load_registry().unwrap();
+    assert_eq!(reg.sessions.len(), 1);
+    assert!(reg.sessions[0].worktree_path.is_some());
+    assert!(belljar_core::git::worktree_dir(repo.path(), "tx")
+        .unwrap()
+        .exists());
+}
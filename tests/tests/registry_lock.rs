@@ -0,0 +1,35 @@
+use std::collections::HashSet;
+use std::thread;
+use tempfile::TempDir;
+
+#[test]
+fn concurrent_creates_do_not_lose_sessions() {
+    let data = TempDir::new().unwrap();
+    belljar_core::set_data_dir_override_for_testing(data.path());
+    let repo = TempDir::new().unwrap();
+
+    let handles: Vec<_> = (0..8)
+        .map(|i| {
+            let repo = repo.path().to_path_buf();
+            thread::spawn(move || {
+                belljar_core::create_session(&format!("s{i}"), &repo, None, vec![])
+                    .expect("create session")
+            })
+        })
+        .collect();
+    for h in handles {
+        h.join().unwrap();
+    }
+
+    let reg = belljar_core::load_registry().expect("load");
+    let labels: HashSet<_> = reg.sessions.iter().map(|s| s.label.clone()).collect();
+    assert_eq!(labels.len(), 8, "lost sessions: {labels:?}");
+
+    // No temp files are left behind after atomic renames
+    let leftovers: Vec<_> = std::fs::read_dir(data.path())
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().ends_with(".tmp"))
+        .collect();
+    assert!(leftovers.is_empty());
+}
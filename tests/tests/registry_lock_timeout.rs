@@ -0,0 +1,27 @@
+use std::fs::OpenOptions;
+use tempfile::TempDir;
+
+#[test]
+fn held_lock_times_out_with_registry_locked() {
+    let data = TempDir::new().unwrap();
+    belljar_core::set_data_dir_override_for_testing(data.path());
+    std::env::set_var("BELLJAR_LOCK_TIMEOUT_MS", "100");
+
+    // Simulate another belljar holding the lock
+    let holder = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(data.path().join("registry.lock"))
+        .unwrap();
+    holder.lock().unwrap();
+
+    let repo = TempDir::new().unwrap();
+    match belljar_core::create_session("t", repo.path(), None, vec![]) {
+        Err(belljar_core::CoreError::RegistryLocked(_)) => {}
+        other => panic!("expected RegistryLocked, got {other:?}"),
+    }
+
+    holder.unlock().unwrap();
+    belljar_core::create_session("t", repo.path(), None, vec![]).expect("create after unlock");
+}
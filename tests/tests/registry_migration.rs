@@ -0,0 +1,62 @@
+use std::fs;
+use std::sync::{Mutex, OnceLock};
+use tempfile::TempDir;
+
+static DATA_LOCK: OnceLock<Mutex<()>> = OnceLock::new();
+
+#[test]
+fn legacy_registry_is_migrated_with_backup() {
+    let _guard = DATA_LOCK.get_or_init(|| Mutex::new(())).lock().unwrap();
+    let data = TempDir::new().unwrap();
+    belljar_core::set_data_dir_override_for_testing(data.path());
+
+    // Pre-versioning registry: no schema_version and no workspaces
+    let legacy = r#"{"sessions":[{"id":"abc","label":"old","repo_path":"/tmp","branch":null,"worktree_path":null,"compose_project":"belljar_abc","services":[],"tmux_session":"old","created_at":"now"}]}"#;
+    fs::write(data.path().join("registry.json"), legacy).unwrap();
+
+    let reg = belljar_core::load_registry().expect("load legacy");
+    assert_eq!(reg.schema_version, belljar_core::REGISTRY_SCHEMA_VERSION);
+    assert_eq!(reg.sessions.len(), 1);
+    assert!(reg.workspaces.is_empty());
+    assert!(reg.sessions[0].ports.is_empty());
+    assert!(!reg.sessions[0].stopped);
+    assert!(reg.sessions[0].compose_files.is_empty());
+    assert!(reg.sessions[0].stack.is_none() && reg.sessions[0].profiles.is_empty());
+    assert!(reg.sessions[0].limits.is_empty());
+    assert!(!reg.sessions[0].template);
+    assert!(reg.sessions[0].docker_context.is_none() && reg.sessions[0].docker_host.is_none());
+
+    // Backup holds the original bytes; the registry itself was upgraded
+    let backup = fs::read_to_string(data.path().join("registry.json.v0.bak")).unwrap();
+    assert_eq!(backup, legacy);
+    let on_disk: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(data.path().join("registry.json")).unwrap())
+            .unwrap();
+    assert_eq!(
+        on_disk["schema_version"],
+        serde_json::json!(belljar_core::REGISTRY_SCHEMA_VERSION)
+    );
+}
+
+#[test]
+fn newer_registry_is_refused() {
+    let _guard = DATA_LOCK.get_or_init(|| Mutex::new(())).lock().unwrap();
+    let data = TempDir::new().unwrap();
+    belljar_core::set_data_dir_override_for_testing(data.path());
+
+    let future = belljar_core::REGISTRY_SCHEMA_VERSION + 1;
+    let text = format!(r#"{{"schema_version":{future},"sessions":[],"workspaces":[],"extra":1}}"#);
+    fs::write(data.path().join("registry.json"), &text).unwrap();
+
+    match belljar_core::load_registry() {
+        Err(belljar_core::CoreError::RegistryTooNew { found, .. }) => assert_eq!(found, future),
+        other => panic!("expected RegistryTooNew, got {other:?}"),
+    }
+    let repo = TempDir::new().unwrap();
+    assert!(belljar_core::create_session("t", repo.path(), None, vec![]).is_err());
+    // The newer file must be left untouched
+    assert_eq!(
+        fs::read_to_string(data.path().join("registry.json")).unwrap(),
+        text
+    );
+}
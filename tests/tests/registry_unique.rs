@@ -0,0 +1,37 @@
+use std::sync::{Mutex, OnceLock};
+use tempfile::TempDir;
+
+static DATA_LOCK: OnceLock<Mutex<()>> = OnceLock::new();
+
+#[test]
+fn duplicate_session_label_is_rejected() {
+    let _guard = DATA_LOCK.get_or_init(|| Mutex::new(())).lock().unwrap();
+    let data = TempDir::new().unwrap();
+    belljar_core::set_data_dir_override_for_testing(data.path());
+    let repo = TempDir::new().unwrap();
+
+    belljar_core::create_session("foo", repo.path(), None, vec![]).unwrap();
+    match belljar_core::create_session("foo", repo.path(), None, vec![]) {
+        Err(belljar_core::CoreError::LabelConflict { kind, label }) => {
+            assert_eq!(kind, "session");
+            assert_eq!(label, "foo");
+        }
+        other => panic!("expected LabelConflict, got {other:?}"),
+    }
+    assert_eq!(belljar_core::load_registry().unwrap().sessions.len(), 1);
+}
+
+#[test]
+fn duplicate_workspace_label_is_rejected() {
+    let _guard = DATA_LOCK.get_or_init(|| Mutex::new(())).lock().unwrap();
+    let data = TempDir::new().unwrap();
+    belljar_core::set_data_dir_override_for_testing(data.path());
+    let root = TempDir::new().unwrap();
+
+    belljar_core::create_workspace("ws", root.path(), vec![]).unwrap();
+    match belljar_core::create_workspace("ws", root.path(), vec![]) {
+        Err(belljar_core::CoreError::LabelConflict { kind, .. }) => assert_eq!(kind, "workspace"),
+        other => panic!("expected LabelConflict, got {other:?}"),
+    }
+    assert_eq!(belljar_core::list_workspaces().unwrap().len(), 1);
+}
@@ -32,6 +32,15 @@ fn tmux_helpers_call_binary() {
         services: vec![],
         tmux_session: "sess".into(),
         created_at: "now".into(),
+        ports: vec![],
+        stopped: false,
+        compose_files: vec![],
+        stack: None,
+        profiles: vec![],
+        limits: vec![],
+        template: false,
+        docker_context: None,
+        docker_host: None,
     };
 
     belljar_core::tmux::ensure_session(&s).unwrap();
@@ -39,6 +48,7 @@ fn tmux_helpers_call_binary() {
     belljar_core::tmux::select_layout(&s.tmux_session, "tiled").unwrap();
     // attach too
     belljar_core::tmux::attach(&s.tmux_session).unwrap();
+    belljar_core::tmux::set_environment(&s.tmux_session, &[("K".into(), "v w".into())]).unwrap();
 
     let logged = fs::read_to_string(&log).unwrap();
     assert!(logged.contains("has-session"));
@@ -47,4 +57,17 @@ fn tmux_helpers_call_binary() {
     assert!(logged.contains("select-layout"));
     assert!(logged.contains("attach-session"));
     assert!(logged.contains("set-option"));
+    // No `new-session -e`, which needs tmux 3.2
+    assert!(!logged.contains(" -e "), "{logged}");
+    let lines: Vec<&str> = logged.lines().collect();
+    let set = lines
+        .iter()
+        .position(|l| *l == "set-environment -t sess BELLJAR_SESSION lab")
+        .unwrap();
+    let respawn = lines
+        .iter()
+        .position(|l| *l == "respawn-pane -k -t sess")
+        .unwrap();
+    assert!(set < respawn, "{logged}");
+    assert!(logged.contains("set-environment -t sess K v w"));
 }